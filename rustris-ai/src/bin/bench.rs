@@ -0,0 +1,60 @@
+use rustris_ai::evaluator::Weights;
+use rustris_ai::player::AiPlayer;
+use rustris_core::game::Game;
+use std::time::Instant;
+
+const USAGE: &str = "usage: rustris-bench [games] [max-pieces] [first-seed] [weights-file]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let numbers: Result<Vec<u64>, _> = args.iter().take(3).map(|a| a.parse::<u64>()).collect();
+    let numbers = match numbers {
+        Ok(numbers) if args.len() <= 4 => numbers,
+        _ => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+    let games = *numbers.first().unwrap_or(&10);
+    let max_pieces = *numbers.get(1).unwrap_or(&10000) as i32;
+    let first_seed = *numbers.get(2).unwrap_or(&0);
+    let weights = match args.get(3) {
+        Some(path) => match Weights::load(path) {
+            Ok(weights) => weights,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        },
+        None => Weights::default(),
+    };
+
+    let mut player = AiPlayer::new(weights);
+    let mut total_lines = 0;
+    let mut total_pieces = 0;
+    let t_start = Instant::now();
+
+    for seed in first_seed..first_seed + games {
+        let mut game = Game::with_seed(seed);
+        while !game.is_game_over() && game.stats().pieces < max_pieces {
+            player.play_piece(&mut game);
+        }
+        let stats = game.stats();
+        println!(
+            "seed {:5}: {:7} lines, {:7} pieces{}",
+            seed,
+            stats.cleared,
+            stats.pieces,
+            if game.is_game_over() { ", topped out" } else { "" }
+        );
+        total_lines += stats.cleared as u64;
+        total_pieces += stats.pieces as u64;
+    }
+
+    let seconds = t_start.elapsed().as_secs_f64();
+    println!(
+        "average: {:.1} lines per game, {:.0} pieces per second",
+        total_lines as f64 / games.max(1) as f64,
+        total_pieces as f64 / seconds
+    );
+}
@@ -0,0 +1,162 @@
+// A small TBP bot backed by the heuristic AI. It is mostly useful as a
+// stand-in for third-party bots when testing a TBP frontend.
+//
+// usage: rustris-tbp-bot [--rules rustris|guideline]
+
+use rustris_ai::evaluator::Weights;
+use rustris_ai::player::{spawned_piece, AiPlayer};
+use rustris_ai::tbp::{
+    board_from_rows, location_cells, move_of, piece_from_name, to_line, BotMessage, FrontendMessage, Move,
+};
+use rustris_core::board::BoardController;
+use rustris_core::model::{CellVal, TetrominoType};
+use rustris_core::placement::find_placements;
+use rustris_core::rotation::RotationSystem;
+use rustris_core::ruleset::Ruleset;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+
+struct BotState {
+    board: BoardController,
+    hold: Option<TetrominoType>,
+    queue: VecDeque<TetrominoType>,
+}
+
+fn main() {
+    // the rules the frontend plays by, for the moves its pieces can make
+    let ruleset = match rules() {
+        Ok(ruleset) => ruleset,
+        Err(err) => {
+            eprintln!("rustris-tbp-bot: {}", err);
+            return;
+        }
+    };
+    let player = AiPlayer::new(Weights::default());
+    let mut state: Option<BotState> = None;
+
+    send(&BotMessage::Info {
+        name: String::from("rustris-tbp-bot"),
+        version: String::from(env!("CARGO_PKG_VERSION")),
+        author: String::from("rustris"),
+        features: Vec::new(),
+    });
+
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let message: FrontendMessage = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("rustris-tbp-bot: ignoring message: {}", err);
+                continue;
+            }
+        };
+
+        match message {
+            FrontendMessage::Rules { .. } => send(&BotMessage::Ready),
+            FrontendMessage::Start(start) => {
+                state = Some(BotState {
+                    board: BoardController {
+                        board: board_from_rows(&start.board),
+                    },
+                    hold: start.hold.as_deref().and_then(piece_from_name),
+                    queue: start.queue.iter().filter_map(|p| piece_from_name(p)).collect(),
+                });
+            }
+            FrontendMessage::Suggest => {
+                let moves = match &state {
+                    Some(state) => suggest(&player, &ruleset, state),
+                    None => Vec::new(),
+                };
+                send(&BotMessage::Suggestion { moves });
+            }
+            FrontendMessage::Play { mv } => {
+                if let Some(state) = &mut state {
+                    play(state, &mv);
+                }
+            }
+            FrontendMessage::NewPiece { piece } => {
+                if let (Some(state), Some(t_type)) = (&mut state, piece_from_name(&piece)) {
+                    state.queue.push_back(t_type);
+                }
+            }
+            FrontendMessage::Stop => state = None,
+            FrontendMessage::Quit => break,
+        }
+    }
+}
+
+// TBP locations are those of the SRS shapes, so only rules with these shapes
+// can be played.
+fn rules() -> Result<Ruleset, String> {
+    let mut args = std::env::args().skip(1);
+    let mut ruleset = Ruleset::rustris();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--rules", Some(name)) => {
+                ruleset = Ruleset::by_name(&name).ok_or(format!("unknown rules '{}'", name))?;
+            }
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+    return match ruleset.rotation {
+        RotationSystem::Kickless | RotationSystem::Srs => Ok(ruleset),
+        _ => Err(format!("TBP can't describe the pieces of the {} rules", ruleset.name)),
+    };
+}
+
+fn send(message: &BotMessage) {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    // the frontend going away ends the bot on the next read anyway
+    let _ = writeln!(out, "{}", to_line(message));
+    let _ = out.flush();
+}
+
+fn suggest(player: &AiPlayer, ruleset: &Ruleset, state: &BotState) -> Vec<Move> {
+    let current = match state.queue.front() {
+        Some(current) => *current,
+        None => return Vec::new(),
+    };
+    let board = &state.board.board;
+    // only placements TBP has a location for can be suggested
+    let placements = find_placements(board, &spawned_piece(current, &ruleset.rotation, 1))
+        .into_iter()
+        .filter(|placement| move_of(placement, board).is_some())
+        .collect();
+    return player
+        .best_of(board, ruleset, placements)
+        .and_then(|placement| move_of(&placement, board))
+        .into_iter()
+        .collect();
+}
+
+fn play(state: &mut BotState, mv: &Move) {
+    let t_type = match piece_from_name(&mv.location.piece) {
+        Some(t_type) => t_type,
+        None => return,
+    };
+    // a piece other than the current one means the hold slot was used
+    if state.queue.front() != Some(&t_type) {
+        let current = state.queue.pop_front();
+        if state.hold.is_none() {
+            state.queue.pop_front();
+        }
+        state.hold = current;
+    } else {
+        state.queue.pop_front();
+    }
+
+    let location = &mv.location;
+    let board = &mut state.board.board;
+    let cells = location_cells(t_type, location.orientation, location.x, location.y, board);
+    for (x, y) in cells.unwrap_or_default() {
+        if x >= 0 && x < board.width() && y >= 0 && y < board.height() {
+            board.set(x, y, CellVal::Color1);
+        }
+    }
+    state.board.clear_full_rows();
+}
@@ -0,0 +1,132 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rustris_ai::evaluator::Weights;
+use rustris_ai::trainer::{Trainer, TrainerConfig};
+
+const USAGE: &str = "usage: rustris-train [--generations N] [--population N] [--games N] [--pieces N] \
+                     [--threads N] [--seed N] [--start FILE] [--out FILE]";
+
+struct Args {
+    generations: u32,
+    seed: u64,
+    start: Option<String>,
+    out: String,
+    config: TrainerConfig,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut iter: I) -> Result<Args, String> {
+    let mut args = Args {
+        generations: 20,
+        seed: 0,
+        start: None,
+        out: String::from("weights.txt"),
+        config: TrainerConfig::new(),
+    };
+
+    while let Some(flag) = iter.next() {
+        let value = match iter.next() {
+            Some(value) => value,
+            None => return Err(format!("missing value for {}", flag)),
+        };
+        let number = value.parse::<u64>();
+        let bad_number = || format!("bad number for {}: {}", flag, value);
+        match flag.as_str() {
+            "--generations" => args.generations = number.map_err(|_| bad_number())? as u32,
+            "--population" => args.config.population = number.map_err(|_| bad_number())?.max(2) as usize,
+            // averages are taken over the games, and no pieces means no lines
+            "--games" => args.config.games_per_candidate = positive(number).ok_or_else(bad_number)?,
+            "--pieces" => args.config.max_pieces = positive(number).ok_or_else(bad_number)? as i32,
+            "--threads" => args.config.threads = number.map_err(|_| bad_number())? as usize,
+            "--seed" => args.seed = number.map_err(|_| bad_number())?,
+            "--start" => args.start = Some(value),
+            "--out" => args.out = value,
+            _ => return Err(format!("unknown argument '{}'", flag)),
+        }
+    }
+    return Ok(args);
+}
+
+fn positive<E>(number: Result<u64, E>) -> Option<u64> {
+    return number.ok().filter(|number| *number > 0);
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            println!("{}\n{}", err, USAGE);
+            return;
+        }
+    };
+
+    println!(
+        "population {}, {} games of up to {} pieces each, {} threads",
+        args.config.population, args.config.games_per_candidate, args.config.max_pieces, args.config.threads
+    );
+    let games = args.config.games_per_candidate;
+    let mut trainer = Trainer::new(args.config, StdRng::seed_from_u64(args.seed));
+    if let Some(path) = &args.start {
+        match Weights::load(path) {
+            Ok(weights) => trainer.seed_with(weights),
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        }
+    }
+
+    let mut best_ever: Option<(f64, Weights)> = None;
+    for _ in 0..args.generations {
+        let report = trainer.run_generation();
+        let best_lines = report.best.fitness as f64 / games as f64;
+        // this generation's games differ from the last one's, so the best
+        // candidates are compared on the validation games instead
+        let validated = trainer.validate(report.best.weights);
+        println!(
+            "generation {:3}: best {:8.1} lines ({:8.1} validated), average {:8.1} lines",
+            report.generation, best_lines, validated, report.average_lines
+        );
+
+        if best_ever.is_none_or(|(lines, _)| validated > lines) {
+            best_ever = Some((validated, report.best.weights));
+            // written every time so an interrupted run keeps its progress
+            if let Err(err) = std::fs::write(&args.out, report.best.weights.to_string()) {
+                println!("can't write {}: {}", args.out, err);
+                return;
+            }
+        }
+    }
+
+    if let Some((lines, weights)) = best_ever {
+        println!(
+            "best weights ({:.1} validated lines per game) in {}:\n{}",
+            lines, args.out, weights
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        return parse_args(args.iter().map(|arg| String::from(*arg)));
+    }
+
+    #[test]
+    fn games_and_pieces_must_be_positive() {
+        assert!(parse(&["--games", "0"]).is_err());
+        assert!(parse(&["--games", "-1"]).is_err());
+        assert!(parse(&["--pieces", "0"]).is_err());
+        let args = parse(&["--games", "3", "--pieces", "100"]).unwrap();
+        assert_eq!(args.config.games_per_candidate, 3);
+        assert_eq!(args.config.max_pieces, 100);
+    }
+
+    #[test]
+    fn flags_need_a_value() {
+        assert!(parse(&["--generations"]).is_err());
+        assert!(parse(&["--colors", "3"]).is_err());
+        assert_eq!(parse(&["--generations", "7"]).unwrap().generations, 7);
+    }
+}
@@ -0,0 +1,387 @@
+// A gym-style environment around `Game` for reinforcement learning, and a
+// vectorized version stepping many games at once. Nothing is allocated per
+// step once the placement buffers have grown to their working size.
+
+use crate::evaluator::Features;
+use rustris_core::game::{Game, PREVIEW_SIZE};
+use rustris_core::model::{CellVal, TetrominoRotation, TetrominoType, UserInput, BOARD_DIM_X, BOARD_DIM_Y};
+use rustris_core::placement::{Placement, PlacementSearch};
+use rustris_core::ruleset::Ruleset;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    // index into `placements()` of the current state; the piece locks there
+    Placement(usize),
+    // a single input, followed by one step of game time
+    Input(UserInput),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Observation {
+    // 1 for an occupied cell, 0 for a free one; rows top to bottom
+    pub board: [[u8; BOARD_DIM_X]; BOARD_DIM_Y],
+    pub piece: TetrominoType,
+    pub piece_rotation: TetrominoRotation,
+    // offset of the piece's 4x4 box, as in `Tetromino`
+    pub piece_x: i32,
+    pub piece_y: i32,
+    pub queue: [TetrominoType; PREVIEW_SIZE],
+    // the piece in the hold slot, see `Ruleset::hold`
+    pub hold: Option<TetrominoType>,
+}
+
+impl Default for Observation {
+    fn default() -> Observation {
+        return Observation::new();
+    }
+}
+
+impl Observation {
+    pub fn new() -> Observation {
+        return Observation {
+            board: [[0; BOARD_DIM_X]; BOARD_DIM_Y],
+            piece: TetrominoType::I,
+            piece_rotation: TetrominoRotation::Rot0,
+            piece_x: 0,
+            piece_y: 0,
+            queue: [TetrominoType::I; PREVIEW_SIZE],
+            hold: None,
+        };
+    }
+
+    pub fn update(&mut self, game: &Game) {
+        let board = game.current_board();
+        for (y, row) in self.board.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = match board.at(x as i32, y as i32) {
+                    CellVal::Free => 0,
+                    _ => 1,
+                };
+            }
+        }
+        let piece = game.active_piece();
+        self.piece = piece.t_type;
+        self.piece_rotation = piece.rotation;
+        self.piece_x = piece.offset.0;
+        self.piece_y = piece.offset.1;
+        for (slot, t_type) in self.queue.iter_mut().zip(game.preview_iter()) {
+            *slot = t_type;
+        }
+        self.hold = game.held_piece();
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct StepInfo {
+    pub lines_cleared: i32,
+    pub pieces_locked: i32,
+    // the action was out of range or came after the game ended; nothing happened
+    pub invalid_action: bool,
+}
+
+// Reward of a step: the sum of all terms that apply. The board terms are
+// multiplied with the change of the feature during the step, so a negative
+// `holes` weight punishes creating holes and rewards uncovering them.
+#[derive(Copy, Clone, Debug)]
+pub struct RewardConfig {
+    // indexed by the number of lines cleared by one piece
+    pub line_clears: [f64; 5],
+    pub per_piece: f64,
+    pub game_over: f64,
+    pub invalid_action: f64,
+    pub aggregate_height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+}
+
+impl Default for RewardConfig {
+    fn default() -> RewardConfig {
+        return RewardConfig::new();
+    }
+}
+
+impl RewardConfig {
+    pub fn new() -> RewardConfig {
+        return RewardConfig {
+            line_clears: [0.0, 1.0, 3.0, 5.0, 8.0],
+            per_piece: 0.01,
+            game_over: -1.0,
+            invalid_action: -0.1,
+            aggregate_height: 0.0,
+            holes: 0.0,
+            bumpiness: 0.0,
+        };
+    }
+}
+
+pub struct Env {
+    game: Game,
+    pub reward: RewardConfig,
+    search: PlacementSearch,
+    searched: bool,
+    features: Features,
+}
+
+impl Env {
+    // Games play by `ruleset`; hold with `Action::Input(UserInput::Hold)`
+    // where it has a hold slot.
+    pub fn new(reward: RewardConfig, ruleset: Ruleset) -> Env {
+        let mut game = Game::with_seed(0);
+        game.set_ruleset(ruleset);
+        let features = Features::of(game.current_board(), 0);
+        return Env {
+            game,
+            reward,
+            search: PlacementSearch::new(),
+            searched: false,
+            features,
+        };
+    }
+
+    pub fn game(&self) -> &Game {
+        return &self.game;
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.restart(seed);
+        return self.observation();
+    }
+
+    pub fn step(&mut self, action: Action) -> (Observation, f64, bool, StepInfo) {
+        let (reward, info) = self.apply(action);
+        return (self.observation(), reward, self.game.is_game_over(), info);
+    }
+
+    pub fn observation(&self) -> Observation {
+        let mut observation = Observation::new();
+        observation.update(&self.game);
+        return observation;
+    }
+
+    // The placements of the current piece, i.e. the valid `Action::Placement`
+    // indices. They are searched once per state, when first asked for.
+    pub fn placements(&mut self) -> &[Placement] {
+        if !self.searched {
+            self.search.run(self.game.current_board(), self.game.active_piece());
+            self.searched = true;
+        }
+        return self.search.placements();
+    }
+
+    fn restart(&mut self, seed: u64) {
+        self.game.reset_with_seed(seed);
+        self.searched = false;
+        self.features = Features::of(self.game.current_board(), 0);
+    }
+
+    fn apply(&mut self, action: Action) -> (f64, StepInfo) {
+        if self.game.is_game_over() {
+            return self.invalid();
+        }
+        let lines_before = self.game.stats().cleared;
+        let pieces_before = self.game.stats().pieces;
+
+        match action {
+            Action::Placement(index) => {
+                if index >= self.placements().len() {
+                    return self.invalid();
+                }
+                for i in 0..self.search.placements()[index].path.len() {
+                    let input = self.search.placements()[index].path[i];
+                    self.game.handle_input(&input);
+                }
+                self.game.handle_input(&UserInput::MoveDown);
+                // rules where soft drops don't lock wait out the lock delay
+                while self.game.stats().pieces == pieces_before && !self.game.is_finished() {
+                    self.game.run_step();
+                }
+            }
+            Action::Input(UserInput::Reset) => return self.invalid(),
+            Action::Input(input) => {
+                self.game.handle_input(&input);
+                self.game.run_step();
+            }
+        }
+        self.searched = false;
+
+        let info = StepInfo {
+            lines_cleared: self.game.stats().cleared - lines_before,
+            pieces_locked: self.game.stats().pieces - pieces_before,
+            invalid_action: false,
+        };
+        let config = &self.reward;
+        let mut reward = config.line_clears[info.lines_cleared.clamp(0, 4) as usize];
+        reward += config.per_piece * info.pieces_locked as f64;
+        if self.game.is_game_over() {
+            reward += config.game_over;
+        }
+        if info.pieces_locked > 0 {
+            let features = Features::of(self.game.current_board(), info.lines_cleared);
+            reward += config.aggregate_height * (features.aggregate_height - self.features.aggregate_height) as f64
+                + config.holes * (features.holes - self.features.holes) as f64
+                + config.bumpiness * (features.bumpiness - self.features.bumpiness) as f64;
+            self.features = features;
+        }
+        return (reward, info);
+    }
+
+    fn invalid(&self) -> (f64, StepInfo) {
+        let info = StepInfo {
+            invalid_action: true,
+            ..StepInfo::default()
+        };
+        return (self.reward.invalid_action, info);
+    }
+}
+
+// Many environments stepped together. Results are written to the public
+// buffers, which keep their size, instead of being returned. A game that
+// ends is restarted right away with the next seed; its `dones` entry is set
+// and its observation is the first one of the new game.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    next_seed: u64,
+    pub observations: Vec<Observation>,
+    pub rewards: Vec<f64>,
+    pub dones: Vec<bool>,
+    pub infos: Vec<StepInfo>,
+}
+
+impl VecEnv {
+    pub fn new(count: usize, reward: RewardConfig, ruleset: &Ruleset) -> VecEnv {
+        return VecEnv {
+            envs: (0..count).map(|_| Env::new(reward, ruleset.clone())).collect(),
+            next_seed: 0,
+            observations: vec![Observation::new(); count],
+            rewards: vec![0.0; count],
+            dones: vec![false; count],
+            infos: vec![StepInfo::default(); count],
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.envs.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.envs.is_empty();
+    }
+
+    pub fn env(&self, index: usize) -> &Env {
+        return &self.envs[index];
+    }
+
+    pub fn placements(&mut self, index: usize) -> &[Placement] {
+        return self.envs[index].placements();
+    }
+
+    // Every game started from now on gets the next seed, counting up from `seed`.
+    pub fn reset(&mut self, seed: u64) {
+        self.next_seed = seed;
+        for i in 0..self.envs.len() {
+            self.restart(i);
+            self.rewards[i] = 0.0;
+            self.dones[i] = false;
+            self.infos[i] = StepInfo::default();
+        }
+    }
+
+    pub fn step(&mut self, actions: &[Action]) {
+        assert_eq!(actions.len(), self.envs.len(), "one action per environment");
+        for (i, action) in actions.iter().enumerate() {
+            let (reward, info) = self.envs[i].apply(*action);
+            self.rewards[i] = reward;
+            self.infos[i] = info;
+            self.dones[i] = self.envs[i].game.is_game_over();
+            if self.dones[i] {
+                self.restart(i);
+            } else {
+                self.observations[i].update(&self.envs[i].game);
+            }
+        }
+    }
+
+    fn restart(&mut self, index: usize) {
+        self.envs[index].restart(self.next_seed);
+        self.next_seed += 1;
+        self.observations[index].update(&self.envs[index].game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_by_the_ruleset_and_observes_the_hold() {
+        let mut env = Env::new(RewardConfig::new(), Ruleset::guideline());
+        let first = env.reset(1);
+        let (observation, _, _, info) = env.step(Action::Input(UserInput::Hold));
+        assert!(!info.invalid_action);
+        assert_eq!(observation.hold, Some(first.piece));
+
+        // soft drops don't lock here, placements still do
+        let (_, _, _, info) = env.step(Action::Placement(0));
+        assert_eq!(info.pieces_locked, 1);
+    }
+
+    #[test]
+    fn out_of_range_placements_are_invalid() {
+        let mut env = Env::new(RewardConfig::new(), Ruleset::rustris());
+        env.reset(1);
+        let count = env.placements().len();
+        let (_, reward, done, info) = env.step(Action::Placement(count));
+        assert!(info.invalid_action && !done);
+        assert_eq!(reward, env.reward.invalid_action);
+        assert_eq!(env.game().stats().pieces, 0);
+    }
+
+    #[test]
+    fn reward_terms_add_up() {
+        let reward = RewardConfig {
+            per_piece: 0.5,
+            aggregate_height: -0.1,
+            holes: -0.7,
+            bumpiness: -0.3,
+            ..RewardConfig::new()
+        };
+        let mut env = Env::new(reward, Ruleset::rustris());
+        env.reset(1);
+        for _ in 0..10 {
+            let before = Features::of(env.game().current_board(), 0);
+            let last = env.placements().len() - 1;
+            let (_, total, done, info) = env.step(Action::Placement(last));
+            let after = Features::of(env.game().current_board(), info.lines_cleared);
+            let mut expected = reward.line_clears[info.lines_cleared as usize] + reward.per_piece;
+            expected += reward.aggregate_height * (after.aggregate_height - before.aggregate_height) as f64;
+            expected += reward.holes * (after.holes - before.holes) as f64;
+            expected += reward.bumpiness * (after.bumpiness - before.bumpiness) as f64;
+            if done {
+                expected += reward.game_over;
+            }
+            assert!((total - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn vec_env_restarts_finished_games_with_the_next_seed() {
+        let mut envs = VecEnv::new(2, RewardConfig::new(), &Ruleset::rustris());
+        envs.reset(10);
+        let mut steps = 0;
+        while !envs.dones[0] {
+            envs.step(&[Action::Placement(0), Action::Input(UserInput::MoveLeft)]);
+            assert!(!envs.dones[1]);
+            steps += 1;
+            assert!(steps < 1000, "stacking in one place ends the game");
+        }
+
+        // seeds 10 and 11 went to the first games
+        let mut fresh = Env::new(RewardConfig::new(), Ruleset::rustris());
+        let expected = fresh.reset(12);
+        let observation = &envs.observations[0];
+        assert_eq!(envs.env(0).game().stats().pieces, 0);
+        assert_eq!(observation.board, expected.board);
+        assert_eq!(observation.piece, expected.piece);
+        assert_eq!(observation.queue, expected.queue);
+    }
+}
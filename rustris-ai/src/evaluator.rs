@@ -0,0 +1,173 @@
+use rustris_core::model::{Board, CellVal, BOARD_DIM_X};
+use std::fmt;
+
+const WEIGHT_NAMES: [&str; 5] = ["aggregate_height", "holes", "bumpiness", "wells", "lines_cleared"];
+
+#[derive(Copy, Clone, Debug)]
+pub struct Weights {
+    pub aggregate_height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub lines_cleared: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        return Weights::new();
+    }
+}
+
+impl Weights {
+    // A well-known hand-tuned starting point, good enough to play for a long time.
+    pub fn new() -> Weights {
+        return Weights {
+            aggregate_height: -0.510066,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+            wells: -0.1,
+            lines_cleared: 0.760666,
+        };
+    }
+
+    pub fn to_array(&self) -> [f64; 5] {
+        return [
+            self.aggregate_height,
+            self.holes,
+            self.bumpiness,
+            self.wells,
+            self.lines_cleared,
+        ];
+    }
+
+    pub fn from_array(values: [f64; 5]) -> Weights {
+        return Weights {
+            aggregate_height: values[0],
+            holes: values[1],
+            bumpiness: values[2],
+            wells: values[3],
+            lines_cleared: values[4],
+        };
+    }
+
+    // Reads the `name = value` lines written by `Display`. Weights that are
+    // missing keep their default value; '#' starts a comment.
+    pub fn parse(text: &str) -> Result<Weights, String> {
+        let mut values = Weights::new().to_array();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=').map(|part| part.trim());
+            let name = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("");
+            let index = match WEIGHT_NAMES.iter().position(|n| *n == name) {
+                Some(index) => index,
+                None => return Err(format!("line {}: unknown weight '{}'", number + 1, name)),
+            };
+            values[index] = match value.parse::<f64>() {
+                Ok(value) => value,
+                Err(_) => return Err(format!("line {}: bad value '{}'", number + 1, value)),
+            };
+        }
+        return Ok(Weights::from_array(values));
+    }
+
+    pub fn load(path: &str) -> Result<Weights, String> {
+        return match std::fs::read_to_string(path) {
+            Ok(text) => Weights::parse(&text).map_err(|err| format!("{}: {}", path, err)),
+            Err(err) => Err(format!("{}: {}", path, err)),
+        };
+    }
+}
+
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in WEIGHT_NAMES.iter().zip(self.to_array().iter()) {
+            writeln!(f, "{} = {}", name, value)?;
+        }
+        return Ok(());
+    }
+}
+
+pub struct Features {
+    pub aggregate_height: i32,
+    pub holes: i32,
+    pub bumpiness: i32,
+    pub wells: i32,
+    pub lines_cleared: i32,
+}
+
+impl Features {
+    pub fn of(board: &Board, lines_cleared: i32) -> Features {
+        let mut heights = [0; BOARD_DIM_X];
+        for (x, height) in heights.iter_mut().enumerate() {
+            *height = column_height(board, x as i32);
+        }
+
+        let mut holes = 0;
+        for x in 0..board.width() {
+            for y in (board.height() - heights[x as usize])..board.height() {
+                if let CellVal::Free = board.at(x, y) {
+                    holes += 1;
+                }
+            }
+        }
+
+        let mut wells = 0;
+        for x in 0..heights.len() {
+            // the board walls count as infinitely high neighbors
+            let left = if x == 0 { None } else { Some(heights[x - 1]) };
+            let right = heights.get(x + 1).copied();
+            let rim = match (left, right) {
+                (Some(l), Some(r)) => l.min(r),
+                (Some(l), None) => l,
+                (None, Some(r)) => r,
+                (None, None) => continue,
+            };
+            if rim > heights[x] {
+                wells += rim - heights[x];
+            }
+        }
+
+        return Features {
+            aggregate_height: heights.iter().sum(),
+            holes,
+            bumpiness: heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum(),
+            wells,
+            lines_cleared,
+        };
+    }
+}
+
+fn column_height(board: &Board, x: i32) -> i32 {
+    for y in 0..board.height() {
+        match board.at(x, y) {
+            CellVal::Free => (),
+            _ => return board.height() - y,
+        }
+    }
+    return 0;
+}
+
+pub struct Evaluator {
+    pub weights: Weights,
+}
+
+impl Evaluator {
+    pub fn new(weights: Weights) -> Evaluator {
+        return Evaluator { weights };
+    }
+
+    // Scores a board after a piece locked on it; higher is better.
+    pub fn evaluate(&self, board: &Board, lines_cleared: i32) -> f64 {
+        let f = Features::of(board, lines_cleared);
+        let w = &self.weights;
+        return w.aggregate_height * f.aggregate_height as f64
+            + w.holes * f.holes as f64
+            + w.bumpiness * f.bumpiness as f64
+            + w.wells * f.wells as f64
+            + w.lines_cleared * f.lines_cleared as f64;
+    }
+}
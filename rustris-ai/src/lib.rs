@@ -0,0 +1,6 @@
+pub mod env;
+pub mod evaluator;
+pub mod pc_solver;
+pub mod player;
+pub mod tbp;
+pub mod trainer;
@@ -0,0 +1,341 @@
+use crate::player::spawned_piece;
+use rustris_core::model::{Board, CellVal, Tetromino, TetrominoRotation, TetrominoType};
+use rustris_core::placement::{find_placements, Placement};
+use rustris_core::ruleset::Ruleset;
+use std::collections::HashSet;
+
+// The board rows of the search area are packed into a u64.
+const MAX_HEIGHT: i32 = 6;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum SearchMode {
+    FirstSolution,
+    AllSolutions,
+}
+
+#[derive(Clone)]
+pub struct PcStep {
+    // the piece was taken from (or swapped with) the hold slot
+    pub hold: bool,
+    pub placement: Placement,
+}
+
+pub struct PcResult {
+    pub solutions: Vec<Vec<PcStep>>,
+    // false if the node budget ran out before the search was done
+    pub complete: bool,
+}
+
+pub struct PcSolver {
+    pub height: i32,
+    pub mode: SearchMode,
+    pub max_nodes: usize,
+    // the active piece came out of the hold slot, so it can't be held again
+    pub hold_used: bool,
+    nodes: usize,
+    failed: HashSet<(u64, i32, usize, Option<TetrominoType>)>,
+    steps: Vec<PcStep>,
+    solutions: Vec<Vec<PcStep>>,
+}
+
+impl PcSolver {
+    // Searches perfect clears that use at most the bottom `height` rows (at most 6).
+    pub fn new(height: i32, mode: SearchMode) -> PcSolver {
+        return PcSolver {
+            height: height.clamp(1, MAX_HEIGHT),
+            mode,
+            max_nodes: 100_000,
+            hold_used: false,
+            nodes: 0,
+            failed: HashSet::new(),
+            steps: Vec::new(),
+            solutions: Vec::new(),
+        };
+    }
+
+    // `current` is the active piece where it is now; `queue` are the
+    // upcoming pieces as they will spawn, see `Game::preview_pieces`. Pieces
+    // lock by `ruleset`, chains and color groups included.
+    pub fn solve(
+        &mut self,
+        board: &Board,
+        ruleset: &Ruleset,
+        current: &Tetromino,
+        hold: Option<TetrominoType>,
+        queue: &[Tetromino],
+    ) -> PcResult {
+        self.nodes = 0;
+        self.failed.clear();
+        self.steps.clear();
+        self.solutions.clear();
+
+        let mut pieces = vec![current.clone()];
+        pieces.extend_from_slice(queue);
+        let sizes: Vec<usize> = pieces
+            .iter()
+            .map(|piece| piece.t_type)
+            .chain(hold)
+            .map(|t_type| current.system.cells(t_type, TetrominoRotation::Rot0).len())
+            .map(|minos| minos * (current.scale * current.scale) as usize)
+            .collect();
+        let search = Search {
+            ruleset,
+            pieces: &pieces,
+            size: Some(sizes[0])
+                .filter(|size| sizes.iter().all(|s| s == size))
+                .filter(|_| !ruleset.cascade && ruleset.color_match.is_none()),
+        };
+
+        // smaller perfect clears first, they need fewer pieces
+        let mut complete = true;
+        for limit in 1..=self.height {
+            if !rows_above_empty(board, limit) {
+                continue;
+            }
+            self.failed.clear();
+            complete = self.search(&search, board, limit, 0, hold);
+            let done = self.mode == SearchMode::FirstSolution && !self.solutions.is_empty();
+            if !complete || done {
+                break;
+            }
+        }
+
+        return PcResult {
+            solutions: std::mem::take(&mut self.solutions),
+            complete,
+        };
+    }
+
+    // Returns false if the search was aborted.
+    fn search(&mut self, s: &Search, board: &Board, limit: i32, next: usize, hold: Option<TetrominoType>) -> bool {
+        if limit == 0 {
+            self.solutions.push(self.steps.clone());
+            return true;
+        }
+        if !is_solvable(board, limit, s.pieces.len() - next + hold.is_some() as usize, s.size) {
+            return true;
+        }
+        let key = (pack_rows(board, limit), limit, next, hold);
+        if self.failed.contains(&key) {
+            return true;
+        }
+        self.nodes += 1;
+        if self.nodes > self.max_nodes {
+            return false;
+        }
+
+        let found_before = self.solutions.len();
+        // play the next piece, the held piece, or hold and play the one after;
+        // once the queue is used up, only the held piece is left. Holding
+        // takes rules with a hold slot, and once per piece.
+        let can_hold = s.ruleset.hold && (next > 0 || !self.hold_used);
+        let mut options: Vec<(usize, bool, usize, Option<TetrominoType>)> = Vec::new();
+        match (s.pieces.get(next), hold) {
+            (Some(piece), Some(held)) => {
+                options.push((next, false, next + 1, hold));
+                if can_hold && held != piece.t_type {
+                    options.push((usize::MAX, true, next + 1, Some(piece.t_type)));
+                }
+            }
+            (Some(piece), None) => {
+                options.push((next, false, next + 1, None));
+                if can_hold && next + 1 < s.pieces.len() {
+                    options.push((next + 1, true, next + 2, Some(piece.t_type)));
+                }
+            }
+            (None, Some(_)) if can_hold => options.push((usize::MAX, true, next, None)),
+            (None, _) => return true,
+        }
+
+        for (index, used_hold, after, new_hold) in options {
+            // a piece out of the hold slot always comes back unrotated
+            let piece = match index {
+                usize::MAX => spawned_piece(hold.unwrap(), &s.pieces[0].system, s.pieces[0].scale),
+                _ => s.pieces[index].clone(),
+            };
+            for placement in placements_within(board, &piece, limit) {
+                let (after_board, cleared) = placement.apply(board, s.ruleset);
+                self.steps.push(PcStep {
+                    hold: used_hold,
+                    placement,
+                });
+                let finished = self.search(s, &after_board, limit - cleared, after, new_hold);
+                self.steps.pop();
+                if !finished {
+                    return false;
+                }
+                if self.mode == SearchMode::FirstSolution && !self.solutions.is_empty() {
+                    return true;
+                }
+            }
+        }
+
+        if self.solutions.len() == found_before {
+            self.failed.insert(key);
+        }
+        return true;
+    }
+}
+
+struct Search<'a> {
+    ruleset: &'a Ruleset,
+    // the active piece where it is now, then the upcoming ones
+    pieces: &'a [Tetromino],
+    // the cells of every piece, unless they differ or the rules clear cells
+    // other than in full rows
+    size: Option<usize>,
+}
+
+// Placements completely inside the bottom `limit` rows; spin variants of the
+// same cells make no difference here and are left out.
+fn placements_within(board: &Board, piece: &Tetromino, limit: i32) -> Vec<Placement> {
+    let top = board.height() - limit;
+    let mut result: Vec<Placement> = Vec::new();
+    let mut seen: Vec<Vec<(i32, i32)>> = Vec::new();
+    for placement in find_placements(board, piece) {
+        let mut cells = placement.cells();
+        if cells.iter().any(|(_, y)| *y < top) {
+            continue;
+        }
+        cells.sort_unstable();
+        if seen.contains(&cells) {
+            continue;
+        }
+        seen.push(cells);
+        result.push(placement);
+    }
+    return result;
+}
+
+fn rows_above_empty(board: &Board, limit: i32) -> bool {
+    for y in 0..board.height() - limit {
+        for x in 0..board.width() {
+            if board.at(x, y) != CellVal::Free {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+// Cheap checks that rule out a perfect clear: enough pieces for the empty
+// cells, and every area walled off by completely filled columns must be
+// fillable with whole pieces.
+fn is_solvable(board: &Board, limit: i32, pieces_left: usize, size: Option<usize>) -> bool {
+    let size = match size {
+        Some(size) => size,
+        None => return true,
+    };
+    let top = board.height() - limit;
+    let mut empty_total = 0;
+    let mut empty_in_area = 0;
+    for x in 0..board.width() {
+        let empty_in_column = (top..board.height())
+            .filter(|y| board.at(x, *y) == CellVal::Free)
+            .count();
+        if empty_in_column == 0 {
+            if empty_in_area % size != 0 {
+                return false;
+            }
+            empty_in_area = 0;
+        }
+        empty_in_area += empty_in_column;
+        empty_total += empty_in_column;
+    }
+    return empty_in_area % size == 0 && empty_total / size <= pieces_left;
+}
+
+fn pack_rows(board: &Board, limit: i32) -> u64 {
+    let mut bits: u64 = 0;
+    for y in board.height() - limit..board.height() {
+        for x in 0..board.width() {
+            bits <<= 1;
+            if board.at(x, y) != CellVal::Free {
+                bits |= 1;
+            }
+        }
+    }
+    return bits;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bottom two rows filled but for `open` columns.
+    fn board_with_gap(open: &[i32]) -> Board {
+        let mut board = Board::new();
+        for y in board.height() - 2..board.height() {
+            for x in (0..board.width()).filter(|x| !open.contains(x)) {
+                board.set(x, y, CellVal::Garbage);
+            }
+        }
+        return board;
+    }
+
+    fn pieces(ruleset: &Ruleset, types: &[TetrominoType]) -> Vec<Tetromino> {
+        return types
+            .iter()
+            .map(|t_type| spawned_piece(*t_type, &ruleset.rotation, 1))
+            .collect();
+    }
+
+    fn solve(ruleset: &Ruleset, board: &Board, hold: Option<TetrominoType>, types: &[TetrominoType]) -> PcResult {
+        let pieces = pieces(ruleset, types);
+        let mut solver = PcSolver::new(4, SearchMode::FirstSolution);
+        return solver.solve(board, ruleset, &pieces[0], hold, &pieces[1..]);
+    }
+
+    #[test]
+    fn finds_a_two_line_perfect_clear() {
+        let ruleset = Ruleset::rustris();
+        let board = board_with_gap(&[0, 1, 2, 3]);
+        let result = solve(&ruleset, &board, None, &[TetrominoType::O, TetrominoType::O]);
+        assert!(result.complete);
+        let steps = &result.solutions[0];
+        assert_eq!(steps.len(), 2);
+        let mut cells: Vec<(i32, i32)> = steps.iter().flat_map(|step| step.placement.cells()).collect();
+        cells.sort_unstable();
+        let gap: Vec<(i32, i32)> = (0..4).flat_map(|x| vec![(x, 18), (x, 19)]).collect();
+        assert_eq!(cells, gap);
+    }
+
+    #[test]
+    fn reports_no_solution_for_an_unsolvable_board() {
+        let ruleset = Ruleset::rustris();
+        // two single cells walled off from each other
+        let mut board = board_with_gap(&[]);
+        for x in 0..board.width() {
+            board.set(x, 18, CellVal::Free);
+        }
+        board.set(0, 19, CellVal::Free);
+        board.set(9, 19, CellVal::Free);
+        let result = solve(&ruleset, &board, None, &[TetrominoType::O; 3]);
+        assert!(result.complete);
+        assert!(result.solutions.is_empty());
+    }
+
+    #[test]
+    fn holds_only_where_the_rules_allow() {
+        // only the O fits, behind the I
+        let board = board_with_gap(&[4, 5]);
+        let types = [TetrominoType::I, TetrominoType::O];
+
+        let guideline = Ruleset::guideline();
+        let result = solve(&guideline, &board, None, &types);
+        assert_eq!(result.solutions.len(), 1);
+        assert!(result.solutions[0][0].hold);
+        // the held O is swapped in right away
+        let result = solve(&guideline, &board, Some(TetrominoType::O), &types[..1]);
+        assert!(result.solutions[0][0].hold);
+
+        let result = solve(&Ruleset::rustris(), &board, None, &types);
+        assert!(result.complete && result.solutions.is_empty());
+
+        let pieces = pieces(&guideline, &types);
+        let mut solver = PcSolver::new(4, SearchMode::FirstSolution);
+        solver.hold_used = true;
+        let result = solver.solve(&board, &guideline, &pieces[0], None, &pieces[1..]);
+        assert!(result.complete && result.solutions.is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::evaluator::{Evaluator, Weights};
+use rustris_core::game::Game;
+use rustris_core::model::{Board, Tetromino, TetrominoRotation, TetrominoType, UserInput};
+use rustris_core::placement::{find_placements, Placement};
+use rustris_core::rotation::RotationSystem;
+use rustris_core::ruleset::Ruleset;
+
+pub struct AiPlayer {
+    evaluator: Evaluator,
+    target: Option<Target>,
+}
+
+// The placement the player decided on for the current piece, and the way there.
+struct Target {
+    piece_number: i32,
+    route: Route,
+}
+
+impl AiPlayer {
+    pub fn new(weights: Weights) -> AiPlayer {
+        return AiPlayer {
+            evaluator: Evaluator::new(weights),
+            target: None,
+        };
+    }
+
+    pub fn best_placement(&self, game: &Game) -> Option<Placement> {
+        return self.best_placement_on(game.current_board(), game.ruleset(), game.active_piece());
+    }
+
+    pub fn best_placement_on(&self, board: &Board, ruleset: &Ruleset, piece: &Tetromino) -> Option<Placement> {
+        return self.best_of(board, ruleset, find_placements(board, piece));
+    }
+
+    // The best of the given placements on `board`, locked by `ruleset`.
+    pub fn best_of(&self, board: &Board, ruleset: &Ruleset, placements: Vec<Placement>) -> Option<Placement> {
+        let mut best: Option<(f64, Placement)> = None;
+        for placement in placements {
+            let (after, cleared) = placement.apply(board, ruleset);
+            let score = self.evaluator.evaluate(&after, cleared);
+            let better = match &best {
+                Some((best_score, _)) => score > *best_score,
+                None => true,
+            };
+            if better {
+                best = Some((score, placement));
+            }
+        }
+        return best.map(|(_, placement)| placement);
+    }
+
+    // Inputs that move the active piece to the best placement and lock it there.
+    pub fn plan(&self, game: &Game) -> Vec<UserInput> {
+        return match self.best_placement(game) {
+            Some(placement) => {
+                let mut inputs = placement.path;
+                inputs.push(UserInput::MoveDown);
+                inputs
+            }
+            None => Vec::new(),
+        };
+    }
+
+    // Plays the active piece without waiting for gravity (headless play).
+    pub fn play_piece(&mut self, game: &mut Game) {
+        for input in self.plan(game) {
+            game.handle_input(&input);
+        }
+    }
+
+    // Returns one input per call, for playing in real time.
+    pub fn next_input(&mut self, game: &Game) -> UserInput {
+        if game.is_game_over() {
+            self.target = None;
+            return UserInput::Reset;
+        }
+
+        let piece_number = game.stats().pieces;
+        let have_target = match &self.target {
+            Some(target) => target.piece_number == piece_number,
+            None => false,
+        };
+        if !have_target {
+            self.target = self.best_placement(game).map(|placement| Target {
+                piece_number,
+                route: Route::new(game, &placement),
+            });
+        }
+
+        let target = match &mut self.target {
+            Some(target) => target,
+            None => return UserInput::NoInput,
+        };
+        return match target.route.next_input(game) {
+            Some(input) => input,
+            None => {
+                // the piece left the route, pick a new placement
+                self.target = None;
+                UserInput::NoInput
+            }
+        };
+    }
+}
+
+// The inputs that bring the active piece to a placement and lock it there,
+// worked out once and then played one by one.
+pub struct Route {
+    inputs: Vec<UserInput>,
+    // where the piece is before each input
+    positions: Vec<(i32, i32, TetrominoRotation)>,
+    next: usize,
+}
+
+impl Route {
+    pub fn new(game: &Game, placement: &Placement) -> Route {
+        let mut inputs = placement.path.clone();
+        inputs.push(UserInput::MoveDown);
+        let mut game = game.clone();
+        let mut positions = Vec::with_capacity(inputs.len());
+        for input in inputs.iter() {
+            positions.push(position_of(game.active_piece()));
+            game.handle_input(input);
+        }
+        return Route {
+            inputs,
+            positions,
+            next: 0,
+        };
+    }
+
+    // The next input, or `None` once the piece is off the route. Steps that
+    // gravity took already are skipped. With all inputs played, the piece
+    // waits for its lock delay.
+    pub fn next_input(&mut self, game: &Game) -> Option<UserInput> {
+        if self.next == self.inputs.len() {
+            return Some(UserInput::NoInput);
+        }
+        let current = position_of(game.active_piece());
+        let skipped = self.positions[self.next..].iter().position(|p| *p == current)?;
+        self.next += skipped + 1;
+        return Some(self.inputs[self.next - 1]);
+    }
+}
+
+fn position_of(piece: &Tetromino) -> (i32, i32, TetrominoRotation) {
+    return (piece.offset.0, piece.offset.1, piece.rotation);
+}
+
+// A piece as it appears at the top of the board out of the hold slot, or
+// whenever the ruleset spawns pieces unrotated, for planning ahead.
+pub fn spawned_piece(t_type: TetrominoType, system: &RotationSystem, scale: i32) -> Tetromino {
+    let mut piece = Tetromino::new();
+    piece.t_type = t_type;
+    piece.system = system.clone();
+    piece.scale = scale;
+    piece.rotation = TetrominoRotation::Rot0;
+    piece.offset = piece.spawn_offset();
+    return piece;
+}
@@ -0,0 +1,329 @@
+// Messages and coordinates of the Tetris Bot Protocol (TBP), a JSON-lines
+// protocol spoken over stdin/stdout between a frontend and a bot process.
+// Coordinates follow TBP: x to the right, y up from the bottom row, and the
+// location of a piece is the position of its SRS rotation center.
+
+use rustris_core::model::{Board, CellVal, TetrominoRotation, TetrominoType};
+use rustris_core::placement::Placement;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+pub const BOARD_ROWS: usize = 40;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        randomizer: Option<String>,
+    },
+    Start(Start),
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: String,
+    },
+    Stop,
+    Quit,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Start {
+    pub hold: Option<String>,
+    // the first piece is the one to play now
+    pub queue: Vec<String>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    // BOARD_ROWS rows of 10 cells, bottom row first
+    pub board: Vec<Vec<Option<String>>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Move {
+    pub location: Location,
+    #[serde(default = "no_spin")]
+    pub spin: String,
+}
+
+fn no_spin() -> String {
+    return String::from("none");
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Location {
+    #[serde(rename = "type")]
+    pub piece: String,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+// `None` for the pieces of a custom set past the seven tetrominoes, which TBP
+// has no name for.
+pub fn piece_name(t_type: TetrominoType) -> Option<String> {
+    let name = match t_type {
+        TetrominoType::I => "I",
+        TetrominoType::O => "O",
+        TetrominoType::T => "T",
+        TetrominoType::J => "J",
+        TetrominoType::L => "L",
+        TetrominoType::S => "S",
+        TetrominoType::Z => "Z",
+        _ => return None,
+    };
+    return Some(String::from(name));
+}
+
+pub fn piece_from_name(name: &str) -> Option<TetrominoType> {
+    return match name {
+        "I" => Some(TetrominoType::I),
+        "O" => Some(TetrominoType::O),
+        "T" => Some(TetrominoType::T),
+        "J" => Some(TetrominoType::J),
+        "L" => Some(TetrominoType::L),
+        "S" => Some(TetrominoType::S),
+        "Z" => Some(TetrominoType::Z),
+        _ => None,
+    };
+}
+
+pub fn orientation_of(rotation: TetrominoRotation) -> Orientation {
+    return match rotation {
+        TetrominoRotation::Rot0 => Orientation::North,
+        TetrominoRotation::Rot1 => Orientation::East,
+        TetrominoRotation::Rot2 => Orientation::South,
+        TetrominoRotation::Rot3 => Orientation::West,
+    };
+}
+
+// Mino offsets from the rotation center in north orientation, y up.
+fn north_minos(t_type: TetrominoType) -> Option<[(i32, i32); 4]> {
+    let minos = match t_type {
+        TetrominoType::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        TetrominoType::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        TetrominoType::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        TetrominoType::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        TetrominoType::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        TetrominoType::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        TetrominoType::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        _ => return None,
+    };
+    return Some(minos);
+}
+
+// Board cells (y down, as in `Board`) covered by a piece at a TBP location,
+// or `None` if TBP doesn't know the piece.
+pub fn location_cells(
+    t_type: TetrominoType,
+    orientation: Orientation,
+    x: i32,
+    y: i32,
+    board: &Board,
+) -> Option<Vec<(i32, i32)>> {
+    let mut cells: Vec<(i32, i32)> = north_minos(t_type)?
+        .iter()
+        .map(|&(dx, dy)| match orientation {
+            Orientation::North => (dx, dy),
+            Orientation::East => (dy, -dx),
+            Orientation::South => (-dx, -dy),
+            Orientation::West => (-dy, dx),
+        })
+        .map(|(dx, dy)| (x + dx, board.height() - 1 - (y + dy)))
+        .collect();
+    cells.sort_unstable();
+    return Some(cells);
+}
+
+// The TBP move describing a placement on the given board, or `None` if no
+// TBP location covers the same cells, as with some kicks rustris has.
+pub fn move_of(placement: &Placement, board: &Board) -> Option<Move> {
+    let piece = piece_name(placement.t_type)?;
+    let orientation = orientation_of(placement.rotation);
+    let mut cells = placement.cells();
+    cells.sort_unstable();
+
+    // one of the minos is the rotation center
+    let (x, y) = cells
+        .iter()
+        .map(|&(cx, cy)| (cx, board.height() - 1 - cy))
+        .find(|&(x, y)| location_cells(placement.t_type, orientation, x, y, board).as_ref() == Some(&cells))?;
+
+    let spin = match (placement.spin, placement.t_type) {
+        (true, TetrominoType::T) => "full",
+        _ => "none",
+    };
+    return Some(Move {
+        location: Location {
+            piece,
+            orientation,
+            x,
+            y,
+        },
+        spin: String::from(spin),
+    });
+}
+
+pub fn board_rows(board: &Board) -> Vec<Vec<Option<String>>> {
+    let mut rows = Vec::with_capacity(BOARD_ROWS);
+    for y in (0..board.height()).rev() {
+        let row = (0..board.width())
+            .map(|x| match board.at(x, y) {
+                CellVal::Free => None,
+                _ => Some(String::from("G")),
+            })
+            .collect();
+        rows.push(row);
+    }
+    while rows.len() < BOARD_ROWS {
+        rows.push(vec![None; board.width() as usize]);
+    }
+    return rows;
+}
+
+// Inverse of `board_rows`; rows above the rustris board are dropped.
+pub fn board_from_rows(rows: &[Vec<Option<String>>]) -> Board {
+    let mut board = Board::new();
+    for (row_index, row) in rows.iter().enumerate().take(board.height() as usize) {
+        let y = board.height() - 1 - row_index as i32;
+        for (x, cell) in row.iter().enumerate().take(board.width() as usize) {
+            if cell.is_some() {
+                board.set(x as i32, y, CellVal::Color1);
+            }
+        }
+    }
+    return board;
+}
+
+pub fn to_line(message: &impl Serialize) -> String {
+    return serde_json::to_string(message).unwrap();
+}
+
+// `None` for lines that aren't a message of the expected kind.
+pub fn from_line<T: DeserializeOwned>(line: &str) -> Option<T> {
+    return serde_json::from_str(line).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustris_core::ruleset::Ruleset;
+
+    const TYPES: [TetrominoType; 7] = [
+        TetrominoType::I,
+        TetrominoType::O,
+        TetrominoType::T,
+        TetrominoType::J,
+        TetrominoType::L,
+        TetrominoType::S,
+        TetrominoType::Z,
+    ];
+
+    #[test]
+    fn every_piece_and_rotation_round_trips() {
+        let board = Board::new();
+        let rotations = [
+            TetrominoRotation::Rot0,
+            TetrominoRotation::Rot1,
+            TetrominoRotation::Rot2,
+            TetrominoRotation::Rot3,
+        ];
+        for t_type in TYPES.iter() {
+            for rotation in rotations.iter() {
+                let placement = Placement {
+                    t_type: *t_type,
+                    x: 3,
+                    y: 8,
+                    rotation: *rotation,
+                    system: Ruleset::guideline().rotation,
+                    scale: 1,
+                    color: CellVal::Color1,
+                    spin: false,
+                    path: Vec::new(),
+                };
+                let mv = move_of(&placement, &board).unwrap();
+                let location = &mv.location;
+                assert_eq!(piece_from_name(&location.piece), Some(*t_type));
+                assert_eq!(location.orientation, orientation_of(*rotation));
+                let mut expected = placement.cells();
+                expected.sort_unstable();
+                let found = location_cells(*t_type, location.orientation, location.x, location.y, &board);
+                assert_eq!(found, Some(expected), "{:?} {:?}", t_type, rotation);
+            }
+        }
+    }
+
+    #[test]
+    fn i_and_o_turn_around_their_srs_centers() {
+        let flat = [(0, 0), (1, 0), (2, 0), (3, 0)];
+        let upright = [(0, 0), (0, 1), (0, 2), (0, 3)];
+        let square = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        // the piece, where TBP puts its center, and the minos it covers, y up
+        let cases = [
+            (TetrominoType::I, Orientation::North, (1, 0), flat),
+            (TetrominoType::I, Orientation::South, (2, 0), flat),
+            (TetrominoType::I, Orientation::East, (0, 2), upright),
+            (TetrominoType::I, Orientation::West, (0, 1), upright),
+            (TetrominoType::O, Orientation::North, (0, 0), square),
+            (TetrominoType::O, Orientation::East, (0, 1), square),
+            (TetrominoType::O, Orientation::South, (1, 1), square),
+            (TetrominoType::O, Orientation::West, (1, 0), square),
+        ];
+        let board = Board::new();
+        for (t_type, orientation, (x, y), minos) in cases.iter() {
+            let mut cells: Vec<(i32, i32)> = minos.iter().map(|&(x, y)| (x, board.height() - 1 - y)).collect();
+            cells.sort_unstable();
+            assert_eq!(location_cells(*t_type, *orientation, *x, *y, &board), Some(cells));
+        }
+    }
+
+    #[test]
+    fn names_and_boards_round_trip() {
+        for t_type in TYPES.iter() {
+            assert_eq!(piece_from_name(&piece_name(*t_type).unwrap()), Some(*t_type));
+        }
+        assert_eq!(piece_name(TetrominoType(7)), None);
+
+        let mut board = Board::new();
+        board.set(0, 19, CellVal::Color3);
+        board.set(9, 0, CellVal::Garbage);
+        let rows = board_rows(&board);
+        assert_eq!(rows.len(), BOARD_ROWS);
+        assert_eq!(rows[0][0], Some(String::from("G")));
+        assert_eq!(rows[19][9], Some(String::from("G")));
+        let back = board_from_rows(&rows);
+        let taken = back.cells.iter().flatten().filter(|cell| **cell != CellVal::Free);
+        assert_eq!(taken.count(), 2);
+        assert_ne!(back.at(0, 19), CellVal::Free);
+        assert_ne!(back.at(9, 0), CellVal::Free);
+    }
+}
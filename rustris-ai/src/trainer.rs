@@ -0,0 +1,278 @@
+use crate::evaluator::Weights;
+use crate::player::AiPlayer;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rustris_core::game::Game;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// First seed of the games every generation's best plays again, so that
+// candidates of different generations are compared on the same games. The
+// generations train on seeds far below.
+const VALIDATION_SEED: u64 = 1 << 32;
+
+pub struct TrainerConfig {
+    pub population: usize,
+    pub games_per_candidate: u64,
+    pub max_pieces: i32,
+    pub threads: usize,
+    // share of the population replaced by offspring every generation
+    pub replace_ratio: f64,
+    pub mutation_rate: f64,
+    pub mutation_step: f64,
+}
+
+impl Default for TrainerConfig {
+    fn default() -> TrainerConfig {
+        return TrainerConfig::new();
+    }
+}
+
+impl TrainerConfig {
+    pub fn new() -> TrainerConfig {
+        return TrainerConfig {
+            population: 50,
+            games_per_candidate: 5,
+            max_pieces: 500,
+            threads: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            replace_ratio: 0.3,
+            mutation_rate: 0.05,
+            mutation_step: 0.2,
+        };
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Candidate {
+    pub weights: Weights,
+    // total lines cleared over all games of the last evaluation
+    pub fitness: i64,
+}
+
+pub struct GenerationReport {
+    pub generation: u32,
+    pub best: Candidate,
+    pub average_lines: f64,
+}
+
+pub struct Trainer {
+    config: TrainerConfig,
+    population: Vec<Candidate>,
+    generation: u32,
+    rng: StdRng,
+}
+
+impl Trainer {
+    pub fn new(config: TrainerConfig, mut rng: StdRng) -> Trainer {
+        let population = (0..config.population)
+            .map(|_| Candidate {
+                weights: random_weights(&mut rng),
+                fitness: 0,
+            })
+            .collect();
+        return Trainer {
+            config,
+            population,
+            generation: 0,
+            rng,
+        };
+    }
+
+    // Replaces one random candidate, e.g. to continue from earlier results.
+    pub fn seed_with(&mut self, weights: Weights) {
+        let index = self.rng.gen_range(0, self.population.len());
+        self.population[index].weights = normalized(weights);
+    }
+
+    // Evaluates the population, then breeds the next one. All candidates
+    // play the same seeded games so their results are comparable.
+    pub fn run_generation(&mut self) -> GenerationReport {
+        let first_seed = self.generation as u64 * self.config.games_per_candidate;
+        evaluate_all(&mut self.population, first_seed, &self.config);
+        self.population.sort_by_key(|c| Reverse(c.fitness));
+
+        let total: i64 = self.population.iter().map(|c| c.fitness).sum();
+        let games = self.population.len() as f64 * self.config.games_per_candidate as f64;
+        let report = GenerationReport {
+            generation: self.generation,
+            best: self.population[0],
+            average_lines: total as f64 / games,
+        };
+
+        self.breed();
+        self.generation += 1;
+        return report;
+    }
+
+    // Lines per game on the validation games, the same for every generation.
+    pub fn validate(&self, weights: Weights) -> f64 {
+        let lines = play_games(weights, VALIDATION_SEED, &self.config);
+        return lines as f64 / self.config.games_per_candidate as f64;
+    }
+
+    fn breed(&mut self) {
+        let replace = ((self.population.len() as f64 * self.config.replace_ratio) as usize).max(1);
+        let mut offspring = Vec::with_capacity(replace);
+        for _ in 0..replace {
+            let a = self.tournament();
+            let b = self.tournament();
+            let mut child = crossover(&a, &b);
+            if self.rng.gen_bool(self.config.mutation_rate) {
+                let index = self.rng.gen_range(0, child.len());
+                child[index] += self
+                    .rng
+                    .gen_range(-self.config.mutation_step, self.config.mutation_step);
+            }
+            offspring.push(Candidate {
+                weights: normalized(Weights::from_array(child)),
+                fitness: 0,
+            });
+        }
+
+        // the population is sorted by fitness, so the weakest get replaced
+        let keep = self.population.len() - replace;
+        self.population.truncate(keep);
+        self.population.extend(offspring);
+    }
+
+    fn tournament(&mut self) -> Candidate {
+        let size = (self.population.len() / 10).max(2);
+        let mut best: Option<Candidate> = None;
+        for _ in 0..size {
+            let candidate = self.population[self.rng.gen_range(0, self.population.len())];
+            if best.is_none_or(|b| candidate.fitness > b.fitness) {
+                best = Some(candidate);
+            }
+        }
+        return best.unwrap();
+    }
+}
+
+// Runs all candidates on a pool of worker threads.
+fn evaluate_all(population: &mut [Candidate], first_seed: u64, config: &TrainerConfig) {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![0i64; population.len()]);
+    let candidates: &[Candidate] = population;
+
+    std::thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= candidates.len() {
+                    break;
+                }
+                let lines = play_games(candidates[index].weights, first_seed, config);
+                results.lock().unwrap()[index] = lines;
+            });
+        }
+    });
+
+    for (candidate, fitness) in population.iter_mut().zip(results.into_inner().unwrap()) {
+        candidate.fitness = fitness;
+    }
+}
+
+pub fn play_games(weights: Weights, first_seed: u64, config: &TrainerConfig) -> i64 {
+    let mut player = AiPlayer::new(weights);
+    let mut lines = 0;
+    for seed in first_seed..first_seed + config.games_per_candidate {
+        let mut game = Game::with_seed(seed);
+        while !game.is_game_over() && game.stats().pieces < config.max_pieces {
+            player.play_piece(&mut game);
+        }
+        lines += game.stats().cleared as i64;
+    }
+    return lines;
+}
+
+// Fitness-weighted average of both parents.
+fn crossover(a: &Candidate, b: &Candidate) -> [f64; 5] {
+    let (wa, wb) = match a.fitness + b.fitness {
+        0 => (0.5, 0.5),
+        sum => (a.fitness as f64 / sum as f64, b.fitness as f64 / sum as f64),
+    };
+    let (va, vb) = (a.weights.to_array(), b.weights.to_array());
+    let mut child = [0.0; 5];
+    for i in 0..child.len() {
+        child[i] = va[i] * wa + vb[i] * wb;
+    }
+    return child;
+}
+
+fn random_weights(rng: &mut StdRng) -> Weights {
+    let mut values = [0.0; 5];
+    for value in values.iter_mut() {
+        *value = rng.gen_range(-1.0, 1.0);
+    }
+    return normalized(Weights::from_array(values));
+}
+
+// Only the direction of the weight vector matters for picking placements.
+fn normalized(weights: Weights) -> Weights {
+    let mut values = weights.to_array();
+    let length = values.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if length > 0.0 {
+        for value in values.iter_mut() {
+            *value /= length;
+        }
+    }
+    return Weights::from_array(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn small_config() -> TrainerConfig {
+        return TrainerConfig {
+            population: 6,
+            games_per_candidate: 2,
+            max_pieces: 30,
+            threads: 2,
+            ..TrainerConfig::new()
+        };
+    }
+
+    fn length(weights: Weights) -> f64 {
+        return weights.to_array().iter().map(|v| v * v).sum::<f64>().sqrt();
+    }
+
+    #[test]
+    fn generations_keep_the_population_and_report_averages() {
+        let mut trainer = Trainer::new(small_config(), StdRng::seed_from_u64(1));
+        for generation in 0..3 {
+            let report = trainer.run_generation();
+            assert_eq!(report.generation, generation);
+            assert!(report.average_lines.is_finite());
+            assert!(report.best.fitness as f64 >= report.average_lines * 2.0);
+            assert_eq!(trainer.population.len(), 6);
+        }
+        for candidate in trainer.population.iter() {
+            assert!((length(candidate.weights) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn games_are_seeded() {
+        let weights = Weights::default();
+        let config = small_config();
+        assert_eq!(play_games(weights, 3, &config), play_games(weights, 3, &config));
+    }
+
+    #[test]
+    fn crossover_leans_towards_the_fitter_parent() {
+        let a = Candidate {
+            weights: Weights::from_array([1.0, 0.0, 0.0, 0.0, 0.0]),
+            fitness: 3,
+        };
+        let b = Candidate {
+            weights: Weights::from_array([0.0, 1.0, 0.0, 0.0, 0.0]),
+            fitness: 1,
+        };
+        assert_eq!(crossover(&a, &b), [0.75, 0.25, 0.0, 0.0, 0.0]);
+        let (a, b) = (Candidate { fitness: 0, ..a }, Candidate { fitness: 0, ..b });
+        assert_eq!(crossover(&a, &b), [0.5, 0.5, 0.0, 0.0, 0.0]);
+    }
+}
@@ -1,28 +1,51 @@
+use crate::pc_hint::HintState;
 use rustris_core::game::Game;
 use rustris_core::model::Tetromino;
-use rustris_core::model::{Board, CellVal, Stats, UserInput};
+use rustris_core::model::{Board, CellVal, ClearEvent, Stats, UserInput};
+use rustris_core::view::View;
+use rustris_net::spectator::GameSnapshot;
 use std::time::{Duration, Instant};
 
 const ONE_SECOND: Duration = Duration::from_secs(1);
+// how long cleared color groups flash
+const MATCH_FRAMES: u32 = 20;
 
 #[derive(Debug)]
 pub struct UI {
     screen: pancurses::Window,
     app_win: pancurses::Window,
-    panel_1: pancurses::Window,
+    // only drawn once, but must stay alive
+    _panel_1: pancurses::Window,
     panel_2: pancurses::Window,
     panel_3: pancurses::Window,
     fps_count: i32,
     fps_time: Instant,
     fps_value: i32,
+    // blocks get a look of their color, for rules where it matters
+    colored: bool,
+    // cells of the color groups cleared last, shown for `matched_frames`
+    matched: Vec<(i32, i32)>,
+    matched_frames: u32,
+    // of games drawn with `draw`
+    view: View,
 }
 
 pub enum UiInput {
     ChangeUI,
+    ToggleHint,
     UserWantsToQuit,
     NoInput,
 }
 
+// What a game mode shows besides the game: `status` replaces the "Points"
+// and "Top-Score" rows of panel_3, `results` covers the board once the mode
+// is over.
+#[derive(Default)]
+pub struct ModeView {
+    pub status: Vec<String>,
+    pub results: Vec<String>,
+}
+
 pub struct UiState {
     pub style: i32,
 }
@@ -61,14 +84,18 @@ impl UI {
 
         let app_win = UI::create_app_win(&screen);
         return Ok(UI {
-            screen: screen,
-            panel_1: UI::create_panel_1(&app_win),
+            screen,
+            _panel_1: UI::create_panel_1(&app_win),
             panel_2: UI::create_panel_2(&app_win),
             panel_3: UI::create_panel_3(&app_win),
-            app_win: app_win,
+            app_win,
             fps_count: 0,
             fps_time: Instant::now(),
             fps_value: -1,
+            colored: false,
+            matched: Vec::new(),
+            matched_frames: 0,
+            view: View::default(),
         });
     }
 
@@ -84,40 +111,137 @@ impl UI {
         pancurses::endwin();
     }
 
+    // The keys act on the board as it shows, see `set_view`.
     pub fn read_user_input(&self) -> (UserInput, UiInput) {
         let ch = self.screen.getch();
+        let input = match ch {
+            Some(pancurses::Input::KeyLeft) => UserInput::RotateLeft,
+            Some(pancurses::Input::KeyRight) => UserInput::RotateRight,
+            Some(pancurses::Input::Character('a')) => UserInput::MoveLeft,
+            Some(pancurses::Input::Character('d')) => UserInput::MoveRight,
+            Some(pancurses::Input::Character('s')) => self.view.vertical_input(false),
+            Some(pancurses::Input::Character('w')) => self.view.vertical_input(true),
+            Some(pancurses::Input::Character('c')) => UserInput::Hold,
+            Some(pancurses::Input::Character('n')) => UserInput::Reset,
+            _ => UserInput::NoInput,
+        };
         return (
-            match ch {
-                Some(pancurses::Input::KeyLeft) => UserInput::RotateLeft,
-                Some(pancurses::Input::KeyRight) => UserInput::RotateRight,
-                Some(pancurses::Input::Character('a')) => UserInput::MoveLeft,
-                Some(pancurses::Input::Character('d')) => UserInput::MoveRight,
-                Some(pancurses::Input::Character('s')) => UserInput::MoveDown,
-                Some(pancurses::Input::Character('w')) => UserInput::DropDown,
-                Some(pancurses::Input::Character('n')) => UserInput::Reset,
-                _ => UserInput::NoInput,
-            },
+            self.view.input(input),
             match ch {
                 Some(pancurses::Input::Character('q')) => UiInput::UserWantsToQuit,
                 Some(pancurses::Input::Character(' ')) => UiInput::ChangeUI,
+                Some(pancurses::Input::Character('h')) => UiInput::ToggleHint,
                 _ => UiInput::NoInput,
             },
         );
     }
 
-    pub fn draw(&mut self, game: &Game) {
+    // Shows games mirrored or upside down.
+    pub fn set_view(&mut self, view: View) {
+        self.view = view;
+    }
+
+    pub fn draw(&mut self, game: &Game, hint: &HintState, mode: &ModeView) {
+        self.count_fps();
+        self.colored = game.ruleset().color_match.is_some();
+        let board = game.current_board();
+        self.draw_board(&self.view.board(board));
+        self.draw_matched(board);
+        self.draw_hint(hint, board);
+        self.draw_active_piece(&self.view.piece(game.active_piece(), board));
+        self.draw_next_piece(&self.view.piece(game.next_piece(), board));
+        self.draw_stats(game.stats());
+        if game.ruleset().hold {
+            let held = game.held_tetromino().map(|piece| self.view.piece(piece, board));
+            self.draw_held_piece(held.as_ref());
+        }
+        self.draw_game_over(game.is_game_over());
+        self.draw_mode(mode);
+        self.refresh();
+    }
+
+    // Flashes the color groups a lock cleared for a moment, see `draw_matched`.
+    pub fn show_clears(&mut self, events: &[ClearEvent]) {
+        for event in events {
+            if let ClearEvent::Group { cells, .. } = event {
+                self.matched.extend_from_slice(cells);
+                self.matched_frames = MATCH_FRAMES;
+            }
+        }
+    }
+
+    fn draw_matched(&mut self, board: &Board) {
+        if self.matched_frames == 0 {
+            return;
+        }
+        for (x, y) in self.matched.iter() {
+            let (x, y) = self.view.cell(*x, *y, board);
+            self.panel_2.mv(1 + y, 2 + 2 * x);
+            self.panel_2.printw("**");
+        }
+        self.matched_frames -= 1;
+        if self.matched_frames == 0 {
+            self.matched.clear();
+        }
+    }
+
+    fn draw_mode(&self, mode: &ModeView) {
+        for (i, line) in mode.status.iter().take(4).enumerate() {
+            self.panel_3.mvprintw(12 + i as i32, 0, format!("{:24.24}", line));
+        }
+        if mode.results.is_empty() {
+            return;
+        }
+        let top = 10 - mode.results.len() as i32 / 2;
+        self.panel_2.mvprintw(top - 1, 2, format!("{:20}", ""));
+        for (i, line) in mode.results.iter().enumerate() {
+            self.panel_2.mvprintw(top + i as i32, 2, format!("{:^20.20}", line));
+        }
+        self.panel_2
+            .mvprintw(top + mode.results.len() as i32, 2, format!("{:20}", ""));
+    }
+
+    // A game watched through a spectator feed; `None` until the first
+    // snapshot arrived.
+    pub fn draw_snapshot(&mut self, snapshot: Option<&GameSnapshot>, status: &str) {
+        self.count_fps();
+        if let Some(snapshot) = snapshot {
+            self.draw_board(&snapshot.board());
+            if !snapshot.game_over {
+                self.draw_active_piece(&snapshot.piece());
+            }
+            self.draw_next_piece(&snapshot.next_piece());
+            self.draw_stats(&snapshot.stats());
+            if let Some(held) = snapshot.held_piece() {
+                self.draw_held_piece(Some(&held));
+            }
+            self.draw_mode(&ModeView {
+                status: snapshot.mode.clone(),
+                results: Vec::new(),
+            });
+            self.panel_3.mvprintw(
+                18,
+                0,
+                match snapshot.game_over {
+                    true => " GAME OVER              ",
+                    false => "                        ",
+                },
+            );
+        }
+        self.panel_3.mvprintw(19, 0, format!("{:24.24}", status));
+        self.refresh();
+    }
+
+    fn count_fps(&mut self) {
         let now = Instant::now();
         if now.duration_since(self.fps_time) > ONE_SECOND {
             self.fps_value = self.fps_count;
             self.fps_count = 0;
             self.fps_time = now;
         }
+    }
 
-        self.draw_board(&game.current_board());
-        self.draw_active_piece(&game.active_piece());
-        self.draw_next_piece(&game.next_piece());
-        self.draw_stats(&game.stats());
-
+    fn refresh(&mut self) {
         self.app_win.touch();
         self.app_win.refresh();
 
@@ -127,49 +251,130 @@ impl UI {
     fn draw_board(&self, board: &Board) {
         for y in 0..board.height() {
             for x in 0..board.width() {
-                let val = board.at(x, y);
-                self.panel_2.mv(1 + y as i32, 2 + 2 * x as i32);
-                self.panel_2.printw(self.cell_string(&val));
+                let val = match board.is_visible(x, y) {
+                    true => board.at(x, y),
+                    false => CellVal::Free,
+                };
+                self.panel_2.mv(1 + y, 2 + 2 * x);
+                self.panel_2.printw(self.cell(&val));
             }
         }
     }
 
     fn draw_stats(&self, stats: &Stats) {
+        self.panel_3.mvprintw(5, 16, format!("{:7}", stats.level));
         self.panel_3.mvprintw(6, 16, format!("{:7}", stats.cleared));
+        // only with pieces bigger than tetrominoes
+        match stats.clr_cmb_5 {
+            0 => self.panel_3.mvprintw(7, 0, "                        "),
+            n => self.panel_3.mvprintw(7, 0, format!(" Five-Liners:   {:7} ", n)),
+        };
         self.panel_3.mvprintw(8, 16, format!("{:7}", stats.clr_cmb_4));
         self.panel_3.mvprintw(9, 16, format!("{:7}", stats.clr_cmb_3));
         self.panel_3.mvprintw(10, 16, format!("{:7}", stats.clr_cmb_2));
         self.panel_3.mvprintw(11, 16, format!("{:7}", stats.clr_cmb_1));
+        // only with cascade rules; the status of a mode goes over it
+        match stats.chains {
+            0 => self.panel_3.mvprintw(12, 0, "                        "),
+            n => self.panel_3.mvprintw(12, 0, format!(" Chains:        {:7} ", n)),
+        };
+        self.panel_3.mvprintw(13, 16, format!("{:7}", stats.score));
         self.panel_3.mvprintw(20, 21, format!("{:2}", self.fps_value));
     }
 
+    // Over the project link, which rulesets without hold keep.
+    // Like `draw_next_piece`, in the two rows of its box that have cells,
+    // which is all of a tetromino.
+    fn draw_held_piece(&self, held: Option<&Tetromino>) {
+        self.panel_3.mvprintw(16, 0, " Hold:                  ");
+        self.panel_3.mvprintw(17, 0, "                        ");
+        let held = match held {
+            Some(held) => held,
+            None => {
+                self.panel_3.mvprintw(16, 10, "-");
+                return;
+            }
+        };
+        let step = held.scale;
+        let rows = (0..held.height())
+            .step_by(step as usize)
+            .filter(|y| (0..held.width()).any(|x| held.at(x, *y) != CellVal::Free));
+        for (i, y) in rows.take(2).enumerate() {
+            for x in 0..5 {
+                let val = match x * step < held.width() {
+                    true => held.at(x * step, y),
+                    false => CellVal::Free,
+                };
+                self.panel_3.mv(16 + i as i32, 10 + 2 * x);
+                self.panel_3.printw(self.cell(&val));
+            }
+        }
+    }
+
+    fn draw_hint(&self, hint: &HintState, board: &Board) {
+        let text = match hint {
+            HintState::Off => String::from("                       "),
+            HintState::Searching => String::from(" PC Hint:   searching  "),
+            HintState::NoSolution => String::from(" PC Hint:   none       "),
+            HintState::Solution { cells, pieces } => {
+                for (x, y) in cells {
+                    let (x, y) = self.view.cell(*x, *y, board);
+                    self.panel_2.mv(1 + y, 2 + 2 * x);
+                    self.panel_2.printw("::");
+                }
+                format!(" PC Hint:   {:2} pieces  ", pieces)
+            }
+        };
+        self.panel_3.mvprintw(19, 0, text);
+    }
+
+    fn draw_game_over(&self, game_over: bool) {
+        self.panel_3.mvprintw(
+            18,
+            0,
+            match game_over {
+                true => " GAME OVER, n to reset  ",
+                false => "                        ",
+            },
+        );
+    }
+
     fn draw_active_piece(&self, tetromino: &Tetromino) {
-        for y in 0..4 {
-            for x in 0..4 {
+        for y in 0..tetromino.height() {
+            for x in 0..tetromino.width() {
                 match tetromino.at(x, y) {
                     CellVal::Free => (),
-                    _ => {
+                    val => {
                         let px = tetromino.offset.0 + x;
                         let py = tetromino.offset.1 + y;
                         self.panel_2.mv(1 + py, 2 + 2 * px);
-                        self.panel_2.printw("[]");
+                        self.panel_2.printw(match self.colored {
+                            true => UI::color_string(&val),
+                            false => "[]",
+                        });
                     }
                 }
             }
         }
     }
 
+    // Room for pieces of up to five cells; larger ones are cut off. Big
+    // pieces are shown at their normal size.
     fn draw_next_piece(&self, tetromino: &Tetromino) {
-        for y in 0..3 {
-            for x in 0..4 {
-                let val = tetromino.at(x, y);
-                self.panel_3.mv(1 + y as i32, 10 + 2 * x as i32);
-                self.panel_3.printw(self.cell_string(&val));
+        let step = tetromino.scale;
+        for y in 0..4 {
+            for x in 0..5 {
+                let val = match x * step < tetromino.width() && y * step < tetromino.height() {
+                    true => tetromino.at(x * step, y * step),
+                    false => CellVal::Free,
+                };
+                self.panel_3.mv(1 + y, 10 + 2 * x);
+                self.panel_3.printw(self.cell(&val));
             }
         }
     }
 
-    fn cell_string(&self, val: &CellVal) -> &str {
+    pub fn cell_string(val: &CellVal) -> &'static str {
         return match val {
             CellVal::Free => "  ",
             CellVal::Color1 => "{}",
@@ -180,6 +385,30 @@ impl UI {
             CellVal::Color6 => "{}",
             CellVal::Color7 => "{}",
             CellVal::Color8 => "{}",
+            CellVal::Garbage => "<>",
+        };
+    }
+
+    // Every color its own look.
+    pub fn color_string(val: &CellVal) -> &'static str {
+        return match val {
+            CellVal::Free => "  ",
+            CellVal::Color1 => "{}",
+            CellVal::Color2 => "[]",
+            CellVal::Color3 => "()",
+            CellVal::Color4 => "##",
+            CellVal::Color5 => "%%",
+            CellVal::Color6 => "@@",
+            CellVal::Color7 => "$$",
+            CellVal::Color8 => "&&",
+            CellVal::Garbage => "<>",
+        };
+    }
+
+    fn cell(&self, val: &CellVal) -> &'static str {
+        return match self.colored {
+            true => UI::color_string(val),
+            false => UI::cell_string(val),
         };
     }
 
@@ -212,20 +441,20 @@ impl UI {
         panel.mvaddstr(04, 0, " game written in the    ");
         panel.mvaddstr(05, 0, " rust programming       ");
         panel.mvaddstr(06, 0, " language.              ");
-        panel.mvaddstr(07, 0, "                        ");
-        panel.mvaddstr(08, 0, " Please find the source ");
-        panel.mvaddstr(09, 0, " code in github.        ");
-        panel.mvaddstr(10, 0, "                        ");
-        panel.mvaddstr(11, 0, " Key Mappings:          ");
-        panel.mvaddstr(12, 0, "     q - Quit           ");
-        panel.mvaddstr(13, 0, "     a - Move left      ");
-        panel.mvaddstr(14, 0, "     d - Move right     ");
-        panel.mvaddstr(15, 0, "     s - Move down      ");
-        panel.mvaddstr(16, 0, "     w - Drop           ");
-        panel.mvaddstr(17, 0, "  Left - Rotate         ");
-        panel.mvaddstr(18, 0, " Right - Rotate         ");
-        panel.mvaddstr(19, 0, " Space - Change UI      ");
-        panel.mvaddstr(20, 0, "     n - Reset          ");
+        panel.mvaddstr(07, 0, " Please find the source ");
+        panel.mvaddstr(08, 0, " code in github.        ");
+        panel.mvaddstr(09, 0, "                        ");
+        panel.mvaddstr(10, 0, " Key Mappings:          ");
+        panel.mvaddstr(11, 0, "     q - Quit           ");
+        panel.mvaddstr(12, 0, "     a - Move left      ");
+        panel.mvaddstr(13, 0, "     d - Move right     ");
+        panel.mvaddstr(14, 0, "     s - Move down      ");
+        panel.mvaddstr(15, 0, "     w - Drop           ");
+        panel.mvaddstr(16, 0, "  Left - Rotate         ");
+        panel.mvaddstr(17, 0, " Right - Rotate         ");
+        panel.mvaddstr(18, 0, " Space - Change UI      ");
+        panel.mvaddstr(19, 0, "     n - Reset          ");
+        panel.mvaddstr(20, 0, "     h - PC hint        ");
         panel.mvaddstr(21, 0, "+                       ");
         return panel;
     }
@@ -239,7 +468,7 @@ impl UI {
         return panel;
     }
 
-    fn draw_panel_border(panel: &pancurses::Window, variant: i32) {
+    pub fn draw_panel_border(panel: &pancurses::Window, variant: i32) {
         match variant {
             0 => {
                 panel.mvaddstr(00, 0, "-+--------------------+-");
@@ -1,36 +1,282 @@
 mod curses_ui;
+mod modes;
+mod online;
+mod options;
+mod pc_hint;
+mod tbp_frontend;
+mod versus_ui;
 
-use crate::curses_ui::{UiInput, UiState, UI};
+use crate::curses_ui::{ModeView, UiInput, UiState, UI};
+use crate::modes::{game_mode, ModeRun};
+use crate::online::OnlineGame;
+use crate::options::Options;
+use crate::pc_hint::PcHint;
+use crate::tbp_frontend::TbpFrontend;
+use crate::versus_ui::VersusUI;
+use rustris_ai::evaluator::Weights;
+use rustris_ai::player::AiPlayer;
 use rustris_core::game::Game;
+use rustris_core::model::UserInput;
+use rustris_core::versus::Match;
+use rustris_net::protocol::DEFAULT_PORT;
+use rustris_net::spectator::{SpectatorClient, SpectatorFeed};
 use std::time::{Duration, Instant};
 
 const SLEEP_TIME: Duration = Duration::from_millis(0);
 const FRAME_TIME: Duration = Duration::from_nanos(16666667);
+const DEMO_FRAMES_PER_INPUT: u32 = 4;
 
 fn main() {
-  let ui_result = UI::new();
-  if ui_result.is_err() {
-    println!("UI init failed: {}", ui_result.unwrap_err());
+  let options = match Options::parse(std::env::args().skip(1)) {
+    Ok(options) => options,
+    Err(err) => {
+      println!("{}", err);
+      return;
+    }
+  };
+
+  if options.versus {
+    run_versus();
+    return;
+  }
+  if let Some(addr) = &options.connect {
+    let name = match &options.name {
+      Some(name) => name.clone(),
+      None => std::env::var("USER").unwrap_or_else(|_| String::from("player")),
+    };
+    run_online(addr, &name);
+    return;
+  }
+  if let Some(addr) = &options.spectate {
+    run_spectate(addr);
     return;
   }
 
+  let weights = match &options.weights {
+    Some(path) => match Weights::load(path) {
+      Ok(weights) => weights,
+      Err(err) => {
+        println!("{}", err);
+        return;
+      }
+    },
+    None => Weights::default(),
+  };
+
+  let mut tbp_bot = match &options.tbp {
+    Some(command) => match TbpFrontend::launch(command) {
+      Ok(frontend) => Some(frontend),
+      Err(err) => {
+        println!("{}", err);
+        return;
+      }
+    },
+    None => None,
+  };
+
+  let mut feed = match &options.broadcast {
+    Some(addr) => match SpectatorFeed::bind(addr) {
+      Ok(feed) => Some(feed),
+      Err(err) => {
+        println!("can't broadcast on {}: {}", addr, err);
+        return;
+      }
+    },
+    None => None,
+  };
+
+  let mut ui = match UI::new() {
+    Ok(ui) => ui,
+    Err(err) => {
+      println!("UI init failed: {}", err);
+      return;
+    }
+  };
+
+  ui.set_view(options.view);
   let mut ui_state = UiState::new();
-  let mut ui = ui_result.unwrap();
   let mut game = Game::new();
+  game.set_ruleset(options.ruleset.clone());
+  let mut demo_player = AiPlayer::new(weights);
+  let mut frame: u32 = 0;
+  let mut pc_hint = PcHint::new();
+  let mut goal = None;
+  if let Some(mode) = &options.goal {
+    game.set_mode(game_mode(mode));
+    goal = Some(ModeRun::new(!options.demo && options.tbp.is_none()));
+  }
+  let mut last_frame = Instant::now();
 
   loop {
     let t_start = Instant::now();
-    let (user_input, ui_input) = ui.read_user_input();
+    let frame_time = t_start - last_frame;
+    last_frame = t_start;
+    let (mut user_input, ui_input) = ui.read_user_input();
+
+    if options.demo || tbp_bot.is_some() {
+      // the AI plays; keys other than quit and UI change are ignored
+      frame += 1;
+      user_input = match (frame % DEMO_FRAMES_PER_INPUT, &mut tbp_bot) {
+        (0, Some(bot)) => bot.next_input(&game),
+        (0, None) => demo_player.next_input(&game),
+        _ => UserInput::NoInput,
+      };
+    }
 
     match ui_input {
       UiInput::UserWantsToQuit => break,
       UiInput::ChangeUI => ui.change(&mut ui_state),
+      UiInput::ToggleHint => pc_hint.toggle(),
       _ => (),
     }
 
     game.handle_input(&user_input);
-    game.run_step();
-    ui.draw(&game);
+    game.run_step_timed(frame_time);
+    // a plain game starts over as soon as it's lost, as rustris always did
+    if game.is_game_over() && goal.is_none() && !options.demo && tbp_bot.is_none() {
+      game.handle_input(&UserInput::Reset);
+    }
+    ui.show_clears(&game.take_clear_events());
+
+    let mode = match &mut goal {
+      Some(goal) => {
+        goal.update(&game);
+        goal.view(&game)
+      }
+      None => ModeView::default(),
+    };
+    pc_hint.update(&game);
+    ui.draw(&game, pc_hint.state(), &mode);
+    if let Some(feed) = &mut feed {
+      feed.publish(&game, &mode.status);
+    }
+
+    while Instant::now() - t_start < FRAME_TIME {
+      std::thread::sleep(SLEEP_TIME);
+    }
+  }
+
+  ui.destroy();
+  if let Some(bot) = &mut tbp_bot {
+    bot.quit();
+  }
+}
+
+fn run_versus() {
+  let mut ui = match VersusUI::new() {
+    Ok(ui) => ui,
+    Err(err) => {
+      println!("UI init failed: {}", err);
+      return;
+    }
+  };
+
+  let mut versus = Match::new();
+
+  loop {
+    let t_start = Instant::now();
+    let (inputs, ui_input) = ui.read_inputs();
+
+    if let UiInput::UserWantsToQuit = ui_input {
+      break;
+    }
+    for (player, input) in inputs.iter() {
+      versus.handle_input(*player, input);
+    }
+    versus.run_step();
+    ui.draw(&versus);
+
+    while Instant::now() - t_start < FRAME_TIME {
+      std::thread::sleep(SLEEP_TIME);
+    }
+  }
+
+  ui.destroy();
+}
+
+fn run_online(addr: &str, name: &str) {
+  let addr = match addr.contains(':') {
+    true => String::from(addr),
+    false => format!("{}:{}", addr, DEFAULT_PORT),
+  };
+  let mut online = match OnlineGame::connect(&addr, name) {
+    Ok(online) => online,
+    Err(err) => {
+      println!("{}", err);
+      return;
+    }
+  };
+
+  let mut ui = match VersusUI::new() {
+    Ok(ui) => ui,
+    Err(err) => {
+      println!("UI init failed: {}", err);
+      return;
+    }
+  };
+
+  loop {
+    let t_start = Instant::now();
+    let (inputs, ui_input) = ui.read_inputs();
+
+    if let UiInput::UserWantsToQuit = ui_input {
+      break;
+    }
+    // both key sets control the local game
+    for (_, input) in inputs.iter() {
+      online.handle_input(input);
+    }
+    online.run_step();
+    ui.draw_sides(online.sides(), online.titles(), &online.status());
+
+    while Instant::now() - t_start < FRAME_TIME {
+      std::thread::sleep(SLEEP_TIME);
+    }
+  }
+
+  ui.destroy();
+}
+
+fn run_spectate(addr: &str) {
+  let mut client = match SpectatorClient::connect(addr) {
+    Ok(client) => client,
+    Err(err) => {
+      println!("can't connect to {}: {}", addr, err);
+      return;
+    }
+  };
+
+  let mut ui = match UI::new() {
+    Ok(ui) => ui,
+    Err(err) => {
+      println!("UI init failed: {}", err);
+      return;
+    }
+  };
+
+  let mut ui_state = UiState::new();
+  let mut snapshot = None;
+
+  loop {
+    let t_start = Instant::now();
+    // read-only, game keys are ignored
+    let (_, ui_input) = ui.read_user_input();
+
+    match ui_input {
+      UiInput::UserWantsToQuit => break,
+      UiInput::ChangeUI => ui.change(&mut ui_state),
+      _ => (),
+    }
+
+    if let Some(newer) = client.poll() {
+      snapshot = Some(newer);
+    }
+    let status = match (client.is_connected(), &snapshot) {
+      (false, _) => String::from(" feed ended, q to quit"),
+      (true, None) => String::from(" waiting for the game"),
+      (true, Some(_)) => format!(" watching {}", addr),
+    };
+    ui.draw_snapshot(snapshot.as_ref(), &status);
 
     while Instant::now() - t_start < FRAME_TIME {
       std::thread::sleep(SLEEP_TIME);
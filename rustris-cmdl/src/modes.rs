@@ -0,0 +1,286 @@
+use crate::curses_ui::ModeView;
+use crate::options::Goal;
+use rustris_core::dig::Dig;
+use rustris_core::game::Game;
+use rustris_core::invisible::Invisible;
+use rustris_core::mode::{GameMode, Outcome};
+use rustris_core::records::Records;
+use rustris_core::sprint::{format_time, Sprint};
+use rustris_core::survival::Survival;
+use rustris_core::ultra::Ultra;
+use std::path::PathBuf;
+
+const RECORDS_FILE: &str = ".rustris_records";
+
+// Personal bests live in the home directory, or in the working directory if
+// there is none.
+fn records_path() -> PathBuf {
+    let mut path = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    path.push(RECORDS_FILE);
+    return path;
+}
+
+pub fn load_records() -> Records {
+    return match std::fs::read_to_string(records_path()) {
+        Ok(text) => Records::parse(&text),
+        Err(_) => Records::new(),
+    };
+}
+
+// A record that can't be saved is only lost, the game goes on.
+pub fn save_records(records: &Records) {
+    let _ = std::fs::write(records_path(), records.to_string());
+}
+
+// The rules a goal is played by.
+pub fn game_mode(goal: &Goal) -> Box<dyn GameMode> {
+    return match goal {
+        Goal::Sprint(target) => Box::new(Sprint::new(*target)),
+        Goal::Ultra(limit) => Box::new(Ultra::new(*limit)),
+        Goal::Dig(config) => Box::new(Dig::new(config.clone())),
+        Goal::Survival(config) => Box::new(Survival::new(config.clone())),
+        Goal::Invisible(fade) => Box::new(Invisible::new(*fade)),
+    };
+}
+
+// Personal bests of the games played with a goal. All but dig keep one per
+// rules, saved as soon as it is beaten.
+pub struct ModeRun {
+    records: Records,
+    // false while a bot plays; its games are shown against the bests, but
+    // never become one
+    submit: bool,
+    // if the finished game was submitted
+    recorded: bool,
+    new_best: bool,
+}
+
+impl Default for ModeRun {
+    fn default() -> ModeRun {
+        return ModeRun::new(true);
+    }
+}
+
+impl ModeRun {
+    pub fn new(submit: bool) -> ModeRun {
+        return ModeRun {
+            records: load_records(),
+            submit,
+            recorded: false,
+            new_best: false,
+        };
+    }
+
+    // Call once per frame, after the game ran.
+    pub fn update(&mut self, game: &Game) {
+        let outcome = match game.outcome() {
+            Some(outcome) => outcome,
+            None => {
+                // reset
+                self.recorded = false;
+                self.new_best = false;
+                return;
+            }
+        };
+        if self.recorded || !self.submit {
+            return;
+        }
+        self.recorded = true;
+        let rules = game.ruleset().record_key();
+        if let Some(sprint) = game.mode_as::<Sprint>() {
+            if outcome == Outcome::Won {
+                self.new_best = self.records.submit_sprint(&rules, sprint.target(), sprint.elapsed());
+            }
+        } else if let Some(ultra) = game.mode_as::<Ultra>() {
+            self.new_best = self.records.submit_ultra(&rules, ultra.limit(), game.stats().score);
+        } else if let Some(survival) = game.mode_as::<Survival>() {
+            self.new_best = self.records.submit_survival(&rules, survival.survived(game));
+        }
+        if self.new_best {
+            save_records(&self.records);
+        }
+    }
+
+    pub fn view(&self, game: &Game) -> ModeView {
+        if let Some(sprint) = game.mode_as::<Sprint>() {
+            return self.sprint_view(sprint, game);
+        }
+        if let Some(ultra) = game.mode_as::<Ultra>() {
+            return self.ultra_view(ultra, game);
+        }
+        if let Some(dig) = game.mode_as::<Dig>() {
+            return dig_view(dig, game);
+        }
+        if let Some(survival) = game.mode_as::<Survival>() {
+            return self.survival_view(survival, game);
+        }
+        if let Some(invisible) = game.mode_as::<Invisible>() {
+            return invisible_view(invisible);
+        }
+        return ModeView::default();
+    }
+
+    fn sprint_view(&self, sprint: &Sprint, game: &Game) -> ModeView {
+        let target = sprint.target();
+        let best = match self.records.sprint_best(&game.ruleset().record_key(), target) {
+            Some(best) => format_time(best),
+            None => String::from("-:--.---"),
+        };
+        let status = vec![
+            row("Sprint:", &format!("{} lines", target)),
+            row("Lines Left:", &sprint.lines_left(game).to_string()),
+            row("Time:", &format_time(sprint.elapsed())),
+            row("Best:", &best),
+        ];
+        if game.outcome() != Some(Outcome::Won) {
+            return ModeView {
+                status,
+                results: Vec::new(),
+            };
+        }
+
+        let pieces = game.stats().pieces;
+        let seconds = sprint.elapsed().as_secs_f64();
+        let pps = if seconds > 0.0 { pieces as f64 / seconds } else { 0.0 };
+        let verdict = match self.new_best {
+            true => String::from("NEW PERSONAL BEST"),
+            false => format!("Best   {}", best),
+        };
+        return ModeView {
+            status,
+            results: vec![
+                String::from("SPRINT COMPLETE"),
+                String::new(),
+                format!("Time   {}", format_time(sprint.elapsed())),
+                format!("Pieces {:>8}", pieces),
+                format!("PPS    {:>8.2}", pps),
+                verdict,
+                String::new(),
+                String::from("n - play again"),
+            ],
+        };
+    }
+
+    fn ultra_view(&self, ultra: &Ultra, game: &Game) -> ModeView {
+        let best = match self.records.ultra_best(&game.ruleset().record_key(), ultra.limit()) {
+            Some(best) => best.to_string(),
+            None => String::from("-"),
+        };
+        let score = game.stats().score;
+        let status = vec![
+            row("Ultra:", &format_time(ultra.limit())),
+            row("Time Left:", &format_time(ultra.remaining())),
+            row("Score:", &score.to_string()),
+            row("Best:", &best),
+        ];
+        if !game.is_finished() {
+            return ModeView {
+                status,
+                results: Vec::new(),
+            };
+        }
+
+        let title = match game.is_game_over() {
+            true => "TOPPED OUT",
+            false => "TIME IS UP",
+        };
+        let verdict = match self.new_best {
+            true => String::from("NEW PERSONAL BEST"),
+            false => format!("Best   {:>8}", best),
+        };
+        return ModeView {
+            status,
+            results: vec![
+                String::from(title),
+                String::new(),
+                format!("Score  {:>8}", score),
+                format!("Lines  {:>8}", game.stats().cleared),
+                format!("Pieces {:>8}", game.stats().pieces),
+                verdict,
+                String::new(),
+                String::from("n - play again"),
+            ],
+        };
+    }
+
+    fn survival_view(&self, survival: &Survival, game: &Game) -> ModeView {
+        let best = match self.records.survival_best(&game.ruleset().record_key()) {
+            Some(best) => format_time(best),
+            None => String::from("-:--.---"),
+        };
+        let status = vec![
+            row("Survived:", &format_time(survival.survived(game))),
+            row(
+                "Next Row:",
+                &format!("{:.1}s", survival.until_next_row(game).as_secs_f64()),
+            ),
+            row("Rows Risen:", &survival.rows_risen().to_string()),
+            row("Best:", &best),
+        ];
+        if !game.is_finished() {
+            return ModeView {
+                status,
+                results: Vec::new(),
+            };
+        }
+        let verdict = match self.new_best {
+            true => String::from("NEW PERSONAL BEST"),
+            false => format!("Best   {}", best),
+        };
+        return ModeView {
+            status,
+            results: vec![
+                String::from("SURVIVED"),
+                String::new(),
+                format!("Time   {}", format_time(survival.survived(game))),
+                format!("Lines  {:>8}", game.stats().cleared),
+                format!("Rows   {:>8}", survival.rows_risen()),
+                verdict,
+                String::new(),
+                String::from("n - play again"),
+            ],
+        };
+    }
+}
+
+fn dig_view(dig: &Dig, game: &Game) -> ModeView {
+    let status = vec![
+        row("Dig:", &format!("{} rows", dig.config().rows)),
+        row("Rows Left:", &dig.rows_left(game).to_string()),
+        row("Time:", &format_time(dig.elapsed())),
+        row("Pieces:", &game.stats().pieces.to_string()),
+    ];
+    if game.outcome() != Some(Outcome::Won) {
+        return ModeView {
+            status,
+            results: Vec::new(),
+        };
+    }
+    return ModeView {
+        status,
+        results: vec![
+            String::from("ALL DUG OUT"),
+            String::new(),
+            format!("Time   {}", format_time(dig.elapsed())),
+            format!("Pieces {:>8}", game.stats().pieces),
+            String::new(),
+            String::from("n - play again"),
+        ],
+    };
+}
+
+fn invisible_view(invisible: &Invisible) -> ModeView {
+    let fade = match invisible.fade().as_millis() {
+        0 => String::from("at once"),
+        _ => format!("after {:.1}s", invisible.fade().as_secs_f64()),
+    };
+    return ModeView {
+        status: vec![row("Invisible:", &fade)],
+        results: Vec::new(),
+    };
+}
+
+// A label and a value across one panel_3 row.
+fn row(label: &str, value: &str) -> String {
+    return format!(" {:<11}{:>11}", label, value);
+}
@@ -0,0 +1,222 @@
+use crate::versus_ui::Side;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustris_core::game::Game;
+use rustris_core::model::{Board, UserInput};
+use rustris_core::versus::{AttackTable, GarbageQueue};
+use rustris_net::client::NetClient;
+use rustris_net::protocol::{decode_board, encode_board, ClientMessage, PlayerState, ServerMessage};
+
+const GARBAGE_CAP: i32 = 8;
+
+#[derive(PartialEq, Eq)]
+enum Phase {
+    Lobby,
+    Ready,
+    Playing,
+    // game over was reported, the server decides the round
+    ToppedOut,
+}
+
+// The local side of a versus game against a player on another machine. The
+// local game runs here; the opponent is only seen through its board states.
+pub struct OnlineGame {
+    client: NetClient,
+    game: Game,
+    incoming: GarbageQueue,
+    attack_table: AttackTable,
+    rng: StdRng,
+    phase: Phase,
+    player: usize,
+    names: [String; 2],
+    wins: [i32; 2],
+    sent: i32,
+    received: i32,
+    opponent: Board,
+    opponent_incoming: i32,
+    opponent_lines: i32,
+    message: String,
+}
+
+impl OnlineGame {
+    pub fn connect(addr: &str, name: &str) -> Result<OnlineGame, String> {
+        let client = NetClient::connect(addr, name).map_err(|err| format!("can't connect to {}: {}", addr, err))?;
+        return Ok(OnlineGame {
+            client,
+            game: Game::with_seed(0),
+            incoming: GarbageQueue::new(),
+            attack_table: AttackTable::new(),
+            rng: StdRng::from_entropy(),
+            phase: Phase::Lobby,
+            player: 0,
+            names: [String::new(), String::new()],
+            wins: [0, 0],
+            sent: 0,
+            received: 0,
+            opponent: Board::new(),
+            opponent_incoming: 0,
+            opponent_lines: 0,
+            message: String::from(" connecting"),
+        });
+    }
+
+    // `Reset` means "ready" between rounds.
+    pub fn handle_input(&mut self, input: &UserInput) {
+        match (&self.phase, input) {
+            (Phase::Lobby, UserInput::Reset) => {
+                self.client.send(&ClientMessage::Ready);
+                self.phase = Phase::Ready;
+            }
+            (Phase::Playing, UserInput::Reset) => (),
+            (Phase::Playing, _) => {
+                self.game.handle_input(input);
+                self.after_action();
+            }
+            _ => (),
+        }
+    }
+
+    pub fn run_step(&mut self) {
+        while let Some(message) = self.client.poll() {
+            self.on_message(message);
+        }
+        if self.phase == Phase::Playing {
+            self.game.run_step();
+            self.after_action();
+        }
+    }
+
+    fn after_action(&mut self) {
+        let lock = match self.game.take_lock_event() {
+            Some(lock) => lock,
+            None => return,
+        };
+
+        let attack = self.incoming.cancel(self.attack_table.attack(&lock));
+        if attack > 0 {
+            let hole = self.rng.gen_range(0, self.game.current_board().width());
+            self.client.send(&ClientMessage::Attack { rows: attack, hole });
+            self.sent += attack;
+        }
+        if lock.lines == 0 {
+            self.incoming.raise(&mut self.game, GARBAGE_CAP, 0.0);
+        }
+
+        self.client.send(&ClientMessage::State(PlayerState {
+            board: encode_board(self.game.current_board()),
+            incoming: self.incoming.total(),
+            lines: self.game.stats().cleared,
+        }));
+        if self.game.is_game_over() {
+            self.client.send(&ClientMessage::GameOver);
+            self.phase = Phase::ToppedOut;
+        }
+    }
+
+    fn on_message(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::Welcome { player } => {
+                self.player = player;
+                self.message = String::new();
+            }
+            ServerMessage::Lobby { players } => {
+                for (i, entry) in players.iter().enumerate().take(2) {
+                    let side = self.side_of(i);
+                    match entry {
+                        Some(entry) => {
+                            self.names[side] = entry.name.clone();
+                            self.wins[side] = entry.wins;
+                        }
+                        None => self.names[side] = String::new(),
+                    }
+                }
+            }
+            ServerMessage::Start { seed } => {
+                self.game.reset_with_seed(seed);
+                self.incoming.clear();
+                self.sent = 0;
+                self.received = 0;
+                self.opponent = Board::new();
+                self.opponent_incoming = 0;
+                self.opponent_lines = 0;
+                self.message = String::new();
+                self.phase = Phase::Playing;
+            }
+            ServerMessage::Attack { rows, hole } => {
+                if self.phase == Phase::Playing {
+                    self.incoming.push(rows, hole);
+                    self.received += rows;
+                }
+            }
+            ServerMessage::OpponentState(state) => {
+                self.opponent = decode_board(&state.board);
+                self.opponent_incoming = state.incoming;
+                self.opponent_lines = state.lines;
+            }
+            ServerMessage::RoundOver { winner } => {
+                self.message = match winner == self.player {
+                    true => String::from(" YOU WIN"),
+                    false => String::from(" YOU LOSE"),
+                };
+                self.phase = Phase::Lobby;
+            }
+            ServerMessage::OpponentLeft => {
+                self.message = String::from(" opponent left");
+                self.phase = Phase::Lobby;
+            }
+            ServerMessage::Error { reason } => self.message = format!(" {}", reason),
+        }
+    }
+
+    // Index into the local arrays, where the local player comes first.
+    fn side_of(&self, player: usize) -> usize {
+        return match player == self.player {
+            true => 0,
+            false => 1,
+        };
+    }
+
+    pub fn sides(&self) -> [Side<'_>; 2] {
+        let playing = self.phase == Phase::Playing;
+        return [
+            Side {
+                board: self.game.current_board(),
+                piece: if playing { Some(self.game.active_piece()) } else { None },
+                next: if playing { Some(self.game.next_piece()) } else { None },
+                garbage: self.incoming.total(),
+                sent: self.sent,
+                lines: self.game.stats().cleared,
+                wins: self.wins[0],
+            },
+            Side {
+                board: &self.opponent,
+                piece: None,
+                next: None,
+                garbage: self.opponent_incoming,
+                sent: self.received,
+                lines: self.opponent_lines,
+                wins: self.wins[1],
+            },
+        ];
+    }
+
+    pub fn titles(&self) -> [&str; 2] {
+        let opponent = match self.names[1].is_empty() {
+            true => "(waiting)",
+            false => self.names[1].as_str(),
+        };
+        return ["You", opponent];
+    }
+
+    pub fn status(&self) -> String {
+        if !self.client.is_connected() {
+            return String::from(" server connection lost");
+        }
+        let prompt = match self.phase {
+            Phase::Lobby => ", n: ready",
+            Phase::Ready => ", waiting",
+            Phase::Playing | Phase::ToppedOut => "",
+        };
+        return format!("{}{}", self.message, prompt);
+    }
+}
@@ -0,0 +1,275 @@
+use rustris_core::dig::DigConfig;
+use rustris_core::invisible::FADE_TIME;
+use rustris_core::pieces::PieceSet;
+use rustris_core::rotation::RotationSystem;
+use rustris_core::ruleset::Ruleset;
+use rustris_core::sprint::SPRINT_LINES;
+use rustris_core::survival::SurvivalConfig;
+use rustris_core::ultra::ULTRA_TIME;
+use rustris_core::view::View;
+use std::time::Duration;
+
+// What a single player game is about, besides surviving.
+pub enum Goal {
+    // clear this many lines
+    Sprint(i32),
+    // score within this time
+    Ultra(Duration),
+    Dig(DigConfig),
+    Survival(SurvivalConfig),
+    // locked cells disappear after this time
+    Invisible(Duration),
+}
+
+pub struct Options {
+    pub demo: bool,
+    pub versus: bool,
+    pub weights: Option<String>,
+    pub tbp: Option<String>,
+    pub connect: Option<String>,
+    pub name: Option<String>,
+    pub broadcast: Option<String>,
+    pub spectate: Option<String>,
+    pub goal: Option<Goal>,
+    pub ruleset: Ruleset,
+    pub pieces: Option<String>,
+    pub view: View,
+}
+
+impl Options {
+    pub const USAGE: &'static str = "usage: rustris-cmdl [MODE]
+modes:
+  --demo [--weights FILE]       let the AI play
+  --tbp 'BOT COMMAND'           let a TBP bot play
+  --versus                      two players on one keyboard
+  --connect HOST[:PORT] [--name NAME]
+                                versus over the network, see rustris-server
+  --spectate HOST:PORT          watch a game started with --broadcast
+options:
+  --broadcast ADDR              let others watch the game, e.g. 0.0.0.0:7879
+  --rules NAME                  rustris (default), guideline, nes or tgm
+  --pieces FILE                 play with the pieces defined in FILE, see pieces/
+  --big                         every block takes 2x2 cells of the board
+  --cascade                     after a clear, blocks of one color fall as far as
+                                they can and may clear more lines
+  --color-match N               groups of N or more connected blocks of one color
+                                clear too; N must be bigger than the pieces
+  --mirror                      show the board mirrored, left is right
+  --upside-down                 show the board upside down, pieces rise
+  --sprint [--lines N]          clear 40 (or N) lines as fast as possible
+  --ultra [--seconds N]         score as much as possible in 2 minutes (or N seconds)
+  --dig [--rows N] [--messiness P] [--refill]
+                                clear 10 (or N) rows of garbage; holes move with
+                                chance P (default 1.0); --refill keeps 10 on the board
+  --survival                    survive garbage rising faster and faster
+  --invisible [--fade SECONDS]  locked blocks disappear after 3 seconds (or SECONDS,
+                                0 for right away)";
+
+    pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options {
+            demo: false,
+            versus: false,
+            weights: None,
+            tbp: None,
+            connect: None,
+            name: None,
+            broadcast: None,
+            spectate: None,
+            goal: None,
+            ruleset: Ruleset::default(),
+            pieces: None,
+            view: View::default(),
+        };
+        let mut rules = false;
+        let mut big = false;
+        let mut cascade = false;
+        let mut color_match = None;
+        let mut goals = Vec::new();
+        let mut lines = None;
+        let mut seconds = None;
+        let mut fade = None;
+        let mut dig = DigConfig::new();
+        let mut dig_options = false;
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--demo" => options.demo = true,
+                "--versus" => options.versus = true,
+                "--weights" => options.weights = Some(Options::value(&arg, args.next())?),
+                "--tbp" => options.tbp = Some(Options::value(&arg, args.next())?),
+                "--connect" => options.connect = Some(Options::value(&arg, args.next())?),
+                "--name" => options.name = Some(Options::value(&arg, args.next())?),
+                "--broadcast" => options.broadcast = Some(Options::value(&arg, args.next())?),
+                "--spectate" => options.spectate = Some(Options::value(&arg, args.next())?),
+                "--rules" => {
+                    let value = Options::value(&arg, args.next())?;
+                    options.ruleset = match Ruleset::by_name(&value) {
+                        Some(ruleset) => ruleset,
+                        None => return Err(format!("unknown rules '{}'\n{}", value, Options::USAGE)),
+                    };
+                    rules = true;
+                }
+                "--pieces" => options.pieces = Some(Options::value(&arg, args.next())?),
+                "--big" => big = true,
+                "--cascade" => cascade = true,
+                "--mirror" => options.view.mirrored = true,
+                "--upside-down" => options.view.upside_down = true,
+                "--color-match" => {
+                    let value = Options::value(&arg, args.next())?;
+                    color_match = match value.parse::<usize>() {
+                        Ok(size) if size > 0 => Some(size),
+                        _ => return Err(format!("bad group size '{}'\n{}", value, Options::USAGE)),
+                    };
+                }
+                "--sprint" | "--ultra" | "--dig" | "--survival" | "--invisible" => goals.push(arg.clone()),
+                "--seconds" => {
+                    let value = Options::value(&arg, args.next())?;
+                    seconds = match value.parse::<u64>() {
+                        Ok(seconds) if seconds > 0 => Some(Duration::from_secs(seconds)),
+                        _ => return Err(format!("bad number of seconds '{}'\n{}", value, Options::USAGE)),
+                    };
+                }
+                "--fade" => {
+                    let value = Options::value(&arg, args.next())?;
+                    fade = match value.parse::<f64>() {
+                        Ok(fade) if (0.0..=3600.0).contains(&fade) => Some(Duration::from_secs_f64(fade)),
+                        _ => return Err(format!("bad fade time '{}'\n{}", value, Options::USAGE)),
+                    };
+                }
+                "--lines" => {
+                    let value = Options::value(&arg, args.next())?;
+                    lines = match value.parse::<i32>() {
+                        Ok(lines) if lines > 0 => Some(lines),
+                        _ => return Err(format!("bad line count '{}'\n{}", value, Options::USAGE)),
+                    };
+                }
+                "--rows" => {
+                    let value = Options::value(&arg, args.next())?;
+                    dig.rows = match value.parse::<i32>() {
+                        Ok(rows) if rows > 0 => rows,
+                        _ => return Err(format!("bad row count '{}'\n{}", value, Options::USAGE)),
+                    };
+                    dig_options = true;
+                }
+                "--messiness" => {
+                    let value = Options::value(&arg, args.next())?;
+                    dig.messiness = match value.parse::<f64>() {
+                        Ok(messiness) if (0.0..=1.0).contains(&messiness) => messiness,
+                        _ => return Err(format!("bad messiness '{}'\n{}", value, Options::USAGE)),
+                    };
+                    dig_options = true;
+                }
+                "--refill" => {
+                    dig.refill = true;
+                    dig_options = true;
+                }
+                _ => return Err(format!("unknown argument '{}'\n{}", arg, Options::USAGE)),
+            }
+        }
+        let modes = [
+            options.demo,
+            options.tbp.is_some(),
+            options.versus,
+            options.connect.is_some(),
+            options.spectate.is_some(),
+        ];
+        if modes.iter().filter(|m| **m).count() > 1 {
+            return Err(format!("only one mode can be chosen\n{}", Options::USAGE));
+        }
+        let single_player = !options.versus && options.connect.is_none() && options.spectate.is_none();
+        if options.broadcast.is_some() && !single_player {
+            return Err(format!("only single player games can be broadcast\n{}", Options::USAGE));
+        }
+        let variants = big || cascade || color_match.is_some();
+        if (rules || options.pieces.is_some()) && !single_player {
+            return Err(format!(
+                "rules and pieces are for single player games\n{}",
+                Options::USAGE
+            ));
+        }
+        if variants && !single_player {
+            return Err(format!("variants are for single player games\n{}", Options::USAGE));
+        }
+        if options.view != View::default() && !single_player {
+            return Err(format!(
+                "mirror and upside-down are for single player games\n{}",
+                Options::USAGE
+            ));
+        }
+        // TBP locations are those of the SRS shapes, which nes and tgm don't use
+        let srs_shapes = matches!(options.ruleset.rotation, RotationSystem::Kickless | RotationSystem::Srs);
+        if !srs_shapes && options.tbp.is_some() {
+            return Err(format!(
+                "TBP bots play by the rustris or guideline rules only\n{}",
+                Options::USAGE
+            ));
+        }
+        if big && options.tbp.is_some() {
+            return Err(format!("TBP bots don't play big pieces\n{}", Options::USAGE));
+        }
+        if (cascade || color_match.is_some()) && options.tbp.is_some() {
+            return Err(format!(
+                "TBP bots don't know cascade gravity or color matches\n{}",
+                Options::USAGE
+            ));
+        }
+        // read here so that TBP bots never get pieces they don't know
+        if let Some(path) = &options.pieces {
+            let pieces = PieceSet::load(path)?;
+            if options.tbp.is_some() && !pieces.is_tetrominoes() {
+                return Err(format!("{}: TBP bots only know the seven tetrominoes", path));
+            }
+            options.ruleset.rotation = pieces.into_system();
+        }
+        // otherwise every piece would clear as soon as it locks
+        let largest = options.ruleset.rotation.largest_piece();
+        if color_match.is_some_and(|size| size <= largest) {
+            return Err(format!(
+                "--color-match needs groups of more than {} blocks, the biggest piece\n{}",
+                largest,
+                Options::USAGE
+            ));
+        }
+        options.ruleset.big = big;
+        options.ruleset.cascade = cascade;
+        options.ruleset.color_match = color_match;
+        if !goals.is_empty() && !single_player {
+            return Err(format!("goals are for single player games\n{}", Options::USAGE));
+        }
+        if goals.len() > 1 {
+            return Err(format!(
+                "only one of sprint, ultra, dig, survival and invisible can be chosen\n{}",
+                Options::USAGE
+            ));
+        }
+        let goal = goals.first().map(|goal| goal.as_str());
+        if lines.is_some() && goal != Some("--sprint") {
+            return Err(format!("--lines needs --sprint\n{}", Options::USAGE));
+        }
+        if seconds.is_some() && goal != Some("--ultra") {
+            return Err(format!("--seconds needs --ultra\n{}", Options::USAGE));
+        }
+        if fade.is_some() && goal != Some("--invisible") {
+            return Err(format!("--fade needs --invisible\n{}", Options::USAGE));
+        }
+        if dig_options && goal != Some("--dig") {
+            return Err(format!(
+                "--rows, --messiness and --refill need --dig\n{}",
+                Options::USAGE
+            ));
+        }
+        options.goal = match goal {
+            Some("--sprint") => Some(Goal::Sprint(lines.unwrap_or(SPRINT_LINES))),
+            Some("--ultra") => Some(Goal::Ultra(seconds.unwrap_or(ULTRA_TIME))),
+            Some("--dig") => Some(Goal::Dig(dig)),
+            Some("--invisible") => Some(Goal::Invisible(fade.unwrap_or(FADE_TIME))),
+            Some(_) => Some(Goal::Survival(SurvivalConfig::new())),
+            None => None,
+        };
+        return Ok(options);
+    }
+
+    fn value(arg: &str, value: Option<String>) -> Result<String, String> {
+        return value.ok_or(format!("missing value for {}\n{}", arg, Options::USAGE));
+    }
+}
@@ -0,0 +1,120 @@
+use rustris_ai::pc_solver::{PcSolver, SearchMode};
+use rustris_core::game::Game;
+use rustris_core::model::{Board, Tetromino, TetrominoType};
+use rustris_core::ruleset::Ruleset;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+const PC_HEIGHT: i32 = 4;
+const MAX_NODES: usize = 20000;
+
+pub enum HintState {
+    Off,
+    Searching,
+    NoSolution,
+    Solution { cells: Vec<(i32, i32)>, pieces: usize },
+}
+
+// The pieces placed so far, the held piece and if it can be swapped yet,
+// which change with every lock or hold.
+type Position = (i32, Option<TetrominoType>, bool);
+
+struct Request {
+    position: Position,
+    board: Board,
+    ruleset: Ruleset,
+    current: Tetromino,
+    held: Option<TetrominoType>,
+    can_hold: bool,
+    queue: Vec<Tetromino>,
+}
+
+// Searches perfect clears on a worker thread, so the game never waits for it.
+pub struct PcHint {
+    requests: Sender<Request>,
+    results: Receiver<(Position, HintState)>,
+    // of the latest request
+    position: Option<Position>,
+    state: HintState,
+}
+
+impl PcHint {
+    pub fn new() -> PcHint {
+        let (requests, request_rx) = channel::<Request>();
+        let (result_tx, results) = channel();
+        std::thread::spawn(move || {
+            let mut solver = PcSolver::new(PC_HEIGHT, SearchMode::FirstSolution);
+            solver.max_nodes = MAX_NODES;
+            while let Ok(mut request) = request_rx.recv() {
+                // only the latest position is of interest
+                while let Ok(newer) = request_rx.try_recv() {
+                    request = newer;
+                }
+                solver.hold_used = !request.can_hold;
+                let result = solver.solve(
+                    &request.board,
+                    &request.ruleset,
+                    &request.current,
+                    request.held,
+                    &request.queue,
+                );
+                let state = match result.solutions.first() {
+                    Some(steps) => HintState::Solution {
+                        cells: steps[0].placement.cells(),
+                        pieces: steps.len(),
+                    },
+                    None => HintState::NoSolution,
+                };
+                if result_tx.send((request.position, state)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        return PcHint {
+            requests,
+            results,
+            position: None,
+            state: HintState::Off,
+        };
+    }
+
+    pub fn toggle(&mut self) {
+        self.state = match self.state {
+            HintState::Off => HintState::Searching,
+            _ => HintState::Off,
+        };
+        self.position = None;
+    }
+
+    pub fn update(&mut self, game: &Game) {
+        while let Ok((position, state)) = self.results.try_recv() {
+            let current = !matches!(self.state, HintState::Off) && Some(position) == self.position;
+            if current {
+                self.state = state;
+            }
+        }
+        let position = (game.stats().pieces, game.held_piece(), game.can_hold());
+        if matches!(self.state, HintState::Off) || Some(position) == self.position {
+            return;
+        }
+
+        self.position = Some(position);
+        self.state = HintState::Searching;
+        let request = Request {
+            position,
+            board: game.current_board().clone(),
+            ruleset: game.ruleset().clone(),
+            current: game.active_piece().clone(),
+            held: game.held_piece(),
+            can_hold: game.can_hold(),
+            queue: game.preview_pieces().cloned().collect(),
+        };
+        if self.requests.send(request).is_err() {
+            self.state = HintState::NoSolution;
+        }
+    }
+
+    pub fn state(&self) -> &HintState {
+        return &self.state;
+    }
+}
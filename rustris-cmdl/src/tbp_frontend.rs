@@ -0,0 +1,251 @@
+use rustris_ai::evaluator::Weights;
+use rustris_ai::player::{AiPlayer, Route};
+use rustris_ai::tbp::{
+    board_rows, from_line, location_cells, move_of, piece_name, to_line, BotMessage, FrontendMessage, Move, Start,
+};
+use rustris_core::game::Game;
+use rustris_core::model::UserInput;
+use rustris_core::placement::Placement;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Lets a bot process speaking the Tetris Bot Protocol play the game.
+pub struct TbpFrontend {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<BotMessage>,
+    started: bool,
+    piece_number: i32,
+    waiting_for_suggestion: bool,
+    target: Option<Route>,
+    // plays when none of the suggested moves is possible in rustris
+    fallback: AiPlayer,
+}
+
+impl TbpFrontend {
+    pub fn launch(command_line: &str) -> Result<TbpFrontend, String> {
+        let mut words = command_line.split_whitespace();
+        let program = words.next().ok_or("empty bot command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("can't start bot '{}': {}", command_line, err))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, messages) = channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                // messages we don't know are skipped, as TBP asks for
+                if let Some(message) = from_line::<BotMessage>(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let mut frontend = TbpFrontend {
+            child,
+            stdin,
+            messages,
+            started: false,
+            piece_number: -1,
+            waiting_for_suggestion: false,
+            target: None,
+            fallback: AiPlayer::new(Weights::default()),
+        };
+        frontend.handshake()?;
+        return Ok(frontend);
+    }
+
+    fn handshake(&mut self) -> Result<(), String> {
+        match self.receive()? {
+            BotMessage::Info { .. } => (),
+            other => return Err(format!("bot sent {:?} instead of info", other)),
+        }
+        self.send(&FrontendMessage::Rules { randomizer: None })?;
+        return match self.receive()? {
+            BotMessage::Ready => Ok(()),
+            BotMessage::Error { reason } => Err(format!("bot refused the rules: {}", reason)),
+            other => Err(format!("bot sent {:?} instead of ready", other)),
+        };
+    }
+
+    fn receive(&self) -> Result<BotMessage, String> {
+        return match self.messages.recv_timeout(HANDSHAKE_TIMEOUT) {
+            Ok(message) => Ok(message),
+            Err(RecvTimeoutError::Timeout) => Err(String::from("bot did not answer in time")),
+            Err(RecvTimeoutError::Disconnected) => Err(String::from("bot quit")),
+        };
+    }
+
+    fn send(&mut self, message: &FrontendMessage) -> Result<(), String> {
+        return writeln!(self.stdin, "{}", to_line(message))
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("can't talk to bot: {}", err));
+    }
+
+    // Returns one input per call, like `AiPlayer::next_input`.
+    pub fn next_input(&mut self, game: &Game) -> UserInput {
+        if game.is_game_over() {
+            if self.started {
+                let _ = self.send(&FrontendMessage::Stop);
+                self.started = false;
+            }
+            return UserInput::Reset;
+        }
+
+        let pieces = game.stats().pieces;
+        if pieces != self.piece_number {
+            self.target = None;
+        }
+        if !self.started {
+            // after a bad suggestion, the fallback finishes the piece first
+            if self.target.is_none() {
+                self.start(game);
+            }
+        } else if pieces != self.piece_number {
+            // every locked piece reveals one more piece at the end of the preview
+            let preview = game.preview();
+            let revealed = ((pieces - self.piece_number) as usize).min(preview.len());
+            for t_type in preview[preview.len() - revealed..].iter() {
+                if let Some(piece) = piece_name(*t_type) {
+                    let _ = self.send(&FrontendMessage::NewPiece { piece });
+                }
+            }
+            self.piece_number = pieces;
+        }
+
+        let route = match &mut self.target {
+            Some(route) => route,
+            None => return self.await_suggestion(game),
+        };
+        if let Some(input) = route.next_input(game) {
+            return input;
+        }
+
+        // the piece can't get where the bot wants it after all, so the bot is
+        // stopped and told about the real board with the next piece; the
+        // fallback places this one
+        if self.started {
+            let _ = self.send(&FrontendMessage::Stop);
+            self.started = false;
+        }
+        self.target = self
+            .fallback
+            .best_placement(game)
+            .map(|placement| Route::new(game, &placement));
+        return UserInput::NoInput;
+    }
+
+    fn start(&mut self, game: &Game) {
+        let pieces = std::iter::once(game.active_piece().t_type).chain(game.preview());
+        // `Options::parse` keeps other piece sets away from TBP bots; the
+        // fallback would play them
+        let queue = match pieces.map(piece_name).collect::<Option<Vec<String>>>() {
+            Some(queue) => queue,
+            None => {
+                self.target = self
+                    .fallback
+                    .best_placement(game)
+                    .map(|placement| Route::new(game, &placement));
+                return;
+            }
+        };
+        // TBP counts the clears of a combo, rustris the ones after the first
+        let start = Start {
+            hold: game.held_piece().and_then(piece_name),
+            queue,
+            combo: (game.combo() + 1) as u32,
+            back_to_back: game.back_to_back(),
+            board: board_rows(game.current_board()),
+        };
+        self.started = self.send(&FrontendMessage::Start(start)).is_ok();
+        self.piece_number = game.stats().pieces;
+        self.waiting_for_suggestion = false;
+        self.target = None;
+    }
+
+    fn await_suggestion(&mut self, game: &Game) -> UserInput {
+        if !self.waiting_for_suggestion {
+            self.waiting_for_suggestion = self.send(&FrontendMessage::Suggest).is_ok();
+            return UserInput::NoInput;
+        }
+
+        let moves = match self.messages.try_recv() {
+            Ok(BotMessage::Suggestion { moves }) => moves,
+            _ => return UserInput::NoInput,
+        };
+        self.waiting_for_suggestion = false;
+
+        // a suggested move is echoed as is; the fallback's own move otherwise,
+        // out of the placements TBP has a location for
+        let board = game.current_board();
+        let picked = match self.pick(game, &moves) {
+            Some((placement, mv)) => Some((placement, mv.clone())),
+            None => {
+                let placements = game
+                    .placements()
+                    .into_iter()
+                    .filter(|placement| move_of(placement, board).is_some())
+                    .collect();
+                self.fallback
+                    .best_of(board, game.ruleset(), placements)
+                    .and_then(|placement| move_of(&placement, board).map(|mv| (placement, mv)))
+            }
+        };
+        let target = match picked {
+            Some((placement, mv)) => {
+                let _ = self.send(&FrontendMessage::Play { mv });
+                Some(Route::new(game, &placement))
+            }
+            None => None,
+        };
+        self.target = target;
+        return UserInput::NoInput;
+    }
+
+    // The first suggested move rustris can actually play. Moves that need
+    // the hold slot or kicks rustris doesn't have are skipped.
+    fn pick<'a>(&self, game: &Game, moves: &'a [Move]) -> Option<(Placement, &'a Move)> {
+        let t_type = game.active_piece().t_type;
+        let board = game.current_board();
+        let placements = game.placements();
+        for mv in moves {
+            let location = &mv.location;
+            if piece_name(t_type).as_ref() != Some(&location.piece) {
+                continue;
+            }
+            let cells = match location_cells(t_type, location.orientation, location.x, location.y, board) {
+                Some(cells) => cells,
+                None => continue,
+            };
+            let found = placements.iter().find(|p| {
+                let mut placement_cells = p.cells();
+                placement_cells.sort_unstable();
+                placement_cells == cells
+            });
+            if let Some(placement) = found {
+                return Some((placement.clone(), mv));
+            }
+        }
+        return None;
+    }
+
+    pub fn quit(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,211 @@
+use crate::curses_ui::{UiInput, UI};
+use rustris_core::model::{Board, CellVal, Tetromino, UserInput};
+use rustris_core::versus::{Match, PLAYERS};
+
+// What the split-screen layout shows of one player.
+pub struct Side<'a> {
+    pub board: &'a Board,
+    // `None` shows the board without a falling piece
+    pub piece: Option<&'a Tetromino>,
+    pub next: Option<&'a Tetromino>,
+    pub garbage: i32,
+    pub sent: i32,
+    pub lines: i32,
+    pub wins: i32,
+}
+
+// Split-screen layout for two players: the boards left and right, the match
+// panel in between.
+pub struct VersusUI {
+    screen: pancurses::Window,
+    app_win: pancurses::Window,
+    boards: [pancurses::Window; PLAYERS],
+    panel: pancurses::Window,
+}
+
+impl VersusUI {
+    const WIDTH: i32 = 72;
+    const HEIGHT: i32 = 22;
+
+    pub fn new() -> Result<VersusUI, String> {
+        let screen = pancurses::initscr();
+        let width = screen.get_max_x();
+        let height = screen.get_max_y();
+
+        if width < VersusUI::WIDTH || height < VersusUI::HEIGHT {
+            pancurses::endwin();
+            return Err(format!(
+                "Not enough space in terminal; need {}x{}, have {}x{}",
+                VersusUI::WIDTH,
+                VersusUI::HEIGHT,
+                width,
+                height
+            ));
+        }
+        screen.clear();
+        screen.nodelay(true);
+        screen.keypad(true);
+        pancurses::noecho();
+        pancurses::cbreak();
+        pancurses::curs_set(0);
+
+        let app_win = VersusUI::subwin(&screen, VersusUI::WIDTH, 0);
+        let boards = [VersusUI::subwin(&app_win, 24, 0), VersusUI::subwin(&app_win, 24, 48)];
+        for board in boards.iter() {
+            UI::draw_panel_border(board, 0);
+        }
+        let panel = VersusUI::create_panel(&app_win);
+        return Ok(VersusUI {
+            screen,
+            app_win,
+            boards,
+            panel,
+        });
+    }
+
+    fn subwin(parent: &pancurses::Window, width: i32, x: i32) -> pancurses::Window {
+        return match parent.subwin(VersusUI::HEIGHT, width, 0, x) {
+            Ok(win) => win,
+            Err(code) => panic!("pancurses subwin function failed w/ result code {}", code),
+        };
+    }
+
+    pub fn destroy(&self) {
+        pancurses::endwin();
+    }
+
+    // All keys pressed since the last frame, as (player, input) in order.
+    pub fn read_inputs(&self) -> (Vec<(usize, UserInput)>, UiInput) {
+        let mut inputs = Vec::new();
+        let mut ui_input = UiInput::NoInput;
+        while let Some(ch) = self.screen.getch() {
+            let input = match ch {
+                pancurses::Input::Character('a') => (0, UserInput::MoveLeft),
+                pancurses::Input::Character('d') => (0, UserInput::MoveRight),
+                pancurses::Input::Character('s') => (0, UserInput::MoveDown),
+                pancurses::Input::Character('w') => (0, UserInput::DropDown),
+                pancurses::Input::Character('z') => (0, UserInput::RotateLeft),
+                pancurses::Input::Character('x') => (0, UserInput::RotateRight),
+                pancurses::Input::KeyLeft => (1, UserInput::MoveLeft),
+                pancurses::Input::KeyRight => (1, UserInput::MoveRight),
+                pancurses::Input::KeyDown => (1, UserInput::MoveDown),
+                pancurses::Input::KeyUp => (1, UserInput::DropDown),
+                pancurses::Input::Character(',') => (1, UserInput::RotateLeft),
+                pancurses::Input::Character('.') => (1, UserInput::RotateRight),
+                pancurses::Input::Character('n') => (0, UserInput::Reset),
+                pancurses::Input::Character('q') => {
+                    ui_input = UiInput::UserWantsToQuit;
+                    continue;
+                }
+                _ => continue,
+            };
+            inputs.push(input);
+        }
+        return (inputs, ui_input);
+    }
+
+    pub fn draw(&mut self, versus: &Match) {
+        let side = |player: usize| {
+            let game = versus.game(player);
+            return Side {
+                board: game.current_board(),
+                piece: Some(game.active_piece()),
+                next: Some(game.next_piece()),
+                garbage: versus.incoming_garbage(player),
+                sent: versus.lines_sent(player),
+                lines: game.stats().cleared,
+                wins: versus.wins(player),
+            };
+        };
+        let status = match (versus.is_over(), versus.winner()) {
+            (false, _) => String::new(),
+            (true, Some(winner)) => format!(" PLAYER {} WINS, n: next", winner + 1),
+            (true, None) => String::from(" DRAW, n: next round"),
+        };
+        self.draw_sides([side(0), side(1)], ["Player 1", "Player 2"], &status);
+    }
+
+    pub fn draw_sides(&mut self, sides: [Side; PLAYERS], titles: [&str; PLAYERS], status: &str) {
+        self.panel
+            .mvprintw(1, 0, format!("  {:10.10}  {:10.10}", titles[0], titles[1]));
+        for (player, side) in sides.iter().enumerate() {
+            self.draw_board(player, side);
+            self.draw_next_piece(player, side.next);
+
+            let column = 10 + 7 * player as i32;
+            self.panel.mvprintw(7, column, format!("{:4}", side.garbage));
+            self.panel.mvprintw(8, column, format!("{:4}", side.sent));
+            self.panel.mvprintw(9, column, format!("{:4}", side.lines));
+            self.panel.mvprintw(10, column, format!("{:4}", side.wins));
+        }
+        self.panel.mvprintw(19, 0, format!("{:24.24}", status));
+
+        self.app_win.touch();
+        self.app_win.refresh();
+    }
+
+    fn draw_board(&self, player: usize, side: &Side) {
+        let window = &self.boards[player];
+        let board = side.board;
+        for y in 0..board.height() {
+            for x in 0..board.width() {
+                window.mv(1 + y, 2 + 2 * x);
+                window.printw(UI::cell_string(&board.at(x, y)));
+            }
+        }
+
+        let piece = match side.piece {
+            Some(piece) => piece,
+            None => return,
+        };
+        for y in 0..piece.height() {
+            for x in 0..piece.width() {
+                if piece.at(x, y) != CellVal::Free {
+                    window.mv(1 + piece.offset.1 + y, 2 + 2 * (piece.offset.0 + x));
+                    window.printw("[]");
+                }
+            }
+        }
+    }
+
+    fn draw_next_piece(&self, player: usize, piece: Option<&Tetromino>) {
+        let column = 3 + 11 * player as i32;
+        for y in 0..3 {
+            for x in 0..4 {
+                let val = match piece {
+                    Some(piece) => piece.at(x, y),
+                    None => CellVal::Free,
+                };
+                self.panel.mv(3 + y, column + 2 * x);
+                self.panel.printw(UI::cell_string(&val));
+            }
+        }
+    }
+
+    fn create_panel(app_win: &pancurses::Window) -> pancurses::Window {
+        let panel = VersusUI::subwin(app_win, 24, 24);
+        panel.mvaddstr(00, 0, "+                      +");
+        panel.mvaddstr(01, 0, "                        ");
+        panel.mvaddstr(02, 0, "                        ");
+        panel.mvaddstr(03, 0, "                        ");
+        panel.mvaddstr(04, 0, "                        ");
+        panel.mvaddstr(05, 0, "                        ");
+        panel.mvaddstr(06, 0, "                        ");
+        panel.mvaddstr(07, 0, " Garbage   ####   ####  ");
+        panel.mvaddstr(08, 0, " Sent      ####   ####  ");
+        panel.mvaddstr(09, 0, " Lines     ####   ####  ");
+        panel.mvaddstr(10, 0, " Wins      ####   ####  ");
+        panel.mvaddstr(11, 0, "                        ");
+        panel.mvaddstr(12, 0, " Keys:     P1     P2    ");
+        panel.mvaddstr(13, 0, "  Left      a    Left   ");
+        panel.mvaddstr(14, 0, "  Right     d    Right  ");
+        panel.mvaddstr(15, 0, "  Down      s    Down   ");
+        panel.mvaddstr(16, 0, "  Drop      w    Up     ");
+        panel.mvaddstr(17, 0, "  Rotate   z x   , .    ");
+        panel.mvaddstr(18, 0, "                        ");
+        panel.mvaddstr(19, 0, "                        ");
+        panel.mvaddstr(20, 0, "  n - Next round q-Quit ");
+        panel.mvaddstr(21, 0, "+                      +");
+        return panel;
+    }
+}
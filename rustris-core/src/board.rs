@@ -1,10 +1,47 @@
 use crate::model::Tetromino;
-use crate::model::{Board, CellVal};
+use crate::model::{Board, CellVal, BOARD_DIM_X, BOARD_DIM_Y};
+use crate::ruleset::Ruleset;
+use rand::Rng;
 
+// The group of every cell, see `settle`; 0 for free cells.
+type Labels = [[usize; BOARD_DIM_X]; BOARD_DIM_Y];
+
+// What one step of `clear_after_lock` removed.
+#[derive(Clone, Debug)]
+pub struct ClearStep {
+    // full rows, where they were before they were removed
+    pub rows: Vec<i32>,
+    // see `clear_color_groups`
+    pub groups: Vec<(CellVal, Vec<(i32, i32)>)>,
+}
+
+impl ClearStep {
+    pub fn is_empty(&self) -> bool {
+        return self.rows.is_empty() && self.groups.is_empty();
+    }
+
+    // Big pieces clear their rows in pairs, which count as one.
+    pub fn row_count(&self, scale: i32) -> i32 {
+        return (self.rows.len() as i32 + scale - 1) / scale;
+    }
+
+    pub fn minos(&self, scale: i32) -> i32 {
+        let cells: usize = self.groups.iter().map(|(_, cells)| cells.len()).sum();
+        return cells as i32 / (scale * scale);
+    }
+}
+
+#[derive(Clone)]
 pub struct BoardController {
     pub board: Board,
 }
 
+impl Default for BoardController {
+    fn default() -> BoardController {
+        return BoardController::new();
+    }
+}
+
 impl BoardController {
     pub fn new() -> BoardController {
         return BoardController { board: Board::new() };
@@ -16,12 +53,48 @@ impl BoardController {
                 self.board.set(x, y, CellVal::Free);
             }
         }
+        self.board.ages = [[0; BOARD_DIM_X]; BOARD_DIM_Y];
+        self.board.fade = None;
+        self.board.reveal = 0;
+        self.board.inc_change_count();
+    }
+
+    // Hides cells after `frames` from now on, or shows all of them again.
+    pub fn set_fade(&mut self, frames: Option<u32>) {
+        self.board.fade = frames;
         self.board.inc_change_count();
     }
 
+    // Shows every cell for the next `frames`, or longer if it already does.
+    pub fn reveal(&mut self, frames: u32) {
+        if frames > self.board.reveal {
+            self.board.reveal = frames;
+            self.board.inc_change_count();
+        }
+    }
+
+    // One frame passed.
+    pub fn tick(&mut self) {
+        let fade = match self.board.fade {
+            Some(fade) => fade,
+            None => return,
+        };
+        let mut changed = self.board.reveal == 1;
+        self.board.reveal = self.board.reveal.saturating_sub(1);
+        for row in self.board.ages.iter_mut() {
+            for age in row.iter_mut() {
+                *age = age.saturating_add(1);
+                changed |= *age == fade;
+            }
+        }
+        if changed {
+            self.board.inc_change_count();
+        }
+    }
+
     pub fn freeze_tetromino(&mut self, tetromino: &Tetromino) {
-        for y in 0..4 {
-            for x in 0..4 {
+        for y in 0..tetromino.height() {
+            for x in 0..tetromino.width() {
                 let bx = tetromino.offset.0 + x;
                 let by = tetromino.offset.1 + y;
                 let in_range = (bx >= 0) && (bx < self.board.width()) && (by >= 0) && (by < self.board.height());
@@ -32,7 +105,10 @@ impl BoardController {
                 let color = tetromino.at(x, y);
                 match color {
                     CellVal::Free => (),
-                    _ => self.board.set(bx, by, color),
+                    _ => {
+                        self.board.set(bx, by, color);
+                        self.board.ages[by as usize][bx as usize] = 0;
+                    }
                 };
             }
         }
@@ -41,9 +117,8 @@ impl BoardController {
 
     fn is_row_full(&self, row: i32) -> bool {
         for x in 0..self.board.width() {
-            match self.board.at(x, row) {
-                CellVal::Free => return false,
-                _ => (),
+            if let CellVal::Free = self.board.at(x, row) {
+                return false;
             }
         }
         return true;
@@ -55,6 +130,7 @@ impl BoardController {
                 let val = self.board.at(x, y);
                 self.board.set(x, y + 1, val);
             }
+            self.board.ages[y as usize + 1] = self.board.ages[y as usize];
         }
         for x in 0..self.board.width() {
             self.board.set(x, 0, CellVal::Free);
@@ -67,10 +143,310 @@ impl BoardController {
         for y in 0..self.board.height() {
             if self.is_row_full(y) {
                 self.remove_row(y);
-                count = count + 1;
+                count += 1;
             }
         }
         self.board.inc_change_count();
         return count;
     }
+
+    // Clears what a lock clears by the rules: full rows, and groups of
+    // `Ruleset::color_match` minos of `scale` cells per side. With
+    // `Ruleset::cascade`, the blocks then settle and clear again, as long as
+    // anything clears. Returns the first step, then those of the chain.
+    pub fn clear_after_lock(&mut self, ruleset: &Ruleset, scale: i32) -> Vec<ClearStep> {
+        let mut steps = vec![self.clear_step(ruleset, scale)];
+        if steps[0].is_empty() || !ruleset.cascade {
+            return steps;
+        }
+        while self.settle(scale) {
+            let step = self.clear_step(ruleset, scale);
+            if step.is_empty() {
+                break;
+            }
+            steps.push(step);
+        }
+        return steps;
+    }
+
+    fn clear_step(&mut self, ruleset: &Ruleset, scale: i32) -> ClearStep {
+        let rows = self.full_rows();
+        if !rows.is_empty() {
+            self.clear_full_rows();
+        }
+        let groups = match ruleset.color_match {
+            Some(min) => self.clear_color_groups(min * (scale * scale) as usize),
+            None => Vec::new(),
+        };
+        return ClearStep { rows, groups };
+    }
+
+    // The rows `clear_full_rows` would remove.
+    pub fn full_rows(&self) -> Vec<i32> {
+        return (0..self.board.height()).filter(|y| self.is_row_full(*y)).collect();
+    }
+
+    // Removes every group of at least `min` connected cells of one color,
+    // and returns their colors and cells. Garbage isn't a color.
+    pub fn clear_color_groups(&mut self, min: usize) -> Vec<(CellVal, Vec<(i32, i32)>)> {
+        let mut labels = [[0; BOARD_DIM_X]; BOARD_DIM_Y];
+        let mut groups = Vec::new();
+        for y in 0..self.board.height() {
+            for x in 0..self.board.width() {
+                let color = self.board.at(x, y);
+                if matches!(color, CellVal::Free | CellVal::Garbage) || labels[y as usize][x as usize] != 0 {
+                    continue;
+                }
+                let cells = self.group_at(x, y, 1, &mut labels);
+                if cells.len() >= min {
+                    groups.push((color, cells));
+                }
+            }
+        }
+        for (_, cells) in groups.iter() {
+            for (x, y) in cells.iter() {
+                self.board.set(*x, *y, CellVal::Free);
+            }
+        }
+        if !groups.is_empty() {
+            self.board.inc_change_count();
+        }
+        return groups;
+    }
+
+    // Lets every group of connected cells of one color fall as far as it can,
+    // as a whole, into the space below it. Groups fall `scale` rows at a time,
+    // so big minos stay on their grid. Returns if anything fell.
+    pub fn settle(&mut self, scale: i32) -> bool {
+        let mut labels = [[0; BOARD_DIM_X]; BOARD_DIM_Y];
+        let mut groups = Vec::new();
+        for y in 0..self.board.height() {
+            for x in 0..self.board.width() {
+                if self.board.at(x, y) != CellVal::Free && labels[y as usize][x as usize] == 0 {
+                    let group = self.group_at(x, y, groups.len() + 1, &mut labels);
+                    groups.push(group);
+                }
+            }
+        }
+
+        let mut fell = false;
+        let mut moved = true;
+        while moved {
+            moved = false;
+            // the lowest groups first, so the ones above can land on them
+            for (i, cells) in groups.iter_mut().enumerate().rev() {
+                while self.can_fall(cells, i + 1, scale, &labels) {
+                    self.lower(cells, i + 1, scale, &mut labels);
+                    moved = true;
+                    fell = true;
+                }
+            }
+        }
+        if fell {
+            self.board.inc_change_count();
+        }
+        return fell;
+    }
+
+    // The cells connected to (x, y) that have its color, marked with `label`.
+    fn group_at(&self, x: i32, y: i32, label: usize, labels: &mut Labels) -> Vec<(i32, i32)> {
+        let color = self.board.at(x, y);
+        let mut cells = vec![(x, y)];
+        labels[y as usize][x as usize] = label;
+        let mut next = 0;
+        while next < cells.len() {
+            let (x, y) = cells[next];
+            next += 1;
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                let in_range = nx >= 0 && nx < self.board.width() && ny >= 0 && ny < self.board.height();
+                if in_range && labels[ny as usize][nx as usize] == 0 && self.board.at(nx, ny) == color {
+                    labels[ny as usize][nx as usize] = label;
+                    cells.push((nx, ny));
+                }
+            }
+        }
+        return cells;
+    }
+
+    fn can_fall(&self, cells: &[(i32, i32)], label: usize, rows: i32, labels: &Labels) -> bool {
+        return cells.iter().all(|(x, y)| {
+            if y + rows >= self.board.height() {
+                return false;
+            }
+            return (y + 1..=y + rows).all(|below| {
+                let below = labels[below as usize][*x as usize];
+                return below == 0 || below == label;
+            });
+        });
+    }
+
+    fn lower(&mut self, cells: &mut [(i32, i32)], label: usize, rows: i32, labels: &mut Labels) {
+        let taken: Vec<(CellVal, u32)> = cells
+            .iter()
+            .map(|(x, y)| (self.board.at(*x, *y), self.board.ages[*y as usize][*x as usize]))
+            .collect();
+        for (x, y) in cells.iter() {
+            self.board.set(*x, *y, CellVal::Free);
+            labels[*y as usize][*x as usize] = 0;
+        }
+        for ((x, y), (val, age)) in cells.iter_mut().zip(taken) {
+            *y += rows;
+            self.board.set(*x, *y, val);
+            self.board.ages[*y as usize][*x as usize] = age;
+            labels[*y as usize][*x as usize] = label;
+        }
+    }
+
+    // Pushes the stack up by `rows` and fills the rows that come in at the
+    // bottom with garbage, open in one column each. The first row is open at
+    // `hole` (moved onto the board if outside), or at a random column. Every
+    // further row moves its hole to
+    // another random column with a chance of `messiness` (0.0 keeps one
+    // straight well, 1.0 moves it every row). Rows and columns are `scale`
+    // cells wide, like the minos of big pieces. Returns whether blocks were
+    // pushed out at the top.
+    pub fn insert_garbage<R: Rng>(
+        &mut self,
+        rows: i32,
+        hole: Option<i32>,
+        messiness: f64,
+        scale: i32,
+        rng: &mut R,
+    ) -> bool {
+        let width = self.board.width();
+        let columns = (width / scale).max(1);
+        let rows = rows.clamp(0, self.board.height() / scale);
+        let top = self.board.height() - rows * scale;
+        let overflow = self.push_up(rows * scale);
+
+        let mut hole = match hole {
+            Some(hole) => hole.clamp(0, columns - 1),
+            None => rng.gen_range(0, columns),
+        };
+        // the rows rise in order, so the first one ends up at the top
+        for y in top..self.board.height() {
+            if y > top && (y - top) % scale == 0 {
+                hole = next_hole(hole, columns, messiness, rng);
+            }
+            for x in 0..width {
+                let val = match x / scale == hole {
+                    true => CellVal::Free,
+                    false => CellVal::Garbage,
+                };
+                self.board.set(x, y, val);
+            }
+            self.board.ages[y as usize] = [0; BOARD_DIM_X];
+        }
+        self.board.inc_change_count();
+        return overflow;
+    }
+
+    // Moves every row up; the bottom rows are left as they were.
+    fn push_up(&mut self, rows: i32) -> bool {
+        let mut overflow = false;
+        for y in 0..rows {
+            for x in 0..self.board.width() {
+                if self.board.at(x, y) != CellVal::Free {
+                    overflow = true;
+                }
+            }
+        }
+        for y in rows..self.board.height() {
+            for x in 0..self.board.width() {
+                let val = self.board.at(x, y);
+                self.board.set(x, y - rows, val);
+            }
+            self.board.ages[(y - rows) as usize] = self.board.ages[y as usize];
+        }
+        return overflow;
+    }
+}
+
+// The column the next garbage row is open at: `hole` moved to another column
+// with a chance of `messiness`. With a single column, it has nowhere to go.
+fn next_hole<R: Rng>(hole: i32, width: i32, messiness: f64, rng: &mut R) -> i32 {
+    if width < 2 || !rng.gen_bool(messiness.clamp(0.0, 1.0)) {
+        return hole;
+    }
+    return (hole + rng.gen_range(1, width)) % width;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn open_columns(board: &Board, y: i32) -> Vec<i32> {
+        return (0..board.width())
+            .filter(|x| board.at(*x, y) == CellVal::Free)
+            .collect();
+    }
+
+    #[test]
+    fn garbage_hole_outside_the_board_is_moved_onto_it() {
+        let mut board = BoardController::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        board.insert_garbage(1, Some(-3), 0.0, 1, &mut rng);
+        board.insert_garbage(1, Some(42), 0.0, 1, &mut rng);
+        let bottom = board.board.height() - 1;
+        assert_eq!(open_columns(&board.board, bottom - 1), vec![0]);
+        assert_eq!(open_columns(&board.board, bottom), vec![board.board.width() - 1]);
+    }
+
+    #[test]
+    fn messy_garbage_moves_the_hole_every_row() {
+        let mut board = BoardController::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        board.insert_garbage(6, Some(3), 1.0, 1, &mut rng);
+        let height = board.board.height();
+        let holes: Vec<Vec<i32>> = (height - 6..height).map(|y| open_columns(&board.board, y)).collect();
+        assert_eq!(holes[0], vec![3]);
+        for pair in holes.windows(2) {
+            assert_eq!(pair[1].len(), 1);
+            assert_ne!(pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn big_garbage_rows_are_two_cells_high_with_two_cells_open() {
+        let mut board = BoardController::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        board.insert_garbage(3, Some(1), 1.0, 2, &mut rng);
+        let height = board.board.height();
+        assert_eq!(
+            open_columns(&board.board, height - 7).len(),
+            board.board.width() as usize
+        );
+        assert_eq!(open_columns(&board.board, height - 6), vec![2, 3]);
+        for y in (height - 6..height).step_by(2) {
+            let hole = open_columns(&board.board, y);
+            assert_eq!(hole.len(), 2);
+            assert_eq!(hole[0] % 2, 0);
+            assert_eq!(open_columns(&board.board, y + 1), hole);
+        }
+    }
+
+    #[test]
+    fn big_blocks_settle_two_rows_at_a_time() {
+        let mut board = BoardController::new();
+        for (x, y) in [(0, 10), (1, 10), (0, 11), (1, 11)] {
+            board.board.set(x, y, CellVal::Color2);
+        }
+        // one row short of room for another step
+        for y in 15..board.board.height() {
+            board.board.set(0, y, CellVal::Garbage);
+        }
+        assert!(board.settle(2));
+        assert_eq!(open_columns(&board.board, 12), (2..10).collect::<Vec<i32>>());
+        assert_eq!(open_columns(&board.board, 13), (2..10).collect::<Vec<i32>>());
+        assert_eq!(open_columns(&board.board, 14).len(), 10);
+        assert!(!board.settle(2));
+    }
+
+    #[test]
+    fn single_column_keeps_its_hole() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(next_hole(0, 1, 1.0, &mut rng), 0);
+    }
 }
@@ -0,0 +1,136 @@
+use crate::game::Game;
+use crate::mode::{GameMode, ModeContext, Outcome};
+use crate::model::{Board, CellVal, LockEvent};
+use std::time::Duration;
+
+pub const DIG_ROWS: i32 = 10;
+// garbage rows on the board at a time when they refill
+pub const DIG_VISIBLE_ROWS: i32 = 10;
+
+#[derive(Clone, Debug)]
+pub struct DigConfig {
+    // garbage rows to clear in total
+    pub rows: i32,
+    // see `BoardController::insert_garbage`
+    pub messiness: f64,
+    // keep `DIG_VISIBLE_ROWS` rows on the board until all rows came in,
+    // instead of starting with all of them
+    pub refill: bool,
+}
+
+impl Default for DigConfig {
+    fn default() -> DigConfig {
+        return DigConfig::new();
+    }
+}
+
+impl DigConfig {
+    pub fn new() -> DigConfig {
+        return DigConfig {
+            rows: DIG_ROWS,
+            messiness: 1.0,
+            refill: false,
+        };
+    }
+}
+
+// Clear all garbage rows as fast as possible, with as few pieces as
+// possible. The timer counts frame times like for `Sprint`.
+#[derive(Clone, Debug)]
+pub struct Dig {
+    config: DigConfig,
+    // the rows of this game, fewer than configured if they don't fit
+    total: i32,
+    added: i32,
+    elapsed: Duration,
+    finished: bool,
+}
+
+impl Dig {
+    pub fn new(config: DigConfig) -> Dig {
+        return Dig {
+            total: config.rows,
+            config,
+            added: 0,
+            elapsed: Duration::from_secs(0),
+            finished: false,
+        };
+    }
+
+    fn add(&mut self, game: &mut ModeContext, rows: i32) {
+        if rows <= 0 {
+            return;
+        }
+        game.add_garbage(rows, None, self.config.messiness);
+        self.added += rows;
+    }
+
+    pub fn config(&self) -> &DigConfig {
+        return &self.config;
+    }
+
+    // Garbage rows on the board and still to come.
+    pub fn rows_left(&self, game: &Game) -> i32 {
+        return garbage_rows(game.current_board(), game.ruleset().scale()) + self.total - self.added;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        return self.elapsed;
+    }
+}
+
+impl GameMode for Dig {
+    // Fills the board of the new game with garbage.
+    fn on_start(&mut self, game: &mut ModeContext) {
+        self.added = 0;
+        self.elapsed = Duration::from_secs(0);
+        self.finished = false;
+
+        self.total = match self.config.refill {
+            true => self.config.rows.max(0),
+            false => self.config.rows.clamp(0, fitting_rows(game)),
+        };
+        let rows = match self.config.refill {
+            true => self.total.min(visible_rows(game)),
+            false => self.total,
+        };
+        self.add(game, rows);
+    }
+
+    fn on_lock(&mut self, game: &mut ModeContext, _lock: &LockEvent) {
+        if self.config.refill {
+            let missing = visible_rows(game) - garbage_rows(game.board(), game.scale());
+            self.add(game, missing.min(self.total - self.added));
+        }
+        if self.added >= self.total && garbage_rows(game.board(), game.scale()) == 0 {
+            self.finished = true;
+        }
+    }
+
+    fn on_tick(&mut self, _game: &mut ModeContext, frame_time: Duration) {
+        self.elapsed += frame_time;
+    }
+
+    fn is_finished(&self) -> Option<Outcome> {
+        return match self.finished {
+            true => Some(Outcome::Won),
+            false => None,
+        };
+    }
+}
+
+// Garbage rows that fit below the spawn rows, which stay free.
+fn fitting_rows(game: &ModeContext) -> i32 {
+    return game.board().height() / game.scale() - 4;
+}
+
+// At most half the board, less than `DIG_VISIBLE_ROWS` with big pieces.
+fn visible_rows(game: &ModeContext) -> i32 {
+    return DIG_VISIBLE_ROWS.min(game.board().height() / game.scale() / 2);
+}
+
+// In rows of minos, `scale` cells high each.
+fn garbage_rows(board: &Board, scale: i32) -> i32 {
+    let cells = board.cells.iter().filter(|row| row.contains(&CellVal::Garbage)).count() as i32;
+    return (cells + scale - 1) / scale;
+}
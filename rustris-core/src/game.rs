@@ -1,49 +1,231 @@
 use crate::model::Tetromino;
 use crate::tetromino::TetrominoController;
-use crate::board::BoardController;
-use crate::model::{Board, Stats, UserInput};
+use crate::board::{BoardController, ClearStep};
+use crate::model::{Board, CellVal, ClearEvent, LockEvent, Stats, TetrominoRotation, TetrominoType, UserInput};
+use crate::mode::{Endless, GameMode, ModeContext, Outcome};
+use crate::placement::{is_immobile, Placement, PlacementSearch};
+use crate::ruleset::{PieceGenerator, Ruleset, Scoring};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::time::Duration;
 
+pub const PREVIEW_SIZE: usize = 5;
+// how often frontends call `run_step`
+pub const FRAMES_PER_SECOND: i32 = 60;
+pub const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND as u64);
+
+// Guideline scoring, without levels. Indexed by cleared lines.
+const LINE_SCORES: [i32; 5] = [0, 100, 300, 500, 800];
+const T_SPIN_SCORES: [i32; 4] = [400, 800, 1200, 1600];
+const PERFECT_CLEAR_SCORES: [i32; 5] = [0, 800, 1200, 1800, 2000];
+const COMBO_SCORE: i32 = 50;
+const SOFT_DROP_SCORE: i32 = 1;
+const HARD_DROP_SCORE: i32 = 2;
+// NES scoring, times the level + 1
+const NES_LINE_SCORES: [i32; 5] = [0, 40, 100, 300, 1200];
+// per mino of a color group, whatever the scoring
+const MATCH_SCORE: i32 = 20;
+// times the number of the step, for the clears of a chain after the first
+const CHAIN_SCORE: i32 = 100;
+
+// Cloning a game takes a snapshot that plays on exactly like the original.
+#[derive(Clone)]
 pub struct Game {
     time: u32,
     step_interval: u32,
+    // rows a gravity step moves the piece, more than one for fast gravity
+    step_rows: u32,
+    ruleset: Ruleset,
+    generator: PieceGenerator,
     board: BoardController,
     active_piece: TetrominoController,
-    next_piece: TetrominoController,
+    next_pieces: VecDeque<TetrominoController>,
+    held_piece: Option<TetrominoController>,
+    // once per piece
+    held: bool,
+    // frames the active piece rests on the stack, see `Ruleset::lock_delay`
+    grounded: u32,
+    lock_resets: u32,
+    soft_rows: i32,
     stats: Stats,
+    game_over: bool,
+    rotated_last: bool,
+    combo: i32,
+    back_to_back: bool,
+    last_lock: Option<LockEvent>,
+    // of the last lock, until taken
+    clear_events: Vec<ClearEvent>,
+    rng: StdRng,
+    // separate from `rng`, so garbage doesn't change the pieces to come
+    garbage_rng: StdRng,
+    mode: Box<dyn GameMode>,
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        return Game::new();
+    }
 }
 
 impl Game {
     pub fn new() -> Game {
+        return Game::with_rng(StdRng::from_entropy(), StdRng::from_entropy());
+    }
+
+    // Two games created with the same seed play the same sequence of pieces.
+    pub fn with_seed(seed: u64) -> Game {
+        return Game::with_rng(StdRng::seed_from_u64(seed), StdRng::seed_from_u64(!seed));
+    }
+
+    fn with_rng(mut rng: StdRng, garbage_rng: StdRng) -> Game {
+        let ruleset = Ruleset::default();
+        let mut generator = PieceGenerator::new(ruleset.randomizer, ruleset.rotation.piece_count());
+        let active_piece = random_piece(&ruleset, &mut generator, &mut rng);
+        let next_pieces = (0..PREVIEW_SIZE)
+            .map(|_| random_piece(&ruleset, &mut generator, &mut rng))
+            .collect();
+        let (step_interval, step_rows) = ruleset.speed(ruleset.start_level());
+        let mut stats = Stats::new();
+        stats.level = ruleset.start_level();
         return Game {
             time: 0,
-            step_interval: 10,
+            step_interval,
+            step_rows,
+            ruleset,
+            generator,
             board: BoardController::new(),
-            active_piece: TetrominoController::rand(),
-            next_piece: TetrominoController::rand(),
-            stats: Stats::new(),
+            active_piece,
+            next_pieces,
+            held_piece: None,
+            held: false,
+            grounded: 0,
+            lock_resets: 0,
+            soft_rows: 0,
+            stats,
+            game_over: false,
+            rotated_last: false,
+            combo: -1,
+            back_to_back: false,
+            last_lock: None,
+            clear_events: Vec::new(),
+            rng,
+            garbage_rng,
+            mode: Box::new(Endless),
         };
     }
 
     pub fn run_step(&mut self) {
+        self.run_step_timed(FRAME_TIME);
+    }
+
+    // Like `run_step`, for frontends that measure how long the frame took.
+    // Modes with a clock count that time instead of `FRAME_TIME`.
+    pub fn run_step_timed(&mut self, frame_time: Duration) {
+        if self.is_finished() {
+            // until the game is reset
+            self.board.reveal(u32::MAX);
+            return;
+        }
         self.time += 1;
-        if self.time % self.step_interval == 0 {
-            self.try_lower_piece();
+        self.stats.frames += 1;
+        self.board.tick();
+        if self.time.is_multiple_of(self.step_interval) {
+            self.gravity_step();
+        }
+        let lock_delay = self.ruleset.lock_delay;
+        if lock_delay > 0 && !self.game_over && self.active_piece.is_grounded(&self.board.board) {
+            self.grounded += 1;
+            if self.grounded >= lock_delay {
+                self.freeze_piece_and_have_next();
+            }
+        }
+        if !self.is_finished() {
+            self.run_mode(|mode, game| mode.on_tick(game, frame_time));
         }
     }
 
     pub fn handle_input(&mut self, input: &UserInput) {
+        if self.is_finished() && *input != UserInput::Reset {
+            return;
+        }
         match input {
             UserInput::MoveLeft => self.try_move_piece_horizontally(-1),
             UserInput::MoveRight => self.try_move_piece_horizontally(1),
-            UserInput::MoveDown => self.try_lower_piece(),
+            UserInput::MoveDown => self.soft_drop(),
             UserInput::DropDown => self.drop_piece(),
             UserInput::RotateLeft => self.try_rotate_piece(-1),
             UserInput::RotateRight => self.try_rotate_piece(1),
+            UserInput::Hold => self.hold_piece(),
             UserInput::Reset => self.new_game(),
             UserInput::NoInput => (),
         };
     }
 
+    // Starts over like `Reset`, but with the pieces of `Game::with_seed(seed)`.
+    pub fn reset_with_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.garbage_rng = StdRng::seed_from_u64(!seed);
+        self.time = 0;
+        self.new_game();
+    }
+
+    // A hash of everything that decides how the game goes on. Equal games
+    // have equal hashes on every platform, so peers can compare them.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        hasher.write(self.time as i64);
+        hasher.write(self.step_interval as i64);
+        hasher.write(self.step_rows as i64);
+        for row in self.board.board.cells.iter() {
+            for cell in row.iter() {
+                hasher.write(*cell as i64);
+            }
+        }
+        let pieces = std::iter::once(&self.active_piece).chain(self.next_pieces.iter());
+        for piece in pieces.map(|p| &p.tetromino) {
+            hasher.write(piece.t_type.index() as i64);
+            hasher.write(piece.rotation.index() as i64);
+            hasher.write(piece.offset.0 as i64);
+            hasher.write(piece.offset.1 as i64);
+        }
+        let held = self
+            .held_piece
+            .as_ref()
+            .map_or(-1, |p| p.tetromino.t_type.index() as i64);
+        hasher.write(held);
+        hasher.write(self.held as i64);
+        hasher.write(self.grounded as i64);
+        hasher.write(self.lock_resets as i64);
+        hasher.write(self.soft_rows as i64);
+        let stats = &self.stats;
+        let values = [
+            stats.pieces,
+            stats.cleared,
+            stats.clr_cmb_1,
+            stats.clr_cmb_2,
+            stats.clr_cmb_3,
+            stats.clr_cmb_4,
+            stats.clr_cmb_5,
+            stats.chains,
+            stats.score,
+            stats.frames,
+            stats.level,
+        ];
+        for value in values {
+            hasher.write(value as i64);
+        }
+        hasher.write(self.game_over as i64);
+        hasher.write(self.rotated_last as i64);
+        hasher.write(self.combo as i64);
+        hasher.write(self.back_to_back as i64);
+        // what the generators draw next stands in for their state
+        hasher.write(self.rng.clone().gen::<i64>());
+        hasher.write(self.garbage_rng.clone().gen::<i64>());
+        self.generator.write_state(&mut hasher);
+        return hasher.finish();
+    }
+
     pub fn current_board(&self) -> &Board {
         return &self.board.board;
     }
@@ -53,59 +235,428 @@ impl Game {
     }
 
     pub fn next_piece(&self) -> &Tetromino {
-        return &self.next_piece.tetromino;
+        return &self.next_pieces[0].tetromino;
+    }
+
+    // The upcoming pieces, next one first.
+    pub fn preview(&self) -> Vec<TetrominoType> {
+        return self.preview_iter().collect();
+    }
+
+    pub fn preview_iter(&self) -> impl Iterator<Item = TetrominoType> + '_ {
+        return self.next_pieces.iter().map(|p| p.tetromino.t_type);
+    }
+
+    // The upcoming pieces as they will spawn, rotation included.
+    pub fn preview_pieces(&self) -> impl Iterator<Item = &Tetromino> + '_ {
+        return self.next_pieces.iter().map(|p| &p.tetromino);
+    }
+
+    // The piece in the hold slot, see `UserInput::Hold`.
+    pub fn held_piece(&self) -> Option<TetrominoType> {
+        return self.held_piece.as_ref().map(|p| p.tetromino.t_type);
+    }
+
+    // Clearing pieces in a row up to the last one, less one; -1 if the last
+    // one cleared nothing, see `LockEvent::combo`.
+    pub fn combo(&self) -> i32 {
+        return self.combo;
+    }
+
+    // If the next tetris or T-spin clear counts as back-to-back.
+    pub fn back_to_back(&self) -> bool {
+        return self.back_to_back;
+    }
+
+    // If `UserInput::Hold` would swap the active piece now; it can't twice
+    // in a row.
+    pub fn can_hold(&self) -> bool {
+        return self.ruleset.hold && !self.held;
+    }
+
+    // The held piece itself, turned the way it spawns.
+    pub fn held_tetromino(&self) -> Option<&Tetromino> {
+        return self.held_piece.as_ref().map(|p| &p.tetromino);
+    }
+
+    pub fn ruleset(&self) -> &Ruleset {
+        return &self.ruleset;
+    }
+
+    // Plays by `ruleset` from now on, starting a new game.
+    pub fn set_ruleset(&mut self, ruleset: Ruleset) {
+        self.generator = PieceGenerator::new(ruleset.randomizer, ruleset.rotation.piece_count());
+        self.ruleset = ruleset;
+        self.new_game();
     }
 
     pub fn stats(&self) -> &Stats {
         return &self.stats;
     }
 
+    pub fn is_game_over(&self) -> bool {
+        return self.game_over;
+    }
+
+    // Plays by the rules of `mode` from now on, starting a new game.
+    pub fn set_mode(&mut self, mode: Box<dyn GameMode>) {
+        self.mode = mode;
+        self.new_game();
+    }
+
+    pub fn mode(&self) -> &dyn GameMode {
+        return self.mode.as_ref();
+    }
+
+    // The mode as what it is, if it is a `T`.
+    pub fn mode_as<T: GameMode + 'static>(&self) -> Option<&T> {
+        return self.mode.as_any().downcast_ref::<T>();
+    }
+
+    // `None` while the game goes on.
+    pub fn outcome(&self) -> Option<Outcome> {
+        return match self.mode.is_finished() {
+            Some(outcome) => Some(outcome),
+            None if self.game_over => Some(Outcome::Lost),
+            None => None,
+        };
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.outcome().is_some();
+    }
+
+    pub fn gravity(&self) -> u32 {
+        return self.step_interval;
+    }
+
+    pub(crate) fn set_gravity(&mut self, frames_per_row: u32) {
+        self.step_interval = frames_per_row.max(1);
+        self.step_rows = 1;
+    }
+
+    pub(crate) fn board_mut(&mut self) -> &mut BoardController {
+        return &mut self.board;
+    }
+
+    pub fn placements(&self) -> Vec<Placement> {
+        let mut search = PlacementSearch::new();
+        search.set_drop_locks(self.ruleset.hard_drop_locks);
+        return search.run(&self.board.board, &self.active_piece.tetromino).to_vec();
+    }
+
+    // The lock since the last call, if a piece locked.
+    pub fn take_lock_event(&mut self) -> Option<LockEvent> {
+        return self.last_lock.take();
+    }
+
+    // What the last lock cleared, in the order it happened, if it wasn't
+    // taken yet.
+    pub fn take_clear_events(&mut self) -> Vec<ClearEvent> {
+        return std::mem::take(&mut self.clear_events);
+    }
+
+    // Garbage rows rise from the bottom, see `BoardController::insert_garbage`.
+    // Rows and the hole count in minos, so with big pieces every row is two
+    // cells high and its hole two cells wide.
+    // The active piece is pushed up with the stack if the garbage reaches it.
+    // Blocks pushed out at the top, or a piece that can't get free, end the game.
+    pub fn add_garbage(&mut self, rows: i32, hole: Option<i32>, messiness: f64) {
+        if self.game_over {
+            return;
+        }
+        let scale = self.ruleset.scale();
+        let overflow = self
+            .board
+            .insert_garbage(rows, hole, messiness, scale, &mut self.garbage_rng);
+        if overflow || !self.active_piece.lift_out(rows * scale, &self.board.board) {
+            self.game_over = true;
+        }
+    }
+
     fn try_move_piece_horizontally(&mut self, amount: i32) {
-        self.active_piece.try_move_horizontally(amount, &self.board.board);
+        if self.active_piece.try_move_horizontally(amount, &self.board.board) {
+            self.rotated_last = false;
+            self.reset_lock_delay();
+        }
     }
 
     fn try_rotate_piece(&mut self, amount: i32) {
-        self.active_piece.try_rotate(amount, &self.board.board);
+        if self.active_piece.try_rotate(amount, &self.board.board) {
+            self.rotated_last = true;
+            self.reset_lock_delay();
+        }
+    }
+
+    // A piece on the stack gets its lock delay back, as often as the ruleset allows.
+    fn reset_lock_delay(&mut self) {
+        if self.grounded > 0 && self.lock_resets < self.ruleset.lock_resets {
+            self.grounded = 0;
+            self.lock_resets += 1;
+        }
+    }
+
+    // Returns if the piece moved.
+    fn try_lower_piece(&mut self) -> bool {
+        if self.active_piece.try_move_vertically(1, &self.board.board) {
+            self.rotated_last = false;
+            self.grounded = 0;
+            return true;
+        }
+        return false;
     }
 
-    fn try_lower_piece(&mut self) {
-        if !self.active_piece.try_move_vertically(1, &self.board.board) {
+    // Without a lock delay, a piece that can't fall any further locks.
+    fn gravity_step(&mut self) {
+        for _ in 0..self.step_rows {
+            if !self.try_lower_piece() {
+                if self.ruleset.lock_delay == 0 {
+                    self.freeze_piece_and_have_next();
+                }
+                return;
+            }
+        }
+    }
+
+    fn soft_drop(&mut self) {
+        if self.try_lower_piece() {
+            self.soft_rows += 1;
+            if self.ruleset.scoring != Scoring::Tgm {
+                self.stats.score += SOFT_DROP_SCORE;
+            }
+            return;
+        }
+        if self.ruleset.soft_drop_locks || self.ruleset.lock_delay == 0 {
             self.freeze_piece_and_have_next();
         }
     }
 
     fn drop_piece(&mut self) {
-        while self.active_piece.try_move_vertically(1, &self.board.board) {
-            //
+        while self.try_lower_piece() {
+            if self.ruleset.scoring == Scoring::Guideline {
+                self.stats.score += HARD_DROP_SCORE;
+            }
         }
+        if self.ruleset.hard_drop_locks {
+            self.freeze_piece_and_have_next();
+        }
+    }
 
-        // We don't do this right now..
-        // self.freeze_piece_and_have_next(board);
+    // Swaps the active piece with the held one, or with the next piece when
+    // nothing is held yet.
+    fn hold_piece(&mut self) {
+        if !self.ruleset.hold || self.held {
+            return;
+        }
+        let mut held = self.active_piece.clone();
+        held.reset_rotation();
+        match self.held_piece.take() {
+            Some(previous) => self.active_piece.update_by(&previous),
+            None => self.have_next(),
+        }
+        self.held_piece = Some(held);
+        self.held = true;
+        self.new_piece_spawned();
+    }
+
+    fn have_next(&mut self) {
+        let next = self.next_pieces.pop_front().unwrap();
+        self.active_piece.update_by(&next);
+        let next = random_piece(&self.ruleset, &mut self.generator, &mut self.rng);
+        self.next_pieces.push_back(next);
+    }
+
+    fn new_piece_spawned(&mut self) {
+        self.rotated_last = false;
+        self.grounded = 0;
+        self.lock_resets = 0;
+        self.soft_rows = 0;
+        if self.active_piece.collides(&self.board.board) {
+            // our (just placed) new piece already collides..
+            // player lost the game. It stays over until a reset.
+            self.game_over = true;
+        }
     }
 
     fn freeze_piece_and_have_next(&mut self) {
+        let piece = &self.active_piece.tetromino;
+        let position = (piece.offset.0, piece.offset.1, piece.rotation);
+        let spin =
+            self.rotated_last && is_immobile(position, &piece.t_type, &self.board.board, &piece.system, piece.scale);
+        let t_type = piece.t_type;
+        let scale = piece.scale;
+
         self.board.freeze_tetromino(&self.active_piece.tetromino);
+        self.stats.pieces += 1;
 
-        let row_count = self.board.clear_full_rows();
-        if row_count > 0 {
-            self.on_rows_cleared(row_count);
-        };
+        self.clear_events.clear();
+        // the rows and minos the lock cleared, then those of each step of a chain
+        let steps: Vec<(i32, i32)> = self
+            .board
+            .clear_after_lock(&self.ruleset, scale)
+            .into_iter()
+            .map(|step| self.count_clears(step, scale))
+            .collect();
+        self.stats.chains += steps.len() as i32 - 1;
+        self.on_lock(t_type, &steps, spin);
 
-        self.active_piece.update_by(&self.next_piece);
-        self.next_piece.set_random();
+        self.have_next();
+        self.held = false;
+        self.new_piece_spawned();
 
-        if self.active_piece.collides(&self.board.board) {
-            // our (just placed) new piece already collides..
-            // player lost the game.
-            self.new_game();
+        // the mode sees the new piece, so garbage it adds lifts that one
+        if let Some(lock) = self.last_lock {
+            if lock.lines > 0 {
+                self.run_mode(|mode, game| mode.on_lines_cleared(game, lock.lines));
+            }
+            self.run_mode(|mode, game| mode.on_lock(game, &lock));
         }
     }
 
     fn new_game(&mut self) {
         self.board.clear();
-        self.active_piece.set_random();
-        self.next_piece.set_random();
+        self.generator.restart();
+        // updated in place, so their change counts keep going up
+        let piece = random_piece(&self.ruleset, &mut self.generator, &mut self.rng);
+        self.active_piece.update_by(&piece);
+        for next in self.next_pieces.iter_mut() {
+            next.update_by(&random_piece(&self.ruleset, &mut self.generator, &mut self.rng));
+        }
+        self.held_piece = None;
+        self.held = false;
+        self.grounded = 0;
+        self.lock_resets = 0;
+        self.soft_rows = 0;
         self.stats.reset();
+        self.stats.level = self.ruleset.start_level();
+        self.game_over = false;
+        self.rotated_last = false;
+        self.combo = -1;
+        self.back_to_back = false;
+        self.last_lock = None;
+        self.clear_events.clear();
+        self.apply_speed();
+        self.run_mode(|mode, game| mode.on_start(game));
+    }
+
+    // The gravity of the level, which replaces what a mode set.
+    fn apply_speed(&mut self) {
+        let (step_interval, step_rows) = self.ruleset.speed(self.stats.level);
+        self.step_interval = step_interval;
+        self.step_rows = step_rows;
+    }
+
+    // The mode is taken out while it runs, so it can change the game.
+    fn run_mode<F: FnOnce(&mut dyn GameMode, &mut ModeContext)>(&mut self, hook: F) {
+        let mut mode = std::mem::replace(&mut self.mode, Box::new(Endless));
+        hook(mode.as_mut(), &mut ModeContext::new(self));
+        self.mode = mode;
+    }
+
+    // Every clear counts on its own, the ones of a chain too. Returns the
+    // rows and minos of the step.
+    fn count_clears(&mut self, step: ClearStep, scale: i32) -> (i32, i32) {
+        let row_count = step.row_count(scale);
+        let minos = step.minos(scale);
+        if row_count > 0 {
+            self.on_rows_cleared(row_count);
+            self.clear_events.push(ClearEvent::Rows(step.rows));
+        }
+        for (color, cells) in step.groups {
+            self.clear_events.push(ClearEvent::Group { color, cells });
+        }
+        return (row_count, minos);
+    }
+
+    fn on_lock(&mut self, t_type: TetrominoType, steps: &[(i32, i32)], spin: bool) {
+        let t_spin = spin && self.ruleset.rotation.piece_name(t_type) == "T";
+        let lines: i32 = steps.iter().map(|(rows, _)| rows).sum();
+        let matched: i32 = steps.iter().map(|(_, minos)| minos).sum();
+        let chain = steps.len() as i32 - 1;
+        if lines > 0 {
+            self.combo += 1;
+        } else {
+            self.combo = -1;
+        }
+        let board = &self.board.board;
+        let empty = board
+            .cells
+            .iter()
+            .all(|row| row.iter().all(|cell| *cell == CellVal::Free));
+        let perfect_clear = lines > 0 && empty;
+        // the rows that emptied the board get the bonus
+        let last_rows = steps.iter().rposition(|(rows, _)| *rows > 0);
+
+        let level = self.stats.level;
+        let mut back_to_back = false;
+        for (step, (rows, minos)) in steps.iter().enumerate() {
+            // only the piece itself spun, the chain after it didn't
+            let t_spin = t_spin && step == 0;
+            let mut step_back_to_back = false;
+            if *rows > 0 {
+                let difficult = *rows >= 4 || t_spin;
+                step_back_to_back = difficult && self.back_to_back;
+                self.back_to_back = difficult;
+            }
+            back_to_back |= step_back_to_back;
+            let bravo = perfect_clear && last_rows == Some(step);
+            let score = self.clear_score(step, *rows, t_spin, step_back_to_back, bravo);
+            self.stats.score += score + MATCH_SCORE * minos + CHAIN_SCORE * step as i32;
+        }
+
+        let next_level = self.ruleset.next_level(level, self.stats.cleared, lines);
+        if next_level != level {
+            self.stats.level = next_level;
+            self.apply_speed();
+        }
+
+        self.last_lock = Some(LockEvent {
+            t_type,
+            lines,
+            spin,
+            t_spin,
+            combo: self.combo,
+            back_to_back,
+            perfect_clear,
+            chain,
+            matched,
+        });
+    }
+
+    // The score of the rows of one step of a lock, see `on_lock`. The combo
+    // and soft drop bonuses only count for the first step.
+    fn clear_score(&self, step: usize, rows: i32, t_spin: bool, back_to_back: bool, perfect_clear: bool) -> i32 {
+        let scored = rows.clamp(0, 4) as usize;
+        let level = self.stats.level;
+        let combo = match step {
+            0 => self.combo.max(0),
+            _ => 0,
+        };
+        return match self.ruleset.scoring {
+            Scoring::Guideline => {
+                let mut score = match t_spin {
+                    true => T_SPIN_SCORES[scored.min(3)],
+                    false => LINE_SCORES[scored],
+                };
+                if back_to_back {
+                    score = score * 3 / 2;
+                }
+                score += COMBO_SCORE * combo;
+                if perfect_clear {
+                    score += PERFECT_CLEAR_SCORES[scored];
+                }
+                score
+            }
+            Scoring::Nes => NES_LINE_SCORES[scored] * (level + 1),
+            Scoring::Tgm => {
+                let bravo = if perfect_clear { 4 } else { 1 };
+                let soft_rows = match step {
+                    0 => self.soft_rows,
+                    _ => 0,
+                };
+                ((level + rows + 3) / 4 + soft_rows) * rows * (1 + self.combo.max(0)) * bravo
+            }
+        };
     }
 
     fn on_rows_cleared(&mut self, amount: i32) {
@@ -115,7 +666,96 @@ impl Game {
             2 => self.stats.clr_cmb_2 += 1,
             3 => self.stats.clr_cmb_3 += 1,
             4 => self.stats.clr_cmb_4 += 1,
-            _ => panic!("Cleared strange mount of lines.."),
+            _ => self.stats.clr_cmb_5 += 1,
         }
     }
 }
+
+fn random_piece(ruleset: &Ruleset, generator: &mut PieceGenerator, rng: &mut StdRng) -> TetrominoController {
+    let t_type = generator.next(rng);
+    // drawn either way, so piece sets don't change the pieces that follow
+    let color = CellVal::rand_color(rng);
+    let color = ruleset.rotation.color(t_type).unwrap_or(color);
+    let rotation = match ruleset.random_rotation {
+        true => TetrominoRotation::rand(rng),
+        false => TetrominoRotation::Rot0,
+    };
+    return TetrominoController::spawn(t_type, color, rotation, ruleset.rotation.clone(), ruleset.scale());
+}
+
+// 64 bit FNV-1a. Unlike `DefaultHasher`, it is the same in every build.
+pub(crate) struct StateHasher {
+    hash: u64,
+}
+
+impl StateHasher {
+    pub(crate) fn new() -> StateHasher {
+        return StateHasher {
+            hash: 0xcbf2_9ce4_8422_2325,
+        };
+    }
+
+    pub(crate) fn write(&mut self, value: i64) {
+        for byte in value.to_le_bytes().iter() {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        return self.hash;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_with_the_same_seed_and_inputs_hash_equal() {
+        let mut games = [Game::with_seed(5), Game::with_seed(5)];
+        let inputs = [
+            UserInput::MoveLeft,
+            UserInput::RotateRight,
+            UserInput::DropDown,
+            UserInput::Hold,
+        ];
+        for game in games.iter_mut() {
+            for input in inputs.iter().cycle().take(40) {
+                game.handle_input(input);
+                game.run_step();
+            }
+        }
+        assert_eq!(games[0].state_hash(), games[1].state_hash());
+    }
+
+    #[test]
+    fn state_hash_covers_the_random_generators() {
+        let game = Game::with_seed(5);
+        let mut other = game.clone();
+        other.rng.gen::<u64>();
+        assert_ne!(game.state_hash(), other.state_hash());
+
+        let mut other = game.clone();
+        other.garbage_rng.gen::<u64>();
+        assert_ne!(game.state_hash(), other.state_hash());
+    }
+
+    #[test]
+    fn every_step_of_a_chain_scores_its_own_rows() {
+        let mut game = Game::with_seed(0);
+        game.set_ruleset(Ruleset::guideline());
+        game.board.board.set(0, 0, CellVal::Garbage);
+        // two tetrises, the second one back-to-back, in the first step of a chain
+        game.on_lock(TetrominoType::I, &[(4, 0), (4, 0)], false);
+        assert_eq!(game.stats.score, 800 + 1200 + CHAIN_SCORE);
+        let lock = game.take_lock_event().unwrap();
+        assert_eq!((lock.lines, lock.chain, lock.back_to_back), (8, 1, true));
+
+        // a single in between breaks back-to-back
+        let score = game.stats.score;
+        game.on_lock(TetrominoType::I, &[(4, 0), (1, 0), (4, 0)], false);
+        let steps = [1200 + COMBO_SCORE, 100 + CHAIN_SCORE, 800 + 2 * CHAIN_SCORE];
+        assert_eq!(game.stats.score - score, steps.iter().sum::<i32>());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::game::FRAMES_PER_SECOND;
+use crate::mode::{GameMode, ModeContext};
+use std::time::Duration;
+
+pub const FADE_TIME: Duration = Duration::from_secs(3);
+
+// How long line clears show the board.
+const REVEAL_FRAMES: u32 = FRAMES_PER_SECOND as u32;
+
+// Play with a stack that can't be seen. Locked cells disappear after the
+// fade time, right away with a time of zero, but still block pieces. Line
+// clears show the board for a moment, a lost game for good.
+#[derive(Clone, Debug)]
+pub struct Invisible {
+    fade: Duration,
+}
+
+impl Default for Invisible {
+    fn default() -> Invisible {
+        return Invisible::new(FADE_TIME);
+    }
+}
+
+impl Invisible {
+    pub fn new(fade: Duration) -> Invisible {
+        return Invisible { fade };
+    }
+
+    pub fn fade(&self) -> Duration {
+        return self.fade;
+    }
+}
+
+// It never finishes but by topping out.
+impl GameMode for Invisible {
+    fn on_start(&mut self, game: &mut ModeContext) {
+        let frames = self.fade.as_millis() * FRAMES_PER_SECOND as u128 / 1000;
+        game.set_fade(Some(frames.min(u32::MAX as u128) as u32));
+    }
+
+    fn on_lines_cleared(&mut self, game: &mut ModeContext, _lines: i32) {
+        game.reveal_board(REVEAL_FRAMES);
+    }
+}
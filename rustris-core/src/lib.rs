@@ -1,4 +1,17 @@
-mod board;
+pub mod board;
+pub mod dig;
 pub mod game;
+pub mod invisible;
+pub mod mode;
 pub mod model;
+pub mod pieces;
+pub mod placement;
+pub mod records;
+pub mod rotation;
+pub mod ruleset;
+pub mod sprint;
+pub mod survival;
 mod tetromino;
+pub mod ultra;
+pub mod versus;
+pub mod view;
@@ -0,0 +1,120 @@
+use crate::game::Game;
+use crate::model::{Board, LockEvent, Stats};
+use std::any::Any;
+use std::time::Duration;
+
+// How a game with a mode ended.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Won,
+    // the stack topped out, unless the mode says otherwise
+    Lost,
+    // neither, like the time running out in ultra
+    Ended,
+}
+
+// The rules on top of the plain game. `Game` calls the hooks while it runs,
+// a mode only has to implement the ones it needs. Modes are cloned with the
+// game, so they need to be `Clone`; see `Sprint` or `Dig` for examples.
+pub trait GameMode: ModeClone + Send {
+    // A new game started; the board is empty and gravity is the default.
+    fn on_start(&mut self, _game: &mut ModeContext) {}
+
+    // A piece locked and the next one spawned.
+    fn on_lock(&mut self, _game: &mut ModeContext, _lock: &LockEvent) {}
+
+    // Called before `on_lock` for locks that cleared lines.
+    fn on_lines_cleared(&mut self, _game: &mut ModeContext, _lines: i32) {}
+
+    // Once per `Game::run_step`, with the time the frame took.
+    fn on_tick(&mut self, _game: &mut ModeContext, _frame_time: Duration) {}
+
+    // A finished game is frozen until it is reset. Without a verdict of the
+    // mode, a game ends when it topped out.
+    fn is_finished(&self) -> Option<Outcome> {
+        return None;
+    }
+}
+
+// Implemented for every `GameMode` that is `Clone`, so games can be cloned
+// and frontends can get at the concrete mode with `Game::mode_as`.
+pub trait ModeClone {
+    fn clone_mode(&self) -> Box<dyn GameMode>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: GameMode + Clone + 'static> ModeClone for T {
+    fn clone_mode(&self) -> Box<dyn GameMode> {
+        return Box::new(self.clone());
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
+}
+
+impl Clone for Box<dyn GameMode> {
+    fn clone(&self) -> Box<dyn GameMode> {
+        return self.clone_mode();
+    }
+}
+
+// What a mode may do with the game it runs.
+pub struct ModeContext<'a> {
+    game: &'a mut Game,
+}
+
+impl<'a> ModeContext<'a> {
+    pub(crate) fn new(game: &'a mut Game) -> ModeContext<'a> {
+        return ModeContext { game };
+    }
+
+    pub fn stats(&self) -> &Stats {
+        return self.game.stats();
+    }
+
+    pub fn board(&self) -> &Board {
+        return self.game.current_board();
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        return self.game.is_game_over();
+    }
+
+    // Board cells per side of a mino, see `Ruleset::scale`.
+    pub fn scale(&self) -> i32 {
+        return self.game.ruleset().scale();
+    }
+
+    // See `Game::add_garbage`.
+    pub fn add_garbage(&mut self, rows: i32, hole: Option<i32>, messiness: f64) {
+        self.game.add_garbage(rows, hole, messiness);
+    }
+
+    // Frames the piece takes to fall one row.
+    pub fn gravity(&self) -> u32 {
+        return self.game.gravity();
+    }
+
+    // Lasts until the game is reset or the level changes.
+    pub fn set_gravity(&mut self, frames_per_row: u32) {
+        self.game.set_gravity(frames_per_row);
+    }
+
+    // Locked cells are hidden after `frames`, see `Board::is_visible`. Lasts
+    // until the game is reset.
+    pub fn set_fade(&mut self, frames: Option<u32>) {
+        self.game.board_mut().set_fade(frames);
+    }
+
+    // Shows the whole board for a while, hidden cells included.
+    pub fn reveal_board(&mut self, frames: u32) {
+        self.game.board_mut().reveal(frames);
+    }
+}
+
+// Plain play until the stack tops out.
+#[derive(Clone, Default, Debug)]
+pub struct Endless;
+
+impl GameMode for Endless {}
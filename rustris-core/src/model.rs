@@ -1,17 +1,35 @@
+use crate::rotation::RotationSystem;
 use rand::Rng;
+use std::fmt;
 
-const BOARD_DIM_X: usize = 10;
-const BOARD_DIM_Y: usize = 20;
+pub const BOARD_DIM_X: usize = 10;
+pub const BOARD_DIM_Y: usize = 20;
 
+#[derive(Clone)]
 pub struct Board {
     pub cells: [[CellVal; BOARD_DIM_X]; BOARD_DIM_Y],
+    // frames since each cell was locked, counted by `Game::run_step`
+    pub ages: [[u32; BOARD_DIM_X]; BOARD_DIM_Y],
+    // cells older than this many frames are hidden, see `is_visible`
+    pub fade: Option<u32>,
+    // frames left in which every cell shows anyway
+    pub reveal: u32,
     pub change_count: i32,
 }
 
+impl Default for Board {
+    fn default() -> Board {
+        return Board::new();
+    }
+}
+
 impl Board {
     pub fn new() -> Board {
         return Board {
             cells: [[CellVal::Free; BOARD_DIM_X]; BOARD_DIM_Y],
+            ages: [[0; BOARD_DIM_X]; BOARD_DIM_Y],
+            fade: None,
+            reveal: 0,
             change_count: 0,
         };
     }
@@ -24,6 +42,14 @@ impl Board {
         return self.cells[y as usize][x as usize] = val;
     }
 
+    // If renderers should draw the cell. Hidden cells still block pieces.
+    pub fn is_visible(&self, x: i32, y: i32) -> bool {
+        return match self.fade {
+            Some(frames) => self.reveal > 0 || self.ages[y as usize][x as usize] < frames,
+            None => true,
+        };
+    }
+
     pub fn width(&self) -> i32 {
         return self.cells[0].len() as i32;
     }
@@ -37,37 +63,81 @@ impl Board {
     }
 }
 
+// The box of the seven tetrominoes; other pieces bring their own size.
 const TETROMINO_SIZE: usize = 4;
 
+// Where new pieces appear on the board.
+pub const SPAWN_OFFSET: (i32, i32) = (3, 0);
+
+#[derive(Clone)]
 pub struct Tetromino {
-    pub cells: [[CellVal; TETROMINO_SIZE]; TETROMINO_SIZE],
+    // a square box around the piece, `size` cells wide and high, row by row;
+    // set with `resize`
+    pub cells: Vec<CellVal>,
+    pub size: i32,
     pub offset: (i32, i32),
+    pub t_type: TetrominoType,
+    pub rotation: TetrominoRotation,
+    // decides the shape of each rotation, and how the piece turns
+    pub system: RotationSystem,
+    // board cells per side of a mino, 2 for big pieces
+    pub scale: i32,
     pub change_count: i32,
 }
 
+impl Default for Tetromino {
+    fn default() -> Tetromino {
+        return Tetromino::new();
+    }
+}
+
 impl Tetromino {
     pub fn new() -> Tetromino {
         return Tetromino {
-            cells: [[CellVal::Free; TETROMINO_SIZE]; TETROMINO_SIZE],
+            cells: vec![CellVal::Free; TETROMINO_SIZE * TETROMINO_SIZE],
+            size: TETROMINO_SIZE as i32,
             offset: (0, 0),
+            t_type: TetrominoType::I,
+            rotation: TetrominoRotation::Rot0,
+            system: RotationSystem::Kickless,
+            scale: 1,
             change_count: 0,
         };
     }
 
+    // Where the piece appears. Big pieces spawn in the middle of the board,
+    // with every mino on the grid of their size.
+    pub fn spawn_offset(&self) -> (i32, i32) {
+        let (x, y) = self.system.spawn_offset(self.t_type);
+        if self.scale == 1 {
+            return (x, y);
+        }
+        let columns = BOARD_DIM_X as i32 / self.scale;
+        let x = (columns - self.system.box_size(self.t_type) + 1) / 2;
+        return (x.max(0) * self.scale, y * self.scale);
+    }
+
+    // Clears the box and makes it `size` cells wide and high.
+    pub fn resize(&mut self, size: i32) {
+        self.size = size;
+        self.cells.clear();
+        self.cells.resize((size * size) as usize, CellVal::Free);
+    }
+
     pub fn at(&self, x: i32, y: i32) -> CellVal {
-        return self.cells[y as usize][x as usize];
+        return self.cells[(y * self.size + x) as usize];
     }
 
     pub fn set(&mut self, x: i32, y: i32, val: CellVal) {
-        return self.cells[y as usize][x as usize] = val;
+        return self.cells[(y * self.size + x) as usize] = val;
     }
 
     pub fn width(&self) -> i32 {
-        return self.cells[0].len() as i32;
+        return self.size;
     }
 
     pub fn height(&self) -> i32 {
-        return self.cells.len() as i32;
+        return self.size;
     }
 
     pub fn inc_change_count(&mut self) {
@@ -75,34 +145,154 @@ impl Tetromino {
     }
 }
 
+// A kind of piece, numbered in the order of its `RotationSystem`: the seven
+// tetrominoes below, or the pieces of a `PieceSet` in the order of its file.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TetrominoType(pub u8);
+
+impl TetrominoType {
+    pub const I: TetrominoType = TetrominoType(0);
+    pub const O: TetrominoType = TetrominoType(1);
+    pub const T: TetrominoType = TetrominoType(2);
+    pub const J: TetrominoType = TetrominoType(3);
+    pub const L: TetrominoType = TetrominoType(4);
+    pub const S: TetrominoType = TetrominoType(5);
+    pub const Z: TetrominoType = TetrominoType(6);
+
+    // One of the first `count` kinds.
+    pub fn rand<R: Rng>(rng: &mut R, count: usize) -> TetrominoType {
+        return TetrominoType(rng.gen_range(0, count) as u8);
+    }
+
+    pub fn index(&self) -> usize {
+        return self.0 as usize;
+    }
+}
+
+// The letter of the seven tetrominoes, like the enum it used to be.
+impl fmt::Debug for TetrominoType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match "IOTJLSZ".chars().nth(self.index()) {
+            Some(letter) => write!(f, "{}", letter),
+            None => write!(f, "#{}", self.0),
+        };
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TetrominoRotation {
+    Rot0,
+    Rot1,
+    Rot2,
+    Rot3,
+}
+
+impl TetrominoRotation {
+    pub fn rand<R: Rng>(rng: &mut R) -> TetrominoRotation {
+        return match rng.gen_range(0, 4) {
+            0 => TetrominoRotation::Rot0,
+            1 => TetrominoRotation::Rot1,
+            2 => TetrominoRotation::Rot2,
+            _ => TetrominoRotation::Rot3,
+        };
+    }
+
+    pub fn index(&self) -> usize {
+        return match self {
+            TetrominoRotation::Rot0 => 0,
+            TetrominoRotation::Rot1 => 1,
+            TetrominoRotation::Rot2 => 2,
+            TetrominoRotation::Rot3 => 3,
+        };
+    }
+}
+
+// What happened when a piece locked.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LockEvent {
+    pub t_type: TetrominoType,
+    pub lines: i32,
+    // the last input was a rotation and the piece couldn't move afterwards
+    pub spin: bool,
+    // a spin of the rotation system's T piece, see `RotationSystem::piece_name`
+    pub t_spin: bool,
+    // consecutive clearing pieces before this one; -1 without a clear
+    pub combo: i32,
+    // a tetris or T-spin clear right after another one
+    pub back_to_back: bool,
+    pub perfect_clear: bool,
+    // clears set off by blocks falling after the first one, see `Ruleset::cascade`
+    pub chain: i32,
+    // minos cleared in groups of one color, see `Ruleset::color_match`
+    pub matched: i32,
+}
+
+// Blocks a lock cleared, for renderers to show, see `Game::take_clear_events`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClearEvent {
+    // full rows, where they were before they were removed
+    Rows(Vec<i32>),
+    // connected cells of one color, see `Ruleset::color_match`
+    Group { color: CellVal, cells: Vec<(i32, i32)> },
+}
+
+#[derive(Clone)]
 pub struct Stats {
+    pub pieces: i32,
     pub cleared: i32,
     pub clr_cmb_4: i32,
     pub clr_cmb_3: i32,
     pub clr_cmb_2: i32,
     pub clr_cmb_1: i32,
+    // five rows or more at once, which takes pieces bigger than tetrominoes
+    pub clr_cmb_5: i32,
+    // clears that followed from falling blocks, see `LockEvent::chain`
+    pub chains: i32,
+    pub score: i32,
+    // frames played, see `FRAMES_PER_SECOND`
+    pub frames: i32,
+    // see `Ruleset::gravity`
+    pub level: i32,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        return Stats::new();
+    }
 }
 
 impl Stats {
     pub fn new() -> Stats {
         return Stats {
+            pieces: 0,
             cleared: 0,
             clr_cmb_4: 0,
             clr_cmb_3: 0,
             clr_cmb_2: 0,
             clr_cmb_1: 0,
+            clr_cmb_5: 0,
+            chains: 0,
+            score: 0,
+            frames: 0,
+            level: 0,
         };
     }
     pub fn reset(&mut self) {
+        self.pieces = 0;
         self.cleared = 0;
         self.clr_cmb_4 = 0;
         self.clr_cmb_3 = 0;
         self.clr_cmb_2 = 0;
         self.clr_cmb_1 = 0;
+        self.clr_cmb_5 = 0;
+        self.chains = 0;
+        self.score = 0;
+        self.frames = 0;
+        self.level = 0;
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum CellVal {
     Free,
     Color1,
@@ -113,11 +303,13 @@ pub enum CellVal {
     Color6,
     Color7,
     Color8,
+    // rows pushed in from below, see `BoardController::insert_garbage`
+    Garbage,
 }
 
 impl CellVal {
-    pub fn rand_color() -> CellVal {
-        return match rand::thread_rng().gen_range(0, 8) {
+    pub fn rand_color<R: Rng>(rng: &mut R) -> CellVal {
+        return match rng.gen_range(0, 8) {
             0 => CellVal::Color1,
             1 => CellVal::Color2,
             2 => CellVal::Color3,
@@ -130,6 +322,7 @@ impl CellVal {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum UserInput {
     MoveLeft,
     MoveRight,
@@ -137,6 +330,8 @@ pub enum UserInput {
     DropDown,
     RotateLeft,
     RotateRight,
+    // see `Ruleset::hold`
+    Hold,
     Reset,
     NoInput,
 }
@@ -0,0 +1,477 @@
+use crate::model::{CellVal, TetrominoType, BOARD_DIM_X, BOARD_DIM_Y, SPAWN_OFFSET};
+use crate::rotation::{RotationSystem, TYPE_NAMES};
+use std::sync::Arc;
+
+// Pieces as a file defines them, see pieces/standard.toml and
+// pieces/pentris.toml. Play with them through `into_system`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct PieceSet {
+    pub name: String,
+    // in the order of the file, which numbers their `TetrominoType`
+    pub pieces: Vec<PieceDef>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct PieceDef {
+    pub name: String,
+    pub color: CellVal,
+    pub spawn: (i32, i32),
+    // the width and height of the box the piece turns in
+    pub size: i32,
+    // the cells in the box for each rotation, in reading order
+    pub shapes: [Vec<(i32, i32)>; 4],
+    // the offsets to try in order, for turns from each rotation; y points down
+    pub kicks_right: [Vec<(i32, i32)>; 4],
+    pub kicks_left: [Vec<(i32, i32)>; 4],
+}
+
+impl PieceDef {
+    pub(crate) fn kicks(&self, from: usize, r: i32) -> &[(i32, i32)] {
+        return match r > 0 {
+            true => &self.kicks_right[from],
+            false => &self.kicks_left[from],
+        };
+    }
+}
+
+impl PieceSet {
+    // Reads a small part of TOML: a `name` for the set, then a `[[piece]]`
+    // table for each piece. Arrays may span several lines and '#' starts a
+    // comment outside of strings.
+    pub fn parse(text: &str) -> Result<PieceSet, String> {
+        let (top, tables) = parse_tables(text)?;
+        let mut name = String::from("custom");
+        for (line, key, value) in top {
+            match key.as_str() {
+                "name" => name = at_line(line, string(&value))?,
+                _ => return Err(format!("line {}: unknown key '{}'", line, key)),
+            }
+        }
+
+        let mut pieces: Vec<PieceDef> = Vec::new();
+        for (line, table) in tables {
+            let piece = at_line(line, piece_def(table))?;
+            if pieces.iter().any(|p| p.name == piece.name) {
+                return Err(format!("line {}: piece '{}' is defined twice", line, piece.name));
+            }
+            pieces.push(piece);
+        }
+        if pieces.is_empty() {
+            return Err(String::from("no pieces, add a [[piece]] table"));
+        }
+        if pieces.len() > u8::MAX as usize {
+            return Err(format!("{} pieces, the most are {}", pieces.len(), u8::MAX));
+        }
+        return Ok(PieceSet { name, pieces });
+    }
+
+    pub fn load(path: &str) -> Result<PieceSet, String> {
+        return match std::fs::read_to_string(path) {
+            Ok(text) => PieceSet::parse(&text).map_err(|err| format!("{}: {}", path, err)),
+            Err(err) => Err(format!("{}: {}", path, err)),
+        };
+    }
+
+    pub fn piece(&self, t_type: TetrominoType) -> &PieceDef {
+        return &self.pieces[t_type.index()];
+    }
+
+    // The seven tetrominoes in the usual order, whatever their shapes, which
+    // is all that TBP bots know.
+    pub fn is_tetrominoes(&self) -> bool {
+        return self.pieces.len() == TYPE_NAMES.len()
+            && self
+                .pieces
+                .iter()
+                .zip(TYPE_NAMES.iter())
+                .all(|(piece, name)| piece.name == *name && piece.shapes[0].len() == 4);
+    }
+
+    // The rotation system shares the set with every piece played with it.
+    pub fn into_system(self) -> RotationSystem {
+        return RotationSystem::Custom(Arc::new(self));
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Str(String),
+    Int(i32),
+    Array(Vec<Value>),
+}
+
+// The `key = value` pairs of a table, with the line each one starts on.
+type Table = Vec<(usize, String, Value)>;
+
+// The keys before the first `[[piece]]`, and the piece tables with their line.
+fn parse_tables(text: &str) -> Result<(Table, Vec<(usize, Table)>), String> {
+    let mut top = Vec::new();
+    let mut tables: Vec<(usize, Table)> = Vec::new();
+    let mut lines = text.lines().enumerate().map(|(number, line)| (number + 1, line));
+    while let Some((number, line)) = lines.next() {
+        let mut line = String::from(strip_comment(line).trim());
+        if line.is_empty() {
+            continue;
+        }
+        if line == "[[piece]]" {
+            tables.push((number, Vec::new()));
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(format!("line {}: unknown table '{}'", number, line));
+        }
+        while depth(&line) > 0 {
+            match lines.next() {
+                Some((_, more)) => {
+                    line.push(' ');
+                    line.push_str(strip_comment(more).trim());
+                }
+                None => return Err(format!("line {}: array is never closed", number)),
+            }
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return Err(format!("line {}: expected 'key = value'", number)),
+        };
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("line {}: bad key '{}'", number, key));
+        }
+        let (value, rest) = at_line(number, parse_value(value))?;
+        if !rest.trim().is_empty() {
+            return Err(format!("line {}: unexpected '{}'", number, rest.trim()));
+        }
+        let table = match tables.last_mut() {
+            Some((_, table)) => table,
+            None => &mut top,
+        };
+        if table.iter().any(|(_, k, _)| k == key) {
+            return Err(format!("line {}: '{}' is set twice", number, key));
+        }
+        table.push((number, String::from(key), value));
+    }
+    return Ok((top, tables));
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    return line;
+}
+
+// Open brackets, outside of strings.
+fn depth(text: &str) -> i32 {
+    let mut in_string = false;
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => (),
+        }
+    }
+    return depth;
+}
+
+// Returns the value at the start of `text`, and what follows it.
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix('"') {
+        return match rest.find('"') {
+            Some(end) => Ok((Value::Str(String::from(&rest[..end])), &rest[end + 1..])),
+            None => Err(String::from("string is never closed")),
+        };
+    }
+    if let Some(rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        let mut rest = rest.trim_start();
+        loop {
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), after));
+            }
+            let (value, after) = parse_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            match rest.strip_prefix(',') {
+                Some(after) => rest = after.trim_start(),
+                None if rest.starts_with(']') => (),
+                None => return Err(String::from("expected ',' or ']' in array")),
+            }
+        }
+    }
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+        .unwrap_or(text.len());
+    return match text[..end].parse::<i32>() {
+        Ok(number) => Ok((Value::Int(number), &text[end..])),
+        Err(_) => Err(format!("bad value '{}'", text)),
+    };
+}
+
+fn at_line<T>(line: usize, result: Result<T, String>) -> Result<T, String> {
+    return result.map_err(|err| match err.starts_with("line ") {
+        true => err,
+        false => format!("line {}: {}", line, err),
+    });
+}
+
+fn string(value: &Value) -> Result<String, String> {
+    return match value {
+        Value::Str(text) => Ok(text.clone()),
+        _ => Err(String::from("expected a string")),
+    };
+}
+
+fn int(value: &Value) -> Result<i32, String> {
+    return match value {
+        Value::Int(number) => Ok(*number),
+        _ => Err(String::from("expected a number")),
+    };
+}
+
+fn array(value: &Value, len: Option<usize>) -> Result<&[Value], String> {
+    return match (value, len) {
+        (Value::Array(values), None) => Ok(values),
+        (Value::Array(values), Some(len)) if values.len() == len => Ok(values),
+        (_, Some(len)) => Err(format!("expected an array of {}", len)),
+        (_, None) => Err(String::from("expected an array")),
+    };
+}
+
+fn piece_def(table: Table) -> Result<PieceDef, String> {
+    let mut name = None;
+    let mut color = None;
+    let mut spawn = SPAWN_OFFSET;
+    let mut shapes = None;
+    let no_kicks = || [vec![(0, 0)], vec![(0, 0)], vec![(0, 0)], vec![(0, 0)]];
+    let mut kicks_right = no_kicks();
+    let mut kicks_left = no_kicks();
+    for (line, key, value) in table.iter() {
+        let line = *line;
+        match key.as_str() {
+            "name" => name = Some(at_line(line, string(value))?),
+            "color" => color = Some(at_line(line, color_of(value))?),
+            "spawn" => spawn = at_line(line, offset(value))?,
+            "rotations" => shapes = Some(at_line(line, rotations(value))?),
+            "kicks_right" => kicks_right = at_line(line, kicks(value))?,
+            "kicks_left" => kicks_left = at_line(line, kicks(value))?,
+            _ => return Err(format!("line {}: unknown key '{}'", line, key)),
+        }
+    }
+    let name = name.ok_or("piece without a name")?;
+    let color = color.ok_or(format!("piece '{}' has no color", name))?;
+    let shapes: [Vec<(i32, i32)>; 4] = shapes.ok_or(format!("piece '{}' has no rotations", name))?;
+    let size = shapes
+        .iter()
+        .flat_map(|cells| cells.iter())
+        .map(|(x, y)| x.max(y) + 1)
+        .max()
+        .unwrap_or(0);
+    // the rustris rules spawn pieces in any rotation
+    for (x, y) in shapes.iter().flat_map(|cells| cells.iter()) {
+        let (bx, by) = (spawn.0 + x, spawn.1 + y);
+        if bx < 0 || bx >= BOARD_DIM_X as i32 || by < 0 || by >= BOARD_DIM_Y as i32 {
+            return Err(format!("piece '{}' spawns outside the board", name));
+        }
+    }
+    return Ok(PieceDef {
+        name,
+        color,
+        spawn,
+        size,
+        shapes,
+        kicks_right,
+        kicks_left,
+    });
+}
+
+fn color_of(value: &Value) -> Result<CellVal, String> {
+    return match int(value)? {
+        1 => Ok(CellVal::Color1),
+        2 => Ok(CellVal::Color2),
+        3 => Ok(CellVal::Color3),
+        4 => Ok(CellVal::Color4),
+        5 => Ok(CellVal::Color5),
+        6 => Ok(CellVal::Color6),
+        7 => Ok(CellVal::Color7),
+        8 => Ok(CellVal::Color8),
+        color => Err(format!("color {} isn't one of 1 to 8", color)),
+    };
+}
+
+fn offset(value: &Value) -> Result<(i32, i32), String> {
+    let pair = array(value, Some(2))?;
+    return Ok((int(&pair[0])?, int(&pair[1])?));
+}
+
+// Four shapes, for rotation 0, R, 2 and L; each a list of rows of '#' and
+// '.'. All of them have the same number of cells, which touch each other.
+fn rotations(value: &Value) -> Result<[Vec<(i32, i32)>; 4], String> {
+    let mut shapes = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+    for (r, rows) in array(value, Some(4))?.iter().enumerate() {
+        for (y, row) in array(rows, None)?.iter().enumerate() {
+            let row = string(row)?;
+            if row.chars().any(|c| c != '#' && c != '.') {
+                return Err(format!("bad row '{}', rows are made of '#' and '.'", row));
+            }
+            let cells = row.chars().enumerate().filter(|(_, c)| *c == '#');
+            shapes[r].extend(cells.map(|(x, _)| (x as i32, y as i32)));
+        }
+        if shapes[r].is_empty() {
+            return Err(format!("rotation {} has no cells", r));
+        }
+        if shapes[r].len() != shapes[0].len() {
+            return Err(format!(
+                "rotation {} has {} cells, rotation 0 has {}",
+                r,
+                shapes[r].len(),
+                shapes[0].len()
+            ));
+        }
+        if !is_connected(&shapes[r]) {
+            return Err(format!("the cells of rotation {} don't all touch", r));
+        }
+    }
+    return Ok(shapes);
+}
+
+// Every cell can be reached from the first one through cells left, right,
+// above or below.
+fn is_connected(cells: &[(i32, i32)]) -> bool {
+    let mut reached = vec![cells[0]];
+    let mut next = 0;
+    while next < reached.len() {
+        let (x, y) = reached[next];
+        for cell in cells.iter() {
+            let touches = (cell.0 - x).abs() + (cell.1 - y).abs() == 1;
+            if touches && !reached.contains(cell) {
+                reached.push(*cell);
+            }
+        }
+        next += 1;
+    }
+    return reached.len() == cells.len();
+}
+
+// For each rotation turned from, a list of [x, y] offsets.
+fn kicks(value: &Value) -> Result<[Vec<(i32, i32)>; 4], String> {
+    let mut kicks = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+    for (r, offsets) in array(value, Some(4))?.iter().enumerate() {
+        for pair in array(offsets, None)? {
+            kicks[r].push(offset(pair)?);
+        }
+        if kicks[r].is_empty() {
+            return Err(format!("no kicks for turns from rotation {}, use [[0, 0]] for none", r));
+        }
+    }
+    return Ok(kicks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::model::TetrominoRotation::{Rot0, Rot1, Rot2, Rot3};
+    use crate::model::UserInput;
+    use crate::ruleset::Ruleset;
+
+    const O: &str = "[[piece]]\nname = \"O\"\ncolor = 4\nrotations = [[\"##\", \"##\"], [\"##\", \"##\"], [\"##\", \"##\"], [\"##\", \"##\"]]\n";
+
+    fn error(text: &str) -> String {
+        return PieceSet::parse(text).unwrap_err();
+    }
+
+    #[test]
+    fn standard_pieces_are_the_srs_tetrominoes() {
+        let set = PieceSet::parse(include_str!("../../pieces/standard.toml")).unwrap();
+        assert_eq!(set.name, "standard");
+        assert!(set.is_tetrominoes());
+        for (i, piece) in set.pieces.iter().enumerate() {
+            let rotations = [Rot0, Rot1, Rot2, Rot3];
+            for rotation in rotations.iter() {
+                let mut expected = RotationSystem::Srs.cells(TetrominoType(i as u8), *rotation).to_vec();
+                let mut cells = piece.shapes[rotation.index()].clone();
+                expected.sort_unstable();
+                cells.sort_unstable();
+                assert_eq!(cells, expected, "{} in {:?}", piece.name, rotation);
+            }
+        }
+    }
+
+    #[test]
+    fn pentris_pieces_have_five_cells() {
+        let set = PieceSet::parse(include_str!("../../pieces/pentris.toml")).unwrap();
+        assert!(!set.is_tetrominoes());
+        assert!(set
+            .pieces
+            .iter()
+            .all(|piece| piece.shapes.iter().all(|cells| cells.len() == 5)));
+    }
+
+    #[test]
+    fn defaults_and_multi_line_arrays() {
+        let text = "name = \"one\" # a comment\n[[piece]]\nname = \"#\"\ncolor = 2\nrotations = [\n  [\"#\"],\n  [\"#\"],\n  [\"#\"],\n  [\"#\"],\n]\n";
+        let set = PieceSet::parse(text).unwrap();
+        let piece = &set.pieces[0];
+        assert_eq!(piece.name, "#");
+        assert_eq!(piece.spawn, SPAWN_OFFSET);
+        assert_eq!(piece.size, 1);
+        assert_eq!(piece.kicks(0, 1), &[(0, 0)]);
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_line() {
+        assert_eq!(error(""), "no pieces, add a [[piece]] table");
+        assert_eq!(error("size = 3\n"), "line 1: unknown key 'size'");
+        assert_eq!(error(&format!("{}color = 4\n", O)), "line 5: 'color' is set twice");
+        assert_eq!(error(&format!("{}{}", O, O)), "line 5: piece 'O' is defined twice");
+        assert_eq!(error("[[piece]]\ncolor = 1\n"), "line 1: piece without a name");
+        assert_eq!(error("[[piece]]\nname = \"X\"\n"), "line 1: piece 'X' has no color");
+        assert_eq!(error("[[piece]]\ncolor = 9\n"), "line 2: color 9 isn't one of 1 to 8");
+        assert_eq!(error("[[piece]]\nrotations = [\n"), "line 2: array is never closed");
+        assert_eq!(error("[[pieces]]\n"), "line 1: unknown table '[[pieces]]'");
+    }
+
+    #[test]
+    fn shapes_are_checked() {
+        let piece = |rotations: &str| format!("[[piece]]\nname = \"P\"\ncolor = 1\nrotations = {}\n", rotations);
+        assert_eq!(
+            error(&piece("[[\"#.#\"], [\"#\"], [\"#\"], [\"#\"]]")),
+            "line 4: the cells of rotation 0 don't all touch"
+        );
+        assert_eq!(
+            error(&piece("[[\"#\"], [\"##\"], [\"#\"], [\"#\"]]")),
+            "line 4: rotation 1 has 2 cells, rotation 0 has 1"
+        );
+        assert_eq!(
+            error(&piece("[[\"#\"], [\"x\"], [\"#\"], [\"#\"]]")),
+            "line 4: bad row 'x', rows are made of '#' and '.'"
+        );
+        assert_eq!(
+            error(&piece("[[\"#\"], [\"#\"], [\"#\"]]")),
+            "line 4: expected an array of 4"
+        );
+        // any size goes, as long as the board has room for it
+        let hexomino = piece("[[\"######\"], [\"######\"], [\"######\"], [\"######\"]]");
+        assert_eq!(PieceSet::parse(&hexomino).unwrap().pieces[0].size, 6);
+        let mut game = Game::with_seed(1);
+        let mut ruleset = Ruleset::rustris();
+        ruleset.rotation = PieceSet::parse(&hexomino).unwrap().into_system();
+        game.set_ruleset(ruleset);
+        while !game.is_game_over() {
+            game.handle_input(&UserInput::DropDown);
+            game.handle_input(&UserInput::MoveDown);
+        }
+        // a row of six each, all on top of each other
+        assert_eq!(game.stats().pieces, 20);
+        let outside = format!("{}spawn = [9, 0]\n", piece("[[\"##\"], [\"##\"], [\"##\"], [\"##\"]]"));
+        assert_eq!(error(&outside), "line 1: piece 'P' spawns outside the board");
+    }
+}
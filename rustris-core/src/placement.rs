@@ -0,0 +1,493 @@
+use crate::board::BoardController;
+use crate::model::{Board, CellVal, Tetromino, TetrominoRotation, TetrominoType, UserInput};
+use crate::rotation::RotationSystem;
+use crate::ruleset::Ruleset;
+use crate::tetromino::{collides, scaled_cells, try_change};
+use std::collections::VecDeque;
+
+const MOVES: [UserInput; 6] = [
+    UserInput::MoveLeft,
+    UserInput::MoveRight,
+    UserInput::MoveDown,
+    UserInput::DropDown,
+    UserInput::RotateLeft,
+    UserInput::RotateRight,
+];
+
+// A final resting position of a piece, together with the inputs that lead
+// there from the piece's current position. The piece locks on the next
+// gravity step (or an additional `MoveDown`).
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub t_type: TetrominoType,
+    pub x: i32,
+    pub y: i32,
+    pub rotation: TetrominoRotation,
+    pub system: RotationSystem,
+    pub scale: i32,
+    // of the piece, for the color groups it may clear
+    pub color: CellVal,
+    pub spin: bool,
+    pub path: Vec<UserInput>,
+}
+
+impl Placement {
+    pub fn cells(&self) -> Vec<(i32, i32)> {
+        return piece_cells(&self.t_type, &self.rotation, &self.system, self.scale, self.x, self.y);
+    }
+
+    // Returns the board after locking the piece here, and the number of
+    // cleared rows, counted like `Game` does. Color groups and chains clear
+    // as the rules say.
+    pub fn apply(&self, board: &Board, ruleset: &Ruleset) -> (Board, i32) {
+        let mut controller = BoardController { board: board.clone() };
+        for (x, y) in self.cells() {
+            controller.board.set(x, y, self.color);
+        }
+        let steps = controller.clear_after_lock(ruleset, self.scale);
+        let cleared = steps.iter().map(|step| step.row_count(self.scale)).sum();
+        return (controller.board, cleared);
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Node {
+    x: i32,
+    y: i32,
+    rotation: TetrominoRotation,
+    rotated_last: bool,
+    parent: usize,
+    input: UserInput,
+}
+
+// Lists every final placement the given piece can reach on the board using
+// the same move and rotation rules as `Game`. A placement counts as a spin
+// when its last input was a rotation and the piece can't move left, right
+// or up afterwards. Placements that cover the same cells are reported once.
+pub fn find_placements(board: &Board, piece: &Tetromino) -> Vec<Placement> {
+    let mut search = PlacementSearch::new();
+    return search.run(board, piece).to_vec();
+}
+
+// Does the work of `find_placements`, but keeps its buffers (and the
+// placements found) between searches, so searching in a loop doesn't
+// allocate once the buffers have grown large enough.
+pub struct PlacementSearch {
+    visited: Vec<bool>,
+    nodes: Vec<Node>,
+    queue: VecDeque<usize>,
+    steps: Vec<usize>,
+    // the sorted cells of the placements found, one piece size after another
+    seen_cells: Vec<(i32, i32)>,
+    seen_spins: Vec<bool>,
+    cells: Vec<(i32, i32)>,
+    placements: Vec<Placement>,
+    count: usize,
+    drop_locks: bool,
+    // of the piece searched for
+    scale: i32,
+    color: CellVal,
+}
+
+impl Default for PlacementSearch {
+    fn default() -> PlacementSearch {
+        return PlacementSearch::new();
+    }
+}
+
+impl PlacementSearch {
+    pub fn new() -> PlacementSearch {
+        return PlacementSearch {
+            visited: Vec::new(),
+            nodes: Vec::new(),
+            queue: VecDeque::new(),
+            steps: Vec::new(),
+            seen_cells: Vec::new(),
+            seen_spins: Vec::new(),
+            cells: Vec::new(),
+            placements: Vec::new(),
+            count: 0,
+            drop_locks: false,
+            scale: 1,
+            color: CellVal::Color1,
+        };
+    }
+
+    // For rules where `DropDown` locks the piece, paths only use it as
+    // their last input.
+    pub fn set_drop_locks(&mut self, drop_locks: bool) {
+        self.drop_locks = drop_locks;
+    }
+
+    // The placements found by the last search.
+    pub fn placements(&self) -> &[Placement] {
+        return &self.placements[..self.count];
+    }
+
+    pub fn run(&mut self, board: &Board, piece: &Tetromino) -> &[Placement] {
+        self.count = 0;
+        self.nodes.clear();
+        self.queue.clear();
+        self.seen_cells.clear();
+        self.seen_spins.clear();
+
+        let t_type = piece.t_type;
+        let system = &piece.system;
+        let scale = piece.scale;
+        self.scale = scale;
+        // a piece that wasn't drawn into its box yet gets some color
+        let mut cells = (0..piece.height()).flat_map(|y| (0..piece.width()).map(move |x| piece.at(x, y)));
+        self.color = cells.find(|val| *val != CellVal::Free).unwrap_or(CellVal::Color1);
+        if collides(
+            piece.offset.0,
+            piece.offset.1,
+            &t_type,
+            &piece.rotation,
+            board,
+            system,
+            scale,
+        ) {
+            return self.placements();
+        }
+
+        // Above the stack only the walls can block a piece, so moving and rotating
+        // works the same at any height there. The search starts right above the
+        // stack and the skipped steps down are added to the paths afterwards.
+        let size = system.box_size(t_type) * scale;
+        let skipped = ((empty_rows(board) - size - piece.offset.1) / scale).max(0);
+        let start_y = piece.offset.1 + skipped * scale;
+
+        // the box offset may reach this far outside the board
+        let margin = size - 1;
+        let span_x = board.width() + 2 * margin;
+        let span_y = board.height() + 2 * margin;
+        let index = |x: i32, y: i32, r: TetrominoRotation, rotated_last: bool| -> usize {
+            let pos = ((y + margin) * span_x + (x + margin)) as usize;
+            return (pos * 4 + r.index()) * 2 + rotated_last as usize;
+        };
+
+        self.visited.clear();
+        self.visited.resize((span_x * span_y * 8) as usize, false);
+        self.visited[index(piece.offset.0, start_y, piece.rotation, false)] = true;
+        self.nodes.push(Node {
+            x: piece.offset.0,
+            y: start_y,
+            rotation: piece.rotation,
+            rotated_last: false,
+            parent: usize::MAX,
+            input: UserInput::NoInput,
+        });
+        self.queue.push_back(0);
+
+        while let Some(current) = self.queue.pop_front() {
+            let node = self.nodes[current];
+            let pos = (node.x, node.y, node.rotation);
+
+            if try_change(pos, 0, 1, &t_type, board, system, scale).is_none() {
+                let spin = node.rotated_last && is_immobile(pos, &t_type, board, system, scale);
+                let cells = scaled_cells(system, t_type, node.rotation, scale);
+                self.cells.clear();
+                self.cells.extend(cells.map(|(x, y)| (node.x + x, node.y + y)));
+                self.cells.sort_unstable();
+                let seen = self.seen_cells.chunks(self.cells.len()).zip(self.seen_spins.iter());
+                if !seen.into_iter().any(|(c, s)| *c == self.cells[..] && *s == spin) {
+                    self.seen_cells.extend_from_slice(&self.cells);
+                    self.seen_spins.push(spin);
+                    self.push_placement(current, spin, skipped, &t_type, board, system);
+                }
+            }
+
+            for input in MOVES.iter() {
+                let next = match input {
+                    UserInput::MoveLeft => try_change(pos, -1, 0, &t_type, board, system, scale),
+                    UserInput::MoveRight => try_change(pos, 1, 0, &t_type, board, system, scale),
+                    UserInput::MoveDown => try_change(pos, 0, 1, &t_type, board, system, scale),
+                    UserInput::DropDown => drop_down(pos, &t_type, board, system, scale),
+                    UserInput::RotateLeft => system.rotate(pos, -1, &t_type, board, scale),
+                    UserInput::RotateRight => system.rotate(pos, 1, &t_type, board, scale),
+                    _ => None,
+                };
+                let (x, y, rotation) = match next {
+                    Some(next) => next,
+                    None => continue,
+                };
+                let rotated_last = matches!(input, UserInput::RotateLeft | UserInput::RotateRight);
+                let i = index(x, y, rotation, rotated_last);
+                if self.visited[i] {
+                    continue;
+                }
+                self.visited[i] = true;
+                self.nodes.push(Node {
+                    x,
+                    y,
+                    rotation,
+                    rotated_last,
+                    parent: current,
+                    input: *input,
+                });
+                self.queue.push_back(self.nodes.len() - 1);
+            }
+        }
+
+        return self.placements();
+    }
+
+    // Placements of earlier searches are overwritten to reuse their paths.
+    fn push_placement(
+        &mut self,
+        node_index: usize,
+        spin: bool,
+        skipped: i32,
+        t_type: &TetrominoType,
+        board: &Board,
+        system: &RotationSystem,
+    ) {
+        let node = self.nodes[node_index];
+        if self.count == self.placements.len() {
+            self.placements.push(Placement {
+                t_type: *t_type,
+                x: 0,
+                y: 0,
+                rotation: TetrominoRotation::Rot0,
+                system: system.clone(),
+                scale: 1,
+                color: CellVal::Color1,
+                spin: false,
+                path: Vec::new(),
+            });
+        }
+        let placement = &mut self.placements[self.count];
+        placement.t_type = *t_type;
+        placement.x = node.x;
+        placement.y = node.y;
+        placement.rotation = node.rotation;
+        placement.system = system.clone();
+        placement.scale = self.scale;
+        placement.color = self.color;
+        placement.spin = spin;
+        let mut path = std::mem::take(&mut placement.path);
+        self.path_to(node_index, skipped, t_type, board, system, &mut path);
+        self.placements[self.count].path = path;
+        self.count += 1;
+    }
+
+    fn path_to(
+        &mut self,
+        index: usize,
+        skipped: i32,
+        t_type: &TetrominoType,
+        board: &Board,
+        system: &RotationSystem,
+        path: &mut Vec<UserInput>,
+    ) {
+        let nodes = &self.nodes;
+        let steps = &mut self.steps;
+        steps.clear();
+        let mut current = index;
+        while nodes[current].parent != usize::MAX {
+            steps.push(current);
+            current = nodes[current].parent;
+        }
+        steps.reverse();
+
+        let is_down = |i: &usize| matches!(nodes[*i].input, UserInput::MoveDown | UserInput::DropDown);
+        let first_down = steps.iter().position(is_down).unwrap_or(steps.len());
+        let run_end = first_down + steps[first_down..].iter().take_while(|i| is_down(i)).count();
+
+        path.clear();
+        path.extend(steps[..first_down].iter().map(|i| nodes[*i].input));
+        let run_node = match run_end {
+            0 => &nodes[current],
+            _ => &nodes[steps[run_end - 1]],
+        };
+        let grounded = try_change(
+            (run_node.x, run_node.y, run_node.rotation),
+            0,
+            1,
+            t_type,
+            board,
+            system,
+            self.scale,
+        )
+        .is_none();
+        let run_length = skipped as usize + (run_end - first_down);
+        if grounded && run_length > 0 && (!self.drop_locks || run_end == steps.len()) {
+            path.push(UserInput::DropDown);
+        } else {
+            path.extend(std::iter::repeat_n(UserInput::MoveDown, run_length));
+        }
+        path.extend(steps[run_end..].iter().map(|i| nodes[*i].input));
+    }
+}
+
+fn drop_down(
+    pos: (i32, i32, TetrominoRotation),
+    t_type: &TetrominoType,
+    board: &Board,
+    system: &RotationSystem,
+    scale: i32,
+) -> Option<(i32, i32, TetrominoRotation)> {
+    let mut current = try_change(pos, 0, 1, t_type, board, system, scale)?;
+    while let Some(next) = try_change(current, 0, 1, t_type, board, system, scale) {
+        current = next;
+    }
+    return Some(current);
+}
+
+pub(crate) fn is_immobile(
+    pos: (i32, i32, TetrominoRotation),
+    t_type: &TetrominoType,
+    board: &Board,
+    system: &RotationSystem,
+    scale: i32,
+) -> bool {
+    return try_change(pos, -1, 0, t_type, board, system, scale).is_none()
+        && try_change(pos, 1, 0, t_type, board, system, scale).is_none()
+        && try_change(pos, 0, -1, t_type, board, system, scale).is_none();
+}
+
+fn empty_rows(board: &Board) -> i32 {
+    for y in 0..board.height() {
+        for x in 0..board.width() {
+            if board.at(x, y) != CellVal::Free {
+                return y;
+            }
+        }
+    }
+    return board.height();
+}
+
+// Board coordinates of the cells a piece occupies at the given offset.
+pub fn piece_cells(
+    t_type: &TetrominoType,
+    rotation: &TetrominoRotation,
+    system: &RotationSystem,
+    scale: i32,
+    x: i32,
+    y: i32,
+) -> Vec<(i32, i32)> {
+    let cells = scaled_cells(system, *t_type, *rotation, scale);
+    return cells.map(|(sx, sy)| (x + sx, y + sy)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::ruleset::Ruleset;
+
+    fn spawned(t_type: TetrominoType) -> Tetromino {
+        let mut piece = Tetromino::new();
+        piece.t_type = t_type;
+        piece.offset = piece.spawn_offset();
+        return piece;
+    }
+
+    fn occupied(board: &Board) -> Vec<(i32, i32)> {
+        let mut cells = Vec::new();
+        for y in 0..board.height() {
+            for x in 0..board.width() {
+                if board.at(x, y) != CellVal::Free {
+                    cells.push((x, y));
+                }
+            }
+        }
+        return cells;
+    }
+
+    #[test]
+    fn every_spot_on_an_empty_board_is_found_once() {
+        let board = Board::new();
+        assert_eq!(find_placements(&board, &spawned(TetrominoType::O)).len(), 9);
+        assert_eq!(find_placements(&board, &spawned(TetrominoType::I)).len(), 17);
+        assert_eq!(find_placements(&board, &spawned(TetrominoType::T)).len(), 34);
+    }
+
+    #[test]
+    fn paths_lead_to_their_placements() {
+        let rulesets = [Ruleset::rustris(), Ruleset::guideline(), Ruleset::nes(), Ruleset::tgm()];
+        for (seed, ruleset) in rulesets.iter().enumerate() {
+            let mut game = Game::with_seed(seed as u64);
+            game.set_ruleset(ruleset.clone());
+            assert!(!game.placements().is_empty());
+            for placement in game.placements() {
+                let mut played = game.clone();
+                for input in placement.path.iter() {
+                    played.handle_input(input);
+                }
+                played.handle_input(&UserInput::MoveDown);
+                let (expected, _) = placement.apply(game.current_board(), ruleset);
+                assert_eq!(occupied(played.current_board()), occupied(&expected));
+            }
+        }
+    }
+
+    #[test]
+    fn apply_clears_full_rows() {
+        let mut board = Board::new();
+        let bottom = board.height() - 1;
+        for x in 4..board.width() {
+            board.set(x, bottom, CellVal::Garbage);
+        }
+        let placement = find_placements(&board, &spawned(TetrominoType::I))
+            .into_iter()
+            .find(|p| p.cells().iter().all(|&(x, y)| y == bottom && x < 4))
+            .unwrap();
+        let (after, cleared) = placement.apply(&board, &Ruleset::default());
+        assert_eq!(cleared, 1);
+        assert!(occupied(&after).is_empty());
+    }
+
+    #[test]
+    fn apply_clears_color_groups() {
+        let mut board = Board::new();
+        let bottom = board.height() - 1;
+        board.set(0, bottom, CellVal::Color1);
+        for x in 5..board.width() - 1 {
+            board.set(x, bottom, CellVal::Garbage);
+        }
+        // next to the block of its color
+        let placement = find_placements(&board, &spawned(TetrominoType::I))
+            .into_iter()
+            .find(|p| p.cells().iter().all(|&(x, y)| y == bottom && (1..5).contains(&x)))
+            .unwrap();
+        assert_eq!(occupied(&placement.apply(&board, &Ruleset::default()).0).len(), 9);
+
+        let ruleset = Ruleset {
+            color_match: Some(5),
+            ..Ruleset::default()
+        };
+        let (after, cleared) = placement.apply(&board, &ruleset);
+        assert_eq!(cleared, 0);
+        assert_eq!(occupied(&after).len(), 4);
+    }
+
+    #[test]
+    fn apply_clears_the_rows_of_a_chain() {
+        let mut board = Board::new();
+        let bottom = board.height() - 1;
+        for x in 0..board.width() - 1 {
+            board.set(x, bottom, CellVal::Garbage);
+        }
+        for x in 1..board.width() - 1 {
+            board.set(x, bottom - 1, CellVal::Garbage);
+        }
+        // falls into the bottom left corner once the row under it cleared
+        board.set(0, bottom - 2, CellVal::Garbage);
+        let placement = find_placements(&board, &spawned(TetrominoType::I))
+            .into_iter()
+            .find(|p| p.cells().iter().all(|&(x, _)| x == board.width() - 1))
+            .unwrap();
+        let (after, cleared) = placement.apply(&board, &Ruleset::default());
+        assert_eq!(cleared, 1);
+        assert_eq!(occupied(&after).len(), 12);
+
+        let ruleset = Ruleset {
+            cascade: true,
+            ..Ruleset::default()
+        };
+        let (after, cleared) = placement.apply(&board, &ruleset);
+        assert_eq!(cleared, 2);
+        assert_eq!(occupied(&after).len(), 2);
+    }
+}
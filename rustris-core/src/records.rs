@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+// Personal bests, kept apart by the rules they were played by, see
+// `Ruleset::record_key`. They are kept as text, one record per line like
+// `sprint 40 61234 guideline` (line target, milliseconds), `ultra 120 48500
+// rustris+big` (time limit in seconds, score) or `survival 95016 rustris`
+// (milliseconds), so every frontend can store them wherever it likes.
+#[derive(Default)]
+pub struct Records {
+    sprint: BTreeMap<(String, i32), u64>,
+    ultra: BTreeMap<(String, u64), i32>,
+    survival: BTreeMap<String, u64>,
+}
+
+// Records from before they were kept apart were played by the default rules.
+const OLD_RULES: &str = "rustris";
+
+impl Records {
+    pub fn new() -> Records {
+        return Records {
+            sprint: BTreeMap::new(),
+            ultra: BTreeMap::new(),
+            survival: BTreeMap::new(),
+        };
+    }
+
+    // Lines that can't be read are skipped, so old files stay usable.
+    pub fn parse(text: &str) -> Records {
+        let mut records = Records::new();
+        for line in text.lines() {
+            let mut parts: Vec<&str> = line.split_whitespace().collect();
+            let expected = match parts.first() {
+                Some(&"survival") => 3,
+                _ => 4,
+            };
+            if parts.len() == expected - 1 {
+                parts.push(OLD_RULES);
+            }
+            match parts.as_slice() {
+                ["sprint", target, millis, rules] => {
+                    if let (Ok(target), Ok(millis)) = (target.parse(), millis.parse()) {
+                        records.sprint.insert((rules.to_string(), target), millis);
+                    }
+                }
+                ["ultra", seconds, score, rules] => {
+                    if let (Ok(seconds), Ok(score)) = (seconds.parse(), score.parse()) {
+                        records.ultra.insert((rules.to_string(), seconds), score);
+                    }
+                }
+                ["survival", millis, rules] => {
+                    if let Ok(millis) = millis.parse() {
+                        records.survival.insert(rules.to_string(), millis);
+                    }
+                }
+                _ => (),
+            }
+        }
+        return records;
+    }
+
+    pub fn sprint_best(&self, rules: &str, target: i32) -> Option<Duration> {
+        let millis = self.sprint.get(&(rules.to_string(), target));
+        return millis.map(|millis| Duration::from_millis(*millis));
+    }
+
+    // Returns if the time is a new personal best.
+    pub fn submit_sprint(&mut self, rules: &str, target: i32, time: Duration) -> bool {
+        let key = (rules.to_string(), target);
+        let millis = time.as_millis() as u64;
+        if self.sprint.get(&key).is_some_and(|best| *best <= millis) {
+            return false;
+        }
+        self.sprint.insert(key, millis);
+        return true;
+    }
+
+    pub fn ultra_best(&self, rules: &str, limit: Duration) -> Option<i32> {
+        return self.ultra.get(&(rules.to_string(), limit.as_secs())).copied();
+    }
+
+    // Returns if the score is a new personal best.
+    pub fn submit_ultra(&mut self, rules: &str, limit: Duration, score: i32) -> bool {
+        let key = (rules.to_string(), limit.as_secs());
+        if self.ultra.get(&key).is_some_and(|best| *best >= score) {
+            return false;
+        }
+        self.ultra.insert(key, score);
+        return true;
+    }
+
+    pub fn survival_best(&self, rules: &str) -> Option<Duration> {
+        return self.survival.get(rules).map(|millis| Duration::from_millis(*millis));
+    }
+
+    // Returns if the time is a new personal best.
+    pub fn submit_survival(&mut self, rules: &str, time: Duration) -> bool {
+        let millis = time.as_millis() as u64;
+        if self.survival.get(rules).is_some_and(|best| *best >= millis) {
+            return false;
+        }
+        self.survival.insert(rules.to_string(), millis);
+        return true;
+    }
+}
+
+impl fmt::Display for Records {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ((rules, target), millis) in self.sprint.iter() {
+            writeln!(f, "sprint {} {} {}", target, millis, rules)?;
+        }
+        for ((rules, seconds), score) in self.ultra.iter() {
+            writeln!(f, "ultra {} {} {}", seconds, score, rules)?;
+        }
+        for (rules, millis) in self.survival.iter() {
+            writeln!(f, "survival {} {}", millis, rules)?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_are_kept_apart_by_rules() {
+        let second = Duration::from_secs(1);
+        let mut records = Records::parse("sprint 40 61234\nultra 120 48500 guideline\nsurvival 95016\n");
+        assert_eq!(records.sprint_best("rustris", 40), Some(Duration::from_millis(61234)));
+        assert_eq!(records.sprint_best("guideline", 40), None);
+        assert_eq!(records.ultra_best("guideline", 120 * second), Some(48500));
+        assert_eq!(records.survival_best("rustris+big"), None);
+
+        assert!(records.submit_sprint("rustris+big", 40, 90 * second));
+        assert!(!records.submit_sprint("rustris", 40, 90 * second));
+        assert!(records.submit_survival("rustris+big", second));
+
+        let again = Records::parse(&records.to_string());
+        assert_eq!(again.to_string(), records.to_string());
+        assert_eq!(again.sprint_best("rustris+big", 40), Some(90 * second));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::model::{Board, CellVal, TetrominoRotation, TetrominoType, SPAWN_OFFSET};
+use crate::pieces::PieceSet;
+use crate::tetromino::{collides, next_rotation};
+use std::sync::Arc;
+
+// How pieces turn, and how they get out of the way when they can't. Every
+// system is a table of shapes and a kick rule, see `SystemData`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RotationSystem {
+    // the SRS shapes, without kicks
+    Kickless,
+    // the Super Rotation System with its wall kick tables
+    Srs,
+    // Arika's system: bottom-aligned shapes, one step right, then left
+    Ars,
+    // classic Nintendo games, right-handed and without kicks
+    Nintendo,
+    // shapes, kicks, colors and spawn offsets read from a file
+    Custom(Arc<PieceSet>),
+}
+
+pub(crate) const TYPE_NAMES: [&str; 7] = ["I", "O", "T", "J", "L", "S", "Z"];
+
+// The cells of a tetromino in its 4x4 box, in reading order.
+type Shape = [(i32, i32); 4];
+
+struct SystemData {
+    // indexed by `TetrominoType::index`, then `TetrominoRotation::index`
+    shapes: &'static [[Shape; 4]; 7],
+    kicks: Kicks,
+}
+
+enum Kicks {
+    None,
+    Table(&'static KickTable),
+    // right, then left, but never for the I. L, J and T don't kick if the
+    // first blocked cell, in reading order, is in the center column.
+    Ars,
+}
+
+// Offsets to try in order for turns from each rotation, with y pointing down.
+struct KickTable {
+    right: [[(i32, i32); 5]; 4],
+    left: [[(i32, i32); 5]; 4],
+    i_right: [[(i32, i32); 5]; 4],
+    i_left: [[(i32, i32); 5]; 4],
+}
+
+impl RotationSystem {
+    // The kinds of pieces, see `TetrominoType`.
+    pub fn piece_count(&self) -> usize {
+        return match self {
+            RotationSystem::Custom(set) => set.pieces.len(),
+            _ => TYPE_NAMES.len(),
+        };
+    }
+
+    pub fn piece_name(&self, t_type: TetrominoType) -> &str {
+        return match self {
+            RotationSystem::Custom(set) => &set.piece(t_type).name,
+            _ => TYPE_NAMES[t_type.index()],
+        };
+    }
+
+    // The width and height of the box the piece turns in.
+    pub fn box_size(&self, t_type: TetrominoType) -> i32 {
+        return match self {
+            RotationSystem::Custom(set) => set.piece(t_type).size,
+            _ => 4,
+        };
+    }
+
+    // The cells of the piece in its box, in reading order.
+    pub fn cells(&self, t_type: TetrominoType, rotation: TetrominoRotation) -> &[(i32, i32)] {
+        return match self {
+            RotationSystem::Custom(set) => &set.piece(t_type).shapes[rotation.index()],
+            _ => &self.data().shapes[t_type.index()][rotation.index()],
+        };
+    }
+
+    // The minos of the biggest kind of piece.
+    pub fn largest_piece(&self) -> usize {
+        return (0..self.piece_count())
+            .map(|i| self.cells(TetrominoType(i as u8), TetrominoRotation::Rot0).len())
+            .max()
+            .unwrap_or(0);
+    }
+
+    // Where new pieces of the type appear on the board.
+    pub fn spawn_offset(&self, t_type: TetrominoType) -> (i32, i32) {
+        return match self {
+            RotationSystem::Custom(set) => set.piece(t_type).spawn,
+            _ => SPAWN_OFFSET,
+        };
+    }
+
+    // The color of the type, if it has one; built-in pieces take any color.
+    pub fn color(&self, t_type: TetrominoType) -> Option<CellVal> {
+        return match self {
+            RotationSystem::Custom(set) => Some(set.piece(t_type).color),
+            _ => None,
+        };
+    }
+
+    // Where the piece at `pos` ends up after turning by `r` (1 right, -1
+    // left), with the kicks of the system, or `None` if it can't turn. Kicks
+    // move big pieces by whole minos, `scale` cells each.
+    pub(crate) fn rotate(
+        &self,
+        pos: (i32, i32, TetrominoRotation),
+        r: i32,
+        t_type: &TetrominoType,
+        board: &Board,
+        scale: i32,
+    ) -> Option<(i32, i32, TetrominoRotation)> {
+        let rotation = next_rotation(pos.2, r);
+        let fits = |(x, y): (i32, i32)| !collides(pos.0 + x, pos.1 + y, t_type, &rotation, board, self, scale);
+        let kicks: &[(i32, i32)] = match (self, &self.data().kicks) {
+            (RotationSystem::Custom(set), _) => set.piece(*t_type).kicks(pos.2.index(), r),
+            (_, Kicks::None) => &[(0, 0)],
+            (_, Kicks::Table(table)) => match (*t_type, r > 0) {
+                (TetrominoType::O, _) => &table.right[0][..1],
+                (TetrominoType::I, true) => &table.i_right[pos.2.index()],
+                (TetrominoType::I, false) => &table.i_left[pos.2.index()],
+                (_, true) => &table.right[pos.2.index()],
+                (_, false) => &table.left[pos.2.index()],
+            },
+            (_, Kicks::Ars) => {
+                let blocked_center = || {
+                    let cells = self.cells(*t_type, rotation).iter();
+                    let first = cells
+                        .copied()
+                        .find(|(x, y)| blocked(pos.0 + x * scale, pos.1 + y * scale, scale, board));
+                    return matches!(first, Some((1, _)));
+                };
+                match *t_type {
+                    TetrominoType::I => &[(0, 0)],
+                    TetrominoType::L | TetrominoType::J | TetrominoType::T if blocked_center() => &[(0, 0)],
+                    _ => &[(0, 0), (1, 0), (-1, 0)],
+                }
+            }
+        };
+        let kick = kicks
+            .iter()
+            .map(|(x, y)| (x * scale, y * scale))
+            .find(|kick| fits(*kick));
+        return kick.map(|(x, y)| (pos.0 + x, pos.1 + y, rotation));
+    }
+
+    fn data(&self) -> &'static SystemData {
+        return match self {
+            RotationSystem::Kickless => &KICKLESS,
+            RotationSystem::Srs => &SRS,
+            RotationSystem::Ars => &ARS,
+            RotationSystem::Nintendo => &NINTENDO,
+            // piece sets bring their own tables, see `shape` and `rotate`
+            RotationSystem::Custom(_) => &KICKLESS,
+        };
+    }
+}
+
+// If any cell of the mino with its top left cell at x, y is taken.
+fn blocked(x: i32, y: i32, scale: i32, board: &Board) -> bool {
+    return (0..scale * scale).any(|i| {
+        let (x, y) = (x + i % scale, y + i / scale);
+        return x < 0 || x >= board.width() || y < 0 || y >= board.height() || board.at(x, y) != CellVal::Free;
+    });
+}
+
+// Rows of '#' and '.', top row first.
+const fn shape(rows: [&str; 4]) -> Shape {
+    let mut shape = [(0, 0); 4];
+    let mut count = 0;
+    let mut y = 0;
+    while y < 4 {
+        let row = rows[y].as_bytes();
+        let mut x = 0;
+        while x < 4 {
+            if row[x] == b'#' {
+                shape[count] = (x as i32, y as i32);
+                count += 1;
+            }
+            x += 1;
+        }
+        y += 1;
+    }
+    return shape;
+}
+
+static KICKLESS: SystemData = SystemData {
+    shapes: &SRS_SHAPES,
+    kicks: Kicks::None,
+};
+
+static SRS: SystemData = SystemData {
+    shapes: &SRS_SHAPES,
+    kicks: Kicks::Table(&SRS_KICKS),
+};
+
+static ARS: SystemData = SystemData {
+    shapes: &ARS_SHAPES,
+    kicks: Kicks::Ars,
+};
+
+static NINTENDO: SystemData = SystemData {
+    shapes: &NINTENDO_SHAPES,
+    kicks: Kicks::None,
+};
+
+// from: https://strategywiki.org/wiki/Tetris/Rotation_systems
+static SRS_SHAPES: [[Shape; 4]; 7] = [
+    [
+        shape(["....", "####", "....", "...."]),
+        shape(["..#.", "..#.", "..#.", "..#."]),
+        shape(["....", "....", "####", "...."]),
+        shape([".#..", ".#..", ".#..", ".#.."]),
+    ],
+    [
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+    ],
+    [
+        shape([".#..", "###.", "....", "...."]),
+        shape([".#..", ".##.", ".#..", "...."]),
+        shape(["....", "###.", ".#..", "...."]),
+        shape([".#..", "##..", ".#..", "...."]),
+    ],
+    [
+        shape(["#...", "###.", "....", "...."]),
+        shape([".##.", ".#..", ".#..", "...."]),
+        shape(["....", "###.", "..#.", "...."]),
+        shape([".#..", ".#..", "##..", "...."]),
+    ],
+    [
+        shape(["..#.", "###.", "....", "...."]),
+        shape([".#..", ".#..", ".##.", "...."]),
+        shape(["....", "###.", "#...", "...."]),
+        shape(["##..", ".#..", ".#..", "...."]),
+    ],
+    [
+        shape([".##.", "##..", "....", "...."]),
+        shape([".#..", ".##.", "..#.", "...."]),
+        shape(["....", ".##.", "##..", "...."]),
+        shape(["#...", "##..", ".#..", "...."]),
+    ],
+    [
+        shape(["##..", ".##.", "....", "...."]),
+        shape(["..#.", ".##.", ".#..", "...."]),
+        shape(["....", "##..", ".##.", "...."]),
+        shape([".#..", "##..", "#...", "...."]),
+    ],
+];
+
+// Pieces rest on the bottom of their 3x3 box, and spawn pointing down.
+static ARS_SHAPES: [[Shape; 4]; 7] = [
+    [
+        shape(["....", "####", "....", "...."]),
+        shape(["..#.", "..#.", "..#.", "..#."]),
+        shape(["....", "####", "....", "...."]),
+        shape(["..#.", "..#.", "..#.", "..#."]),
+    ],
+    [
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+    ],
+    [
+        shape(["....", "###.", ".#..", "...."]),
+        shape([".#..", "##..", ".#..", "...."]),
+        shape(["....", ".#..", "###.", "...."]),
+        shape([".#..", ".##.", ".#..", "...."]),
+    ],
+    [
+        shape(["....", "###.", "..#.", "...."]),
+        shape([".#..", ".#..", "##..", "...."]),
+        shape(["....", "#...", "###.", "...."]),
+        shape([".##.", ".#..", ".#..", "...."]),
+    ],
+    [
+        shape(["....", "###.", "#...", "...."]),
+        shape(["##..", ".#..", ".#..", "...."]),
+        shape(["....", "..#.", "###.", "...."]),
+        shape([".#..", ".#..", ".##.", "...."]),
+    ],
+    [
+        shape(["....", ".##.", "##..", "...."]),
+        shape(["#...", "##..", ".#..", "...."]),
+        shape(["....", ".##.", "##..", "...."]),
+        shape(["#...", "##..", ".#..", "...."]),
+    ],
+    [
+        shape(["....", "##..", ".##.", "...."]),
+        shape(["..#.", ".##.", ".#..", "...."]),
+        shape(["....", "##..", ".##.", "...."]),
+        shape(["..#.", ".##.", ".#..", "...."]),
+    ],
+];
+
+// Like the NES: two states for I, S and Z, on the right side of the box.
+static NINTENDO_SHAPES: [[Shape; 4]; 7] = [
+    [
+        shape(["....", "....", "####", "...."]),
+        shape(["..#.", "..#.", "..#.", "..#."]),
+        shape(["....", "....", "####", "...."]),
+        shape(["..#.", "..#.", "..#.", "..#."]),
+    ],
+    [
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+        shape(["....", ".##.", ".##.", "...."]),
+    ],
+    [
+        shape(["....", "###.", ".#..", "...."]),
+        shape([".#..", "##..", ".#..", "...."]),
+        shape([".#..", "###.", "....", "...."]),
+        shape([".#..", ".##.", ".#..", "...."]),
+    ],
+    [
+        shape(["....", "###.", "..#.", "...."]),
+        shape([".#..", ".#..", "##..", "...."]),
+        shape(["#...", "###.", "....", "...."]),
+        shape([".##.", ".#..", ".#..", "...."]),
+    ],
+    [
+        shape(["....", "###.", "#...", "...."]),
+        shape(["##..", ".#..", ".#..", "...."]),
+        shape(["..#.", "###.", "....", "...."]),
+        shape([".#..", ".#..", ".##.", "...."]),
+    ],
+    [
+        shape(["....", ".##.", "##..", "...."]),
+        shape([".#..", ".##.", "..#.", "...."]),
+        shape(["....", ".##.", "##..", "...."]),
+        shape([".#..", ".##.", "..#.", "...."]),
+    ],
+    [
+        shape(["....", "##..", ".##.", "...."]),
+        shape(["..#.", ".##.", ".#..", "...."]),
+        shape(["....", "##..", ".##.", "...."]),
+        shape(["..#.", ".##.", ".#..", "...."]),
+    ],
+];
+
+static SRS_KICKS: KickTable = KickTable {
+    right: [
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    ],
+    left: [
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    ],
+    i_right: [
+        [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+        [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+        [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    ],
+    i_left: [
+        [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+        [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+        [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    ],
+};
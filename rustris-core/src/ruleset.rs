@@ -0,0 +1,359 @@
+use crate::game::StateHasher;
+use crate::model::TetrominoType;
+use crate::rotation::RotationSystem;
+use rand::Rng;
+use std::collections::VecDeque;
+
+// Where the pieces to come are taken from.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Randomizer {
+    // every piece on its own
+    Random,
+    // every kind once, shuffled, then the next bag
+    Bag,
+    // rerolls pieces among the last `size` ones, up to `rolls` times
+    History { size: usize, rolls: u32 },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Scoring {
+    // see `LINE_SCORES` and friends in game.rs
+    Guideline,
+    // 40, 100, 300 and 1200 times the level + 1
+    Nes,
+    // the level, lines, soft drop and combo multiplied
+    Tgm,
+}
+
+// How fast pieces fall, and how the level that decides it goes up.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Gravity {
+    // frames per row, whatever the level; the level is 1 + lines / 10
+    Fixed(u32),
+    // level 1 + lines / 10, the guideline speed curve
+    Guideline,
+    // level lines / 10, the frame table of the NES
+    Nes,
+    // the level goes up with every piece and line, up to 20G at 500
+    Tgm,
+}
+
+// The rules a game is played by, see the presets below.
+#[derive(Clone, Debug)]
+pub struct Ruleset {
+    pub name: &'static str,
+    pub rotation: RotationSystem,
+    pub randomizer: Randomizer,
+    // new pieces spawn turned any way, not flat
+    pub random_rotation: bool,
+    pub hold: bool,
+    // frames a piece may rest on the stack before it locks; 0 locks it with
+    // the first gravity step that can't move it
+    pub lock_delay: u32,
+    // moves that restart the lock delay, per piece
+    pub lock_resets: u32,
+    pub hard_drop_locks: bool,
+    // a soft drop on the stack locks the piece without waiting
+    pub soft_drop_locks: bool,
+    pub scoring: Scoring,
+    pub gravity: Gravity,
+    // every mino is 2x2 cells, so the board plays like one of 5x10
+    pub big: bool,
+    // after a clear, connected blocks of one color fall as far as they can
+    // and may clear more rows
+    pub cascade: bool,
+    // besides full rows, groups of at least this many connected minos of one
+    // color clear too; the blocks above stay where they are unless `cascade`
+    pub color_match: Option<usize>,
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        return Ruleset::rustris();
+    }
+}
+
+impl Ruleset {
+    pub const NAMES: [&'static str; 4] = ["rustris", "guideline", "nes", "tgm"];
+
+    // How rustris always played: random pieces turned any way, no kicks and
+    // no hold, and a piece locks as soon as it can't fall any further.
+    pub fn rustris() -> Ruleset {
+        return Ruleset {
+            name: "rustris",
+            rotation: RotationSystem::Kickless,
+            randomizer: Randomizer::Random,
+            random_rotation: true,
+            hold: false,
+            lock_delay: 0,
+            lock_resets: 0,
+            hard_drop_locks: false,
+            soft_drop_locks: true,
+            scoring: Scoring::Guideline,
+            gravity: Gravity::Fixed(10),
+            big: false,
+            cascade: false,
+            color_match: None,
+        };
+    }
+
+    pub fn guideline() -> Ruleset {
+        return Ruleset {
+            name: "guideline",
+            rotation: RotationSystem::Srs,
+            randomizer: Randomizer::Bag,
+            random_rotation: false,
+            hold: true,
+            lock_delay: 30,
+            lock_resets: 15,
+            hard_drop_locks: true,
+            soft_drop_locks: false,
+            scoring: Scoring::Guideline,
+            gravity: Gravity::Guideline,
+            big: false,
+            cascade: false,
+            color_match: None,
+        };
+    }
+
+    pub fn nes() -> Ruleset {
+        return Ruleset {
+            name: "nes",
+            rotation: RotationSystem::Nintendo,
+            randomizer: Randomizer::Random,
+            random_rotation: false,
+            hold: false,
+            lock_delay: 0,
+            lock_resets: 0,
+            hard_drop_locks: false,
+            soft_drop_locks: true,
+            scoring: Scoring::Nes,
+            gravity: Gravity::Nes,
+            big: false,
+            cascade: false,
+            color_match: None,
+        };
+    }
+
+    pub fn tgm() -> Ruleset {
+        return Ruleset {
+            name: "tgm",
+            rotation: RotationSystem::Ars,
+            randomizer: Randomizer::History { size: 4, rolls: 4 },
+            random_rotation: false,
+            hold: false,
+            lock_delay: 30,
+            lock_resets: 0,
+            hard_drop_locks: false,
+            soft_drop_locks: true,
+            scoring: Scoring::Tgm,
+            gravity: Gravity::Tgm,
+            big: false,
+            cascade: false,
+            color_match: None,
+        };
+    }
+
+    pub fn by_name(name: &str) -> Option<Ruleset> {
+        return match name {
+            "rustris" => Some(Ruleset::rustris()),
+            "guideline" => Some(Ruleset::guideline()),
+            "nes" => Some(Ruleset::nes()),
+            "tgm" => Some(Ruleset::tgm()),
+            _ => None,
+        };
+    }
+
+    // The rules as personal bests tell them apart: the preset, then the
+    // variants and a piece set of a file, like `guideline+big+pieces:pentris`.
+    // Never contains whitespace.
+    pub fn record_key(&self) -> String {
+        let mut key = String::from(self.name);
+        if self.big {
+            key.push_str("+big");
+        }
+        if self.cascade {
+            key.push_str("+cascade");
+        }
+        if let Some(size) = self.color_match {
+            key.push_str(&format!("+match{}", size));
+        }
+        if let RotationSystem::Custom(pieces) = &self.rotation {
+            let name: Vec<&str> = pieces.name.split_whitespace().collect();
+            key.push_str(&format!("+pieces:{}", name.join("_")));
+        }
+        return key;
+    }
+
+    // Board cells per side of a mino.
+    pub fn scale(&self) -> i32 {
+        return match self.big {
+            true => 2,
+            false => 1,
+        };
+    }
+
+    pub(crate) fn start_level(&self) -> i32 {
+        return match self.gravity {
+            Gravity::Fixed(_) | Gravity::Guideline => 1,
+            Gravity::Nes | Gravity::Tgm => 0,
+        };
+    }
+
+    // The level after a piece locked. TGM counts pieces too, but a piece
+    // never completes a section (99, 199, ...) on its own.
+    pub(crate) fn next_level(&self, level: i32, cleared: i32, lines: i32) -> i32 {
+        return match self.gravity {
+            Gravity::Fixed(_) | Gravity::Guideline => 1 + cleared / 10,
+            Gravity::Nes => cleared / 10,
+            Gravity::Tgm => {
+                let level = match lines > 0 || level % 100 != 99 {
+                    true => level + 1 + lines,
+                    false => level,
+                };
+                level.min(999)
+            }
+        };
+    }
+
+    // Frames per gravity step, and the rows a step moves the piece.
+    pub(crate) fn speed(&self, level: i32) -> (u32, u32) {
+        return match self.gravity {
+            Gravity::Fixed(frames) => (frames.max(1), 1),
+            Gravity::Guideline => {
+                let level = level.clamp(1, 20) as f64 - 1.0;
+                let seconds = (0.8 - level * 0.007).powf(level);
+                from_rows_per_frame(1.0 / (seconds * crate::game::FRAMES_PER_SECOND as f64))
+            }
+            Gravity::Nes => {
+                let frames = match level {
+                    0..=8 => 48 - 5 * level as u32,
+                    9 => 6,
+                    10..=12 => 5,
+                    13..=15 => 4,
+                    16..=18 => 3,
+                    19..=28 => 2,
+                    _ => 1,
+                };
+                (frames, 1)
+            }
+            Gravity::Tgm => {
+                // 1/256 rows per frame, from this level on
+                let table = [
+                    (0, 4),
+                    (30, 6),
+                    (35, 8),
+                    (40, 10),
+                    (50, 12),
+                    (60, 16),
+                    (70, 32),
+                    (80, 48),
+                    (90, 64),
+                    (100, 80),
+                    (120, 96),
+                    (140, 112),
+                    (160, 128),
+                    (170, 144),
+                    (200, 4),
+                    (220, 32),
+                    (230, 64),
+                    (233, 96),
+                    (236, 128),
+                    (239, 160),
+                    (243, 192),
+                    (247, 224),
+                    (251, 256),
+                    (300, 512),
+                    (330, 768),
+                    (360, 1024),
+                    (400, 1280),
+                    (420, 1024),
+                    (450, 768),
+                    (500, 5120),
+                ];
+                let g = table.iter().rev().find(|(from, _)| level >= *from).unwrap().1;
+                from_rows_per_frame(g as f64 / 256.0)
+            }
+        };
+    }
+}
+
+fn from_rows_per_frame(rows: f64) -> (u32, u32) {
+    if rows >= 1.0 {
+        return (1, rows as u32);
+    }
+    return ((1.0 / rows).round() as u32, 1);
+}
+
+// Draws the pieces to come, as its `Randomizer` says.
+#[derive(Clone)]
+pub(crate) struct PieceGenerator {
+    randomizer: Randomizer,
+    // the kinds of pieces, see `RotationSystem::piece_count`
+    count: usize,
+    bag: Vec<TetrominoType>,
+    history: VecDeque<TetrominoType>,
+}
+
+impl PieceGenerator {
+    pub(crate) fn new(randomizer: Randomizer, count: usize) -> PieceGenerator {
+        let mut generator = PieceGenerator {
+            randomizer,
+            count,
+            bag: Vec::new(),
+            history: VecDeque::new(),
+        };
+        generator.restart();
+        return generator;
+    }
+
+    // For a new game.
+    pub(crate) fn restart(&mut self) {
+        self.bag.clear();
+        self.history.clear();
+        if let Randomizer::History { size, .. } = self.randomizer {
+            // like TGM, so the first piece is hardly ever one of these
+            let start = [TetrominoType::Z, TetrominoType::S];
+            self.history.extend((0..size).map(|i| start[i % 2]));
+        }
+    }
+
+    // The pieces that decide what is drawn next, see `Game::state_hash`.
+    pub(crate) fn write_state(&self, hasher: &mut StateHasher) {
+        hasher.write(self.bag.len() as i64);
+        for t_type in self.bag.iter().chain(self.history.iter()) {
+            hasher.write(t_type.index() as i64);
+        }
+    }
+
+    pub(crate) fn next<R: Rng>(&mut self, rng: &mut R) -> TetrominoType {
+        return match self.randomizer {
+            Randomizer::Random => TetrominoType::rand(rng, self.count),
+            Randomizer::Bag => {
+                if self.bag.is_empty() {
+                    self.bag.extend((0..self.count).map(|i| TetrominoType(i as u8)));
+                    // Fisher-Yates, drawn from the back
+                    for i in (1..self.bag.len()).rev() {
+                        let j = rng.gen_range(0, i + 1);
+                        self.bag.swap(i, j);
+                    }
+                }
+                self.bag.pop().unwrap()
+            }
+            Randomizer::History { size, rolls } => {
+                let mut t_type = TetrominoType::rand(rng, self.count);
+                for _ in 1..rolls {
+                    if !self.history.contains(&t_type) {
+                        break;
+                    }
+                    t_type = TetrominoType::rand(rng, self.count);
+                }
+                self.history.push_back(t_type);
+                while self.history.len() > size {
+                    self.history.pop_front();
+                }
+                t_type
+            }
+        };
+    }
+}
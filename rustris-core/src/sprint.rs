@@ -0,0 +1,73 @@
+use crate::game::Game;
+use crate::mode::{GameMode, ModeContext, Outcome};
+use std::time::Duration;
+
+pub const SPRINT_LINES: i32 = 40;
+
+// Clear `target` lines as fast as possible. The timer counts the frame times
+// of `Game::run_step_timed`, so it is as precise as the frontend's clock. It
+// stops with the lock that clears the last line, or when the game is lost.
+#[derive(Clone, Debug)]
+pub struct Sprint {
+    target: i32,
+    elapsed: Duration,
+    finished: bool,
+}
+
+impl Default for Sprint {
+    fn default() -> Sprint {
+        return Sprint::new(SPRINT_LINES);
+    }
+}
+
+impl Sprint {
+    pub fn new(target: i32) -> Sprint {
+        return Sprint {
+            target: target.max(1),
+            elapsed: Duration::from_secs(0),
+            finished: false,
+        };
+    }
+
+    pub fn target(&self) -> i32 {
+        return self.target;
+    }
+
+    pub fn lines_left(&self, game: &Game) -> i32 {
+        return (self.target - game.stats().cleared).max(0);
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        return self.elapsed;
+    }
+}
+
+impl GameMode for Sprint {
+    fn on_start(&mut self, _game: &mut ModeContext) {
+        self.elapsed = Duration::from_secs(0);
+        self.finished = false;
+    }
+
+    fn on_lines_cleared(&mut self, game: &mut ModeContext, _lines: i32) {
+        if game.stats().cleared >= self.target {
+            self.finished = true;
+        }
+    }
+
+    fn on_tick(&mut self, _game: &mut ModeContext, frame_time: Duration) {
+        self.elapsed += frame_time;
+    }
+
+    fn is_finished(&self) -> Option<Outcome> {
+        return match self.finished {
+            true => Some(Outcome::Won),
+            false => None,
+        };
+    }
+}
+
+// "1:02.345"
+pub fn format_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    return format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000);
+}
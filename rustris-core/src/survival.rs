@@ -0,0 +1,97 @@
+use crate::game::{Game, FRAMES_PER_SECOND};
+use crate::mode::{GameMode, ModeContext};
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct SurvivalConfig {
+    // frames until the first garbage row rises
+    pub start_interval: i32,
+    // the interval never gets shorter than this
+    pub min_interval: i32,
+    // every row makes the next interval this much shorter, 0.05 for 5%
+    pub speedup: f64,
+    // see `BoardController::insert_garbage`
+    pub messiness: f64,
+}
+
+impl Default for SurvivalConfig {
+    fn default() -> SurvivalConfig {
+        return SurvivalConfig::new();
+    }
+}
+
+impl SurvivalConfig {
+    pub fn new() -> SurvivalConfig {
+        return SurvivalConfig {
+            start_interval: 8 * FRAMES_PER_SECOND,
+            min_interval: FRAMES_PER_SECOND / 2,
+            speedup: 0.05,
+            messiness: 0.5,
+        };
+    }
+}
+
+// Survive garbage rising from the bottom, faster and faster. It runs on the
+// frames of `Game::run_step`, so it plays the same on every frontend; the
+// score is the time until the game is lost.
+#[derive(Clone, Debug)]
+pub struct Survival {
+    config: SurvivalConfig,
+    interval: i32,
+    next_rise: i32,
+    risen: i32,
+}
+
+impl Default for Survival {
+    fn default() -> Survival {
+        return Survival::new(SurvivalConfig::new());
+    }
+}
+
+impl Survival {
+    pub fn new(config: SurvivalConfig) -> Survival {
+        let interval = config.start_interval.max(1);
+        return Survival {
+            config,
+            interval,
+            next_rise: interval,
+            risen: 0,
+        };
+    }
+
+    pub fn survived(&self, game: &Game) -> Duration {
+        return frames_to_time(game.stats().frames);
+    }
+
+    pub fn until_next_row(&self, game: &Game) -> Duration {
+        return frames_to_time(self.next_rise - game.stats().frames);
+    }
+
+    pub fn rows_risen(&self) -> i32 {
+        return self.risen;
+    }
+}
+
+// It never finishes but by topping out.
+impl GameMode for Survival {
+    fn on_start(&mut self, _game: &mut ModeContext) {
+        self.interval = self.config.start_interval.max(1);
+        self.next_rise = self.interval;
+        self.risen = 0;
+    }
+
+    fn on_tick(&mut self, game: &mut ModeContext, _frame_time: Duration) {
+        if game.stats().frames < self.next_rise {
+            return;
+        }
+        game.add_garbage(1, None, self.config.messiness);
+        self.risen += 1;
+        let shorter = (self.interval as f64 * (1.0 - self.config.speedup)) as i32;
+        self.interval = shorter.max(self.config.min_interval).max(1);
+        self.next_rise += self.interval;
+    }
+}
+
+fn frames_to_time(frames: i32) -> Duration {
+    return Duration::from_millis(frames.max(0) as u64 * 1000 / FRAMES_PER_SECOND as u64);
+}
@@ -1,150 +1,201 @@
 use crate::model::Board;
 use crate::model::CellVal;
 use crate::model::Tetromino;
-use rand::Rng;
+use crate::model::{TetrominoRotation, TetrominoType};
+use crate::rotation::RotationSystem;
 
+#[derive(Clone)]
 pub struct TetrominoController {
-    t_type: TetrominoType,
-    rotation: TetrominoRotation,
     color: CellVal,
     pub tetromino: Tetromino,
 }
 
-#[derive(Copy, Clone)]
-enum TetrominoType {
-    I,
-    O,
-    T,
-    J,
-    L,
-    S,
-    Z,
-}
-
-impl TetrominoType {
-    pub fn rand() -> TetrominoType {
-        return match rand::thread_rng().gen_range(0, 7) {
-            0 => TetrominoType::I,
-            1 => TetrominoType::O,
-            2 => TetrominoType::T,
-            3 => TetrominoType::J,
-            4 => TetrominoType::L,
-            5 => TetrominoType::S,
-            _ => TetrominoType::Z,
-        };
-    }
-}
-
-#[derive(Copy, Clone)]
-enum TetrominoRotation {
-    Rot0,
-    Rot1,
-    Rot2,
-    Rot3,
-}
-
-impl TetrominoRotation {
-    pub fn rand() -> TetrominoRotation {
-        return match rand::thread_rng().gen_range(0, 4) {
-            0 => TetrominoRotation::Rot0,
-            1 => TetrominoRotation::Rot1,
-            2 => TetrominoRotation::Rot2,
-            _ => TetrominoRotation::Rot3,
-        };
-    }
-}
-
 impl TetrominoController {
-    pub fn rand() -> TetrominoController {
+    pub fn spawn(
+        t_type: TetrominoType,
+        color: CellVal,
+        rotation: TetrominoRotation,
+        system: RotationSystem,
+        scale: i32,
+    ) -> TetrominoController {
         let mut new = TetrominoController {
-            t_type: TetrominoType::I,
             color: CellVal::Free,
-            rotation: TetrominoRotation::Rot0,
             tetromino: Tetromino::new(),
         };
-        new.set_random();
-        new.update_tetromino();
+        new.tetromino.system = system;
+        new.tetromino.scale = scale;
+        new.set_piece(t_type, color, rotation);
         return new;
     }
 
     pub fn try_move_horizontally(&mut self, x: i32, board: &Board) -> bool {
-        return self.try_change(x, 0, 0, board);
+        return self.try_change(x, 0, board);
     }
 
     pub fn try_move_vertically(&mut self, y: i32, board: &Board) -> bool {
-        return self.try_change(0, y, 0, board);
+        return self.try_change(0, y, board);
     }
 
+    // Turns the piece as its rotation system says, kicks included.
     pub fn try_rotate(&mut self, r: i32, board: &Board) -> bool {
-        return self.try_change(0, 0, r, board);
+        let t = &self.tetromino;
+        let (new_off_x, new_off_y, new_rotation) =
+            match t
+                .system
+                .rotate((t.offset.0, t.offset.1, t.rotation), r, &t.t_type, board, t.scale)
+            {
+                Some(rotated) => rotated,
+                None => return false,
+            };
+        self.set_position(new_off_x, new_off_y, new_rotation);
+        return true;
     }
 
-    fn try_change(&mut self, x_change: i32, y_change: i32, rot_change: i32, board: &Board) -> bool {
-        let new_off_x = self.tetromino.offset.0 + x_change;
-        let new_off_y = self.tetromino.offset.1 + y_change;
-        let new_rotation = next_rotation(self.rotation, rot_change);
-
-        if collides(new_off_x, new_off_y, &self.t_type, &new_rotation, board) {
-            return false;
-        }
+    fn try_change(&mut self, x_change: i32, y_change: i32, board: &Board) -> bool {
+        let current = (
+            self.tetromino.offset.0,
+            self.tetromino.offset.1,
+            self.tetromino.rotation,
+        );
+        let (new_off_x, new_off_y, new_rotation) = match try_change(
+            current,
+            x_change,
+            y_change,
+            &self.tetromino.t_type,
+            board,
+            &self.tetromino.system,
+            self.tetromino.scale,
+        ) {
+            Some(changed) => changed,
+            None => return false,
+        };
+        self.set_position(new_off_x, new_off_y, new_rotation);
+        return true;
+    }
 
-        self.tetromino.offset.0 = new_off_x;
-        self.tetromino.offset.1 = new_off_y;
-        self.rotation = new_rotation;
+    fn set_position(&mut self, off_x: i32, off_y: i32, rotation: TetrominoRotation) {
+        self.tetromino.offset.0 = off_x;
+        self.tetromino.offset.1 = off_y;
+        self.tetromino.rotation = rotation;
 
         self.tetromino.inc_change_count();
         self.update_tetromino();
-        return true;
+    }
+
+    // Resting on the stack or the floor.
+    pub fn is_grounded(&self, board: &Board) -> bool {
+        let t = &self.tetromino;
+        return collides(
+            t.offset.0,
+            t.offset.1 + 1,
+            &t.t_type,
+            &t.rotation,
+            board,
+            &t.system,
+            t.scale,
+        );
     }
 
     pub fn collides(&self, board: &Board) -> bool {
         return collides(
             self.tetromino.offset.0,
             self.tetromino.offset.1,
-            &self.t_type,
-            &self.rotation,
+            &self.tetromino.t_type,
+            &self.tetromino.rotation,
             board,
+            &self.tetromino.system,
+            self.tetromino.scale,
         );
     }
 
-    fn update_tetromino(&mut self) {
-        for y in 0..4 {
-            for x in 0..4 {
-                let is_set = tetromino(&self.t_type, &self.rotation)[y as usize][x as usize] == 1;
-                self.tetromino.set(
-                    x,
-                    y,
-                    match is_set {
-                        true => self.color,
-                        false => CellVal::Free,
-                    },
-                );
+    // Moves an overlapped piece up until it's free again, by at most
+    // `max_rows`. Returns false (and leaves it) if that isn't possible.
+    pub fn lift_out(&mut self, max_rows: i32, board: &Board) -> bool {
+        let t = &self.tetromino;
+        let lift = (0..=max_rows).find(|lift| {
+            !collides(
+                t.offset.0,
+                t.offset.1 - lift,
+                &t.t_type,
+                &t.rotation,
+                board,
+                &t.system,
+                t.scale,
+            )
+        });
+        return match lift {
+            Some(0) => true,
+            Some(lift) => {
+                self.tetromino.offset.1 -= lift;
+                self.tetromino.inc_change_count();
+                true
             }
+            None => false,
+        };
+    }
+
+    fn update_tetromino(&mut self) {
+        let t = &mut self.tetromino;
+        let system = t.system.clone();
+        t.resize(system.box_size(t.t_type) * t.scale);
+        for (x, y) in scaled_cells(&system, t.t_type, t.rotation, t.scale) {
+            t.set(x, y, self.color);
         }
     }
 
     pub fn update_by(&mut self, other: &TetrominoController) {
-        self.t_type = other.t_type;
+        self.tetromino.t_type = other.tetromino.t_type;
+        self.tetromino.system = other.tetromino.system.clone();
+        self.tetromino.scale = other.tetromino.scale;
         self.color = other.color;
-        self.rotation = other.rotation;
-        self.tetromino.offset = (3, 0);
+        self.tetromino.rotation = other.tetromino.rotation;
+        self.tetromino.offset = self.tetromino.spawn_offset();
 
         self.tetromino.inc_change_count();
         self.update_tetromino();
     }
 
-    pub fn set_random(&mut self) {
-        self.t_type = TetrominoType::rand();
-        self.color = CellVal::rand_color();
-        self.rotation = TetrominoRotation::rand();
-        self.tetromino.offset = (3, 0);
+    // Turns the piece back the way it spawns.
+    pub fn reset_rotation(&mut self) {
+        self.tetromino.rotation = TetrominoRotation::Rot0;
+        self.tetromino.offset = self.tetromino.spawn_offset();
+
+        self.tetromino.inc_change_count();
+        self.update_tetromino();
+    }
+
+    pub fn set_piece(&mut self, t_type: TetrominoType, color: CellVal, rotation: TetrominoRotation) {
+        self.tetromino.t_type = t_type;
+        self.color = color;
+        self.tetromino.rotation = rotation;
+        self.tetromino.offset = self.tetromino.spawn_offset();
 
         self.tetromino.inc_change_count();
         self.update_tetromino();
     }
 }
 
-fn next_rotation(current: TetrominoRotation, rot_change: i32) -> TetrominoRotation {
+// Moves a piece position by whole minos, `scale` board cells each. Returns
+// the new position, or `None` if the piece would collide with the board there.
+pub(crate) fn try_change(
+    current: (i32, i32, TetrominoRotation),
+    x_change: i32,
+    y_change: i32,
+    t_type: &TetrominoType,
+    board: &Board,
+    system: &RotationSystem,
+    scale: i32,
+) -> Option<(i32, i32, TetrominoRotation)> {
+    let new_off_x = current.0 + x_change * scale;
+    let new_off_y = current.1 + y_change * scale;
+
+    if collides(new_off_x, new_off_y, t_type, &current.2, board, system, scale) {
+        return None;
+    }
+    return Some((new_off_x, new_off_y, current.2));
+}
+
+pub(crate) fn next_rotation(current: TetrominoRotation, rot_change: i32) -> TetrominoRotation {
     match rot_change {
         0 => current,
         1 => match current {
@@ -163,193 +214,40 @@ fn next_rotation(current: TetrominoRotation, rot_change: i32) -> TetrominoRotati
     }
 }
 
-fn collides(off_x: i32, off_y: i32, t_type: &TetrominoType, rot: &TetrominoRotation, board: &Board) -> bool {
-    for y in 0..4 {
-        for x in 0..4 {
-            let is_set = tetromino(&t_type, &rot)[y as usize][x as usize] == 1;
-            if is_set {
-                let bx = off_x + x;
-                let by = off_y + y;
-                if bx < 0 || bx >= board.width() {
-                    return true;
-                }
-                if by < 0 || by >= board.height() {
-                    return true;
-                }
-                match board.at(bx, by) {
-                    CellVal::Free => (/* free */),
-                    _ => return true,
-                }
-            }
+pub(crate) fn collides(
+    off_x: i32,
+    off_y: i32,
+    t_type: &TetrominoType,
+    rot: &TetrominoRotation,
+    board: &Board,
+    system: &RotationSystem,
+    scale: i32,
+) -> bool {
+    for (x, y) in scaled_cells(system, *t_type, *rot, scale) {
+        let bx = off_x + x;
+        let by = off_y + y;
+        if bx < 0 || bx >= board.width() {
+            return true;
+        }
+        if by < 0 || by >= board.height() {
+            return true;
+        }
+        match board.at(bx, by) {
+            CellVal::Free => (/* free */),
+            _ => return true,
         }
     }
     return false;
 }
 
-// from: https://strategywiki.org/wiki/Tetris/Rotation_systems
-fn tetromino(b_type: &TetrominoType, b_rot: &TetrominoRotation) -> [[i32; 4]; 4] {
-    return match b_type {
-        TetrominoType::I => match b_rot {
-            TetrominoRotation::Rot0 => [
-                [0, 0, 0, 0], //
-                [1, 1, 1, 1],
-                [0, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot1 => [
-                [0, 0, 1, 0], //
-                [0, 0, 1, 0],
-                [0, 0, 1, 0],
-                [0, 0, 1, 0],
-            ],
-            TetrominoRotation::Rot2 => [
-                [0, 0, 0, 0], //
-                [0, 0, 0, 0],
-                [1, 1, 1, 1],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot3 => [
-                [0, 1, 0, 0], //
-                [0, 1, 0, 0],
-                [0, 1, 0, 0],
-                [0, 1, 0, 0],
-            ],
-        },
-        TetrominoType::O => [
-            [0, 0, 0, 0], //
-            [0, 1, 1, 0],
-            [0, 1, 1, 0],
-            [0, 0, 0, 0],
-        ],
-        TetrominoType::T => match b_rot {
-            TetrominoRotation::Rot0 => [
-                [0, 1, 0, 0], //
-                [1, 1, 1, 0],
-                [0, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot1 => [
-                [0, 1, 0, 0], //
-                [0, 1, 1, 0],
-                [0, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot2 => [
-                [0, 0, 0, 0], //
-                [1, 1, 1, 0],
-                [0, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot3 => [
-                [0, 1, 0, 0], //
-                [1, 1, 0, 0],
-                [0, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-        },
-        TetrominoType::J => match b_rot {
-            TetrominoRotation::Rot0 => [
-                [1, 0, 0, 0], //
-                [1, 1, 1, 0],
-                [0, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot1 => [
-                [0, 1, 1, 0], //
-                [0, 1, 0, 0],
-                [0, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot2 => [
-                [0, 0, 0, 0], //
-                [1, 1, 1, 0],
-                [0, 0, 1, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot3 => [
-                [0, 1, 0, 0], //
-                [0, 1, 0, 0],
-                [1, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-        },
-        TetrominoType::L => match b_rot {
-            TetrominoRotation::Rot0 => [
-                [0, 0, 1, 0], //
-                [1, 1, 1, 0],
-                [0, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot1 => [
-                [0, 1, 0, 0], //
-                [0, 1, 0, 0],
-                [0, 1, 1, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot2 => [
-                [0, 0, 0, 0], //
-                [1, 1, 1, 0],
-                [1, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot3 => [
-                [1, 1, 0, 0], //
-                [0, 1, 0, 0],
-                [0, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-        },
-        TetrominoType::S => match b_rot {
-            TetrominoRotation::Rot0 => [
-                [0, 1, 1, 0], //
-                [1, 1, 0, 0],
-                [0, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot1 => [
-                [0, 1, 0, 0], //
-                [0, 1, 1, 0],
-                [0, 0, 1, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot2 => [
-                [0, 0, 0, 0], //
-                [0, 1, 1, 0],
-                [1, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot3 => [
-                [1, 0, 0, 0], //
-                [1, 1, 0, 0],
-                [0, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-        },
-        TetrominoType::Z => match b_rot {
-            TetrominoRotation::Rot0 => [
-                [1, 1, 0, 0], //
-                [0, 1, 1, 0],
-                [0, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot1 => [
-                [0, 0, 1, 0], //
-                [0, 1, 1, 0],
-                [0, 1, 0, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot2 => [
-                [0, 0, 0, 0], //
-                [1, 1, 0, 0],
-                [0, 1, 1, 0],
-                [0, 0, 0, 0],
-            ],
-            TetrominoRotation::Rot3 => [
-                [0, 1, 0, 0], //
-                [1, 1, 0, 0],
-                [1, 0, 0, 0],
-                [0, 0, 0, 0],
-            ],
-        },
-    };
+// The cells of a piece in its box, with every mino `scale` cells wide and high.
+pub(crate) fn scaled_cells(
+    system: &RotationSystem,
+    t_type: TetrominoType,
+    rotation: TetrominoRotation,
+    scale: i32,
+) -> impl Iterator<Item = (i32, i32)> + '_ {
+    let cells = system.cells(t_type, rotation).iter();
+    return cells
+        .flat_map(move |(x, y)| (0..scale * scale).map(move |i| (x * scale + i % scale, y * scale + i / scale)));
 }
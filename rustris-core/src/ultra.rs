@@ -0,0 +1,58 @@
+use crate::mode::{GameMode, ModeContext, Outcome};
+use std::time::Duration;
+
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+
+// Score as much as possible before the time runs out. Like `Sprint`, it
+// counts the frame times of the frontend. A lost game stops the clock early.
+#[derive(Clone, Debug)]
+pub struct Ultra {
+    limit: Duration,
+    elapsed: Duration,
+    finished: bool,
+}
+
+impl Default for Ultra {
+    fn default() -> Ultra {
+        return Ultra::new(ULTRA_TIME);
+    }
+}
+
+impl Ultra {
+    pub fn new(limit: Duration) -> Ultra {
+        return Ultra {
+            limit: limit.max(Duration::from_secs(1)),
+            elapsed: Duration::from_secs(0),
+            finished: false,
+        };
+    }
+
+    pub fn limit(&self) -> Duration {
+        return self.limit;
+    }
+
+    pub fn remaining(&self) -> Duration {
+        return self.limit - self.elapsed;
+    }
+}
+
+impl GameMode for Ultra {
+    fn on_start(&mut self, _game: &mut ModeContext) {
+        self.elapsed = Duration::from_secs(0);
+        self.finished = false;
+    }
+
+    fn on_tick(&mut self, _game: &mut ModeContext, frame_time: Duration) {
+        self.elapsed = (self.elapsed + frame_time).min(self.limit);
+        if self.elapsed >= self.limit {
+            self.finished = true;
+        }
+    }
+
+    fn is_finished(&self) -> Option<Outcome> {
+        return match self.finished {
+            true => Some(Outcome::Ended),
+            false => None,
+        };
+    }
+}
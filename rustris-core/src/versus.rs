@@ -0,0 +1,378 @@
+use crate::game::{Game, StateHasher};
+use crate::model::{LockEvent, UserInput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+
+pub const PLAYERS: usize = 2;
+
+// How many garbage rows a lock sends to the opponent.
+#[derive(Clone, Debug)]
+pub struct AttackTable {
+    // by lines cleared at once, without a T-spin
+    pub lines: [i32; 5],
+    // by lines cleared with a T-spin
+    pub t_spin: [i32; 4],
+    // added by combo count; the last entry applies to all longer combos
+    pub combo: Vec<i32>,
+    pub back_to_back: i32,
+    pub perfect_clear: i32,
+}
+
+impl Default for AttackTable {
+    fn default() -> AttackTable {
+        return AttackTable::new();
+    }
+}
+
+impl AttackTable {
+    // Values close to the guideline games.
+    pub fn new() -> AttackTable {
+        return AttackTable {
+            lines: [0, 0, 1, 2, 4],
+            t_spin: [0, 2, 4, 6],
+            combo: vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+            back_to_back: 1,
+            perfect_clear: 10,
+        };
+    }
+
+    pub fn attack(&self, lock: &LockEvent) -> i32 {
+        if lock.lines <= 0 {
+            return 0;
+        }
+        let mut attack = match lock.t_spin {
+            true => self.t_spin[lock.lines.min(3) as usize],
+            false => self.lines[lock.lines.min(4) as usize],
+        };
+        if let Some(last) = self.combo.last() {
+            attack += *self.combo.get(lock.combo as usize).unwrap_or(last);
+        }
+        if lock.back_to_back {
+            attack += self.back_to_back;
+        }
+        if lock.perfect_clear {
+            attack += self.perfect_clear;
+        }
+        return attack;
+    }
+}
+
+// Rows sent by one attack. They rise together, with one open column.
+#[derive(Copy, Clone, Debug)]
+struct Garbage {
+    rows: i32,
+    hole: i32,
+}
+
+// Garbage waiting to rise on a board, oldest attack first.
+#[derive(Clone, Default)]
+pub struct GarbageQueue {
+    queue: VecDeque<Garbage>,
+}
+
+impl GarbageQueue {
+    pub fn new() -> GarbageQueue {
+        return GarbageQueue { queue: VecDeque::new() };
+    }
+
+    pub fn total(&self) -> i32 {
+        return self.queue.iter().map(|g| g.rows).sum();
+    }
+
+    pub fn push(&mut self, rows: i32, hole: i32) {
+        if rows > 0 {
+            self.queue.push_back(Garbage { rows, hole });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    // Cancels queued rows with an attack; returns the part of the attack
+    // that is left to send.
+    pub fn cancel(&mut self, attack: i32) -> i32 {
+        let mut attack = attack;
+        while attack > 0 && !self.queue.is_empty() {
+            let cancelled = attack.min(self.queue[0].rows);
+            attack -= cancelled;
+            self.queue[0].rows -= cancelled;
+            if self.queue[0].rows == 0 {
+                self.queue.pop_front();
+            }
+        }
+        return attack;
+    }
+
+    // Lets up to `cap` rows rise on the game's board.
+    pub fn raise(&mut self, game: &mut Game, cap: i32, messiness: f64) {
+        let mut budget = cap;
+        while budget > 0 {
+            let garbage = match self.queue.front_mut() {
+                Some(garbage) => garbage,
+                None => break,
+            };
+            let rows = garbage.rows.min(budget);
+            let hole = garbage.hole;
+            garbage.rows -= rows;
+            if garbage.rows == 0 {
+                self.queue.pop_front();
+            }
+            budget -= rows;
+            game.add_garbage(rows, Some(hole), messiness);
+        }
+    }
+}
+
+// Two games played against each other. Both get the same pieces. Attacks
+// first cancel the sender's own incoming garbage; the rest is queued for
+// the opponent, where it rises after their next piece that clears no lines.
+#[derive(Clone)]
+pub struct Match {
+    games: [Game; PLAYERS],
+    incoming: [GarbageQueue; PLAYERS],
+    pub attack_table: AttackTable,
+    // the most garbage rows rising after a single piece
+    pub garbage_cap: i32,
+    // see `BoardController::insert_garbage`
+    pub messiness: f64,
+    lines_sent: [i32; PLAYERS],
+    wins: [i32; PLAYERS],
+    over: bool,
+    winner: Option<usize>,
+    rng: StdRng,
+}
+
+impl Default for Match {
+    fn default() -> Match {
+        return Match::new();
+    }
+}
+
+impl Match {
+    pub fn new() -> Match {
+        return Match::with_rng(StdRng::from_entropy());
+    }
+
+    pub fn with_seed(seed: u64) -> Match {
+        return Match::with_rng(StdRng::seed_from_u64(seed));
+    }
+
+    fn with_rng(mut rng: StdRng) -> Match {
+        let seed: u64 = rng.gen();
+        return Match {
+            games: [Game::with_seed(seed), Game::with_seed(seed)],
+            incoming: [GarbageQueue::new(), GarbageQueue::new()],
+            attack_table: AttackTable::new(),
+            garbage_cap: 8,
+            messiness: 0.0,
+            lines_sent: [0; PLAYERS],
+            wins: [0; PLAYERS],
+            over: false,
+            winner: None,
+            rng,
+        };
+    }
+
+    pub fn game(&self, player: usize) -> &Game {
+        return &self.games[player];
+    }
+
+    // Garbage rows waiting to rise on the player's board.
+    pub fn incoming_garbage(&self, player: usize) -> i32 {
+        return self.incoming[player].total();
+    }
+
+    pub fn lines_sent(&self, player: usize) -> i32 {
+        return self.lines_sent[player];
+    }
+
+    pub fn wins(&self, player: usize) -> i32 {
+        return self.wins[player];
+    }
+
+    pub fn is_over(&self) -> bool {
+        return self.over;
+    }
+
+    // `None` while the match runs, or if both players topped out together.
+    pub fn winner(&self) -> Option<usize> {
+        return self.winner;
+    }
+
+    // See `Game::state_hash`.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        for player in 0..PLAYERS {
+            hasher.write(self.games[player].state_hash() as i64);
+            for garbage in self.incoming[player].queue.iter() {
+                hasher.write(garbage.rows as i64);
+                hasher.write(garbage.hole as i64);
+            }
+            hasher.write(self.lines_sent[player] as i64);
+            hasher.write(self.wins[player] as i64);
+        }
+        hasher.write(self.over as i64);
+        hasher.write(self.winner.map_or(-1, |w| w as i64));
+        hasher.write(self.rng.clone().gen::<i64>());
+        return hasher.finish();
+    }
+
+    // Either player's `Reset` starts the next round once this one is over;
+    // during a round it is ignored.
+    pub fn handle_input(&mut self, player: usize, input: &UserInput) {
+        if *input == UserInput::Reset {
+            if self.over {
+                self.new_round();
+            }
+            return;
+        }
+        if self.over {
+            return;
+        }
+        self.games[player].handle_input(input);
+        self.after_action(player);
+    }
+
+    pub fn run_step(&mut self) {
+        if self.over {
+            return;
+        }
+        for player in 0..PLAYERS {
+            self.games[player].run_step();
+            self.after_action(player);
+        }
+    }
+
+    fn after_action(&mut self, player: usize) {
+        let lock = match self.games[player].take_lock_event() {
+            Some(lock) => lock,
+            None => return,
+        };
+
+        let attack = self.incoming[player].cancel(self.attack_table.attack(&lock));
+        if attack > 0 {
+            let width = self.games[player].current_board().width();
+            let hole = self.rng.gen_range(0, width);
+            self.incoming[1 - player].push(attack, hole);
+            self.lines_sent[player] += attack;
+        }
+
+        if lock.lines == 0 {
+            self.incoming[player].raise(&mut self.games[player], self.garbage_cap, self.messiness);
+        }
+        self.check_over();
+    }
+
+    fn check_over(&mut self) {
+        let lost = [self.games[0].is_game_over(), self.games[1].is_game_over()];
+        if !lost[0] && !lost[1] {
+            return;
+        }
+        self.over = true;
+        self.winner = match lost {
+            [true, false] => Some(1),
+            [false, true] => Some(0),
+            _ => None,
+        };
+        if let Some(winner) = self.winner {
+            self.wins[winner] += 1;
+        }
+    }
+
+    fn new_round(&mut self) {
+        let seed: u64 = self.rng.gen();
+        for player in 0..PLAYERS {
+            self.games[player].reset_with_seed(seed);
+            self.incoming[player].clear();
+            self.lines_sent[player] = 0;
+        }
+        self.over = false;
+        self.winner = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{CellVal, TetrominoType};
+
+    fn lock(lines: i32) -> LockEvent {
+        return LockEvent {
+            t_type: TetrominoType::T,
+            lines,
+            spin: false,
+            t_spin: false,
+            combo: 0,
+            back_to_back: false,
+            perfect_clear: false,
+            chain: 0,
+            matched: 0,
+        };
+    }
+
+    #[test]
+    fn attacks_follow_the_table() {
+        let table = AttackTable::new();
+        let (mut spin, mut t_spin, mut combo) = (lock(2), lock(2), lock(1));
+        spin.spin = true;
+        t_spin.t_spin = true;
+        combo.combo = 4;
+        let (mut long_combo, mut back_to_back, mut perfect_clear) = (combo, lock(4), lock(4));
+        long_combo.combo = 50;
+        back_to_back.back_to_back = true;
+        perfect_clear.perfect_clear = true;
+
+        let cases = [
+            (lock(0), 0),
+            (lock(2), 1),
+            (lock(4), 4),
+            // a spin only counts as a T-spin when the game says so
+            (spin, 1),
+            (t_spin, 4),
+            (combo, 2),
+            (long_combo, 5),
+            (back_to_back, 5),
+            (perfect_clear, 14),
+        ];
+        for (lock, attack) in cases.iter() {
+            assert_eq!(table.attack(lock), *attack, "{:?}", lock);
+        }
+    }
+
+    #[test]
+    fn attacks_cancel_the_oldest_garbage_first() {
+        let mut garbage = GarbageQueue::new();
+        garbage.push(3, 1);
+        garbage.push(0, 2);
+        garbage.push(2, 4);
+        assert_eq!(garbage.total(), 5);
+        assert_eq!(garbage.cancel(4), 0);
+        assert_eq!(garbage.total(), 1);
+        assert_eq!(garbage.queue[0].hole, 4);
+        assert_eq!(garbage.cancel(3), 2);
+        assert_eq!(garbage.total(), 0);
+    }
+
+    #[test]
+    fn garbage_rises_up_to_the_cap() {
+        let mut game = Game::with_seed(1);
+        let mut garbage = GarbageQueue::new();
+        garbage.push(3, 2);
+        garbage.push(2, 5);
+        garbage.raise(&mut game, 4, 0.0);
+        assert_eq!(garbage.total(), 1);
+
+        let board = game.current_board();
+        let free = |y| {
+            (0..board.width())
+                .filter(|x| board.at(*x, y) == CellVal::Free)
+                .collect()
+        };
+        let rows: Vec<Vec<i32>> = (16..20).map(free).collect();
+        // the first attack rises first and ends up on top
+        assert_eq!(rows, [vec![2], vec![2], vec![2], vec![5]]);
+        assert_eq!(free(15).len(), board.width() as usize);
+    }
+}
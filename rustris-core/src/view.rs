@@ -0,0 +1,88 @@
+use crate::model::{Board, Tetromino, UserInput};
+
+// How renderers show a game, which plays the same whatever the view. Cells
+// are turned from the game to the view and back the same way.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct View {
+    // left is right and right is left
+    pub mirrored: bool,
+    // the stack hangs from the top and pieces rise
+    pub upside_down: bool,
+}
+
+impl View {
+    pub fn new(mirrored: bool, upside_down: bool) -> View {
+        return View { mirrored, upside_down };
+    }
+
+    // Where the cell (x, y) of the board shows, or which cell shows at (x, y).
+    pub fn cell(&self, x: i32, y: i32, board: &Board) -> (i32, i32) {
+        let x = match self.mirrored {
+            true => board.width() - 1 - x,
+            false => x,
+        };
+        let y = match self.upside_down {
+            true => board.height() - 1 - y,
+            false => y,
+        };
+        return (x, y);
+    }
+
+    // The board as it shows, hidden cells included.
+    pub fn board(&self, board: &Board) -> Board {
+        let mut shown = board.clone();
+        for y in 0..board.height() {
+            for x in 0..board.width() {
+                let (vx, vy) = self.cell(x, y, board);
+                shown.set(vx, vy, board.at(x, y));
+                shown.ages[vy as usize][vx as usize] = board.ages[y as usize][x as usize];
+            }
+        }
+        return shown;
+    }
+
+    // The piece as it shows over `board`, its box turned along with the board.
+    pub fn piece(&self, piece: &Tetromino, board: &Board) -> Tetromino {
+        let mut shown = piece.clone();
+        let (width, height) = (piece.width(), piece.height());
+        for y in 0..height {
+            for x in 0..width {
+                let vx = if self.mirrored { width - 1 - x } else { x };
+                let vy = if self.upside_down { height - 1 - y } else { y };
+                shown.set(vx, vy, piece.at(x, y));
+            }
+        }
+        // the corner of the box nearest to the origin of the view
+        let (x, y) = self.cell(piece.offset.0, piece.offset.1, board);
+        shown.offset = (
+            if self.mirrored { x - (width - 1) } else { x },
+            if self.upside_down { y - (height - 1) } else { y },
+        );
+        return shown;
+    }
+
+    // What an input on the view does in the game: mirrored, left and right
+    // swap, and so do the turns; upside down, the turns swap too. Drops stay
+    // drops; the keys pointing up and down swap, see `vertical_input`.
+    pub fn input(&self, input: UserInput) -> UserInput {
+        let input = match (self.mirrored, input) {
+            (true, UserInput::MoveLeft) => UserInput::MoveRight,
+            (true, UserInput::MoveRight) => UserInput::MoveLeft,
+            _ => input,
+        };
+        return match (self.mirrored != self.upside_down, input) {
+            (true, UserInput::RotateLeft) => UserInput::RotateRight,
+            (true, UserInput::RotateRight) => UserInput::RotateLeft,
+            _ => input,
+        };
+    }
+
+    // The drop of the up or down key on the view: the one pointing towards
+    // the stack moves the piece down, the other drops it all the way.
+    pub fn vertical_input(&self, up: bool) -> UserInput {
+        return match up != self.upside_down {
+            true => UserInput::DropDown,
+            false => UserInput::MoveDown,
+        };
+    }
+}
@@ -0,0 +1,120 @@
+// Plays a versus match between two rollback sessions over a simulated
+// lossy link and checks that both peers end up in the same state.
+//
+// usage: rustris-rollback-sim [--frames N] [--latency FRAMES] [--jitter FRAMES]
+//                             [--loss P] [--delay FRAMES] [--seed N] [--mismatch]
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustris_core::model::UserInput;
+use rustris_net::rollback::{loopback_pair, Advance, LinkConfig, LoopbackTransport, RollbackSession, SessionConfig};
+
+const INPUTS: [UserInput; 6] = [
+    UserInput::MoveLeft,
+    UserInput::MoveRight,
+    UserInput::MoveDown,
+    UserInput::DropDown,
+    UserInput::RotateLeft,
+    UserInput::RotateRight,
+];
+
+fn main() {
+    let mut frames: u32 = 3600;
+    let mut link = LinkConfig {
+        latency: 4,
+        jitter: 3,
+        loss: 0.1,
+        seed: 0,
+    };
+    let mut config = SessionConfig::new();
+    let mut seed: u64 = 0;
+    // the peers start from different seeds, which has to be noticed
+    let mut mismatch = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--mismatch" => {
+                mismatch = true;
+                continue;
+            }
+            _ => args.next().unwrap_or_default(),
+        };
+        let parsed = match arg.as_str() {
+            "--frames" => value.parse().map(|v| frames = v).is_ok(),
+            "--latency" => value.parse().map(|v| link.latency = v).is_ok(),
+            "--jitter" => value.parse().map(|v| link.jitter = v).is_ok(),
+            "--loss" => value.parse().map(|v| link.loss = v).is_ok(),
+            "--delay" => value.parse().map(|v| config.input_delay = v).is_ok(),
+            "--seed" => value.parse().map(|v| seed = v).is_ok(),
+            _ => false,
+        };
+        if !parsed {
+            println!("bad argument {} '{}'", arg, value);
+            return;
+        }
+    }
+    link.seed = seed;
+
+    let (a, b) = loopback_pair(link);
+    let mut peers = [
+        RollbackSession::new(a, 0, seed, config.clone()),
+        RollbackSession::new(b, 1, if mismatch { !seed } else { seed }, config),
+    ];
+    let mut input_rngs = [StdRng::seed_from_u64(seed + 1), StdRng::seed_from_u64(seed + 2)];
+
+    let mut ticks: u64 = 0;
+    while peers.iter().any(|peer| peer.confirmed_frame() < frames) {
+        for (peer, rng) in peers.iter_mut().zip(input_rngs.iter_mut()) {
+            peer.transport_mut().tick();
+            if peer.frame() < frames {
+                let input = random_input(peer, rng);
+                if peer.advance(input) == Advance::Stalled {
+                    continue;
+                }
+            } else {
+                peer.poll();
+            }
+        }
+        ticks += 1;
+        if ticks > frames as u64 * 100 {
+            println!("no progress after {} ticks", ticks);
+            break;
+        }
+    }
+
+    for (i, peer) in peers.iter().enumerate() {
+        let state = peer.state();
+        println!(
+            "peer {}: frame {} confirmed {} rollbacks {} resimulated {} stalls {} wins {}:{} hash {:016x}",
+            i + 1,
+            peer.frame(),
+            peer.confirmed_frame(),
+            peer.rollbacks(),
+            peer.resimulated_frames(),
+            peer.stalls(),
+            state.wins(0),
+            state.wins(1),
+            state.state_hash()
+        );
+    }
+    println!("{} frames in {} ticks", frames, ticks);
+    let hashes_equal = peers[0].state().state_hash() == peers[1].state().state_hash();
+    match peers.iter().filter_map(|peer| peer.desync()).min() {
+        Some(frame) => println!("DESYNC detected after frame {}", frame),
+        None if !hashes_equal => println!("DESYNC not detected, final states differ"),
+        None => println!("in sync"),
+    }
+}
+
+// Presses a key about every fourth frame and starts a new round once the
+// last one is over.
+fn random_input(peer: &RollbackSession<LoopbackTransport>, rng: &mut StdRng) -> UserInput {
+    if peer.state().is_over() && rng.gen_bool(0.05) {
+        return UserInput::Reset;
+    }
+    if rng.gen_bool(0.25) {
+        return INPUTS[rng.gen_range(0, INPUTS.len())];
+    }
+    return UserInput::NoInput;
+}
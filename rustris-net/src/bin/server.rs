@@ -0,0 +1,24 @@
+// Lobby and relay for two rustris-cmdl clients started with `--connect`.
+//
+// usage: rustris-server [ADDR]
+
+use rustris_net::protocol::DEFAULT_PORT;
+use rustris_net::server::serve;
+use std::net::TcpListener;
+
+fn main() {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("0.0.0.0:{}", DEFAULT_PORT));
+    let listener = match TcpListener::bind(&addr) {
+        Ok(listener) => listener,
+        Err(err) => {
+            println!("can't listen on {}: {}", addr, err);
+            return;
+        }
+    };
+    println!("rustris-server listening on {}", addr);
+    if let Err(err) = serve(listener) {
+        println!("rustris-server stopped: {}", err);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::protocol::{read_frame, write_frame, ClientMessage, ServerMessage, PROTOCOL_VERSION};
+use std::io;
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+// A connection to rustris-server. Server messages are read on a thread of
+// their own, so polling never blocks the game.
+pub struct NetClient {
+    stream: TcpStream,
+    messages: Receiver<ServerMessage>,
+    connected: bool,
+}
+
+impl NetClient {
+    pub fn connect(addr: &str, name: &str) -> io::Result<NetClient> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut reader = stream.try_clone()?;
+
+        let (sender, messages) = channel();
+        std::thread::spawn(move || {
+            while let Ok(Some(message)) = read_frame::<_, ServerMessage>(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = NetClient {
+            stream,
+            messages,
+            connected: true,
+        };
+        client.send(&ClientMessage::Hello {
+            name: String::from(name),
+            version: PROTOCOL_VERSION,
+        });
+        return Ok(client);
+    }
+
+    // A failed send shows up as a lost connection.
+    pub fn send(&mut self, message: &ClientMessage) {
+        if write_frame(&mut self.stream, message).is_err() {
+            self.connected = false;
+        }
+    }
+
+    // The next message, if one arrived.
+    pub fn poll(&mut self) -> Option<ServerMessage> {
+        return match self.messages.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.connected = false;
+                None
+            }
+        };
+    }
+
+    pub fn is_connected(&self) -> bool {
+        return self.connected;
+    }
+}
+
+impl Drop for NetClient {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
@@ -0,0 +1,132 @@
+// Messages between rustris-server and its clients. Every message is one
+// frame: a 4 byte big-endian length, followed by that many bytes of JSON.
+//
+// Each client plays its own game. The server only runs the lobby and
+// relays attacks and board states between the two players; it decides a
+// round when a client reports its game over.
+
+use rustris_core::model::{Board, CellVal};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7878;
+
+const MAX_FRAME: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello { name: String, version: u32 },
+    // ready for the next round
+    Ready,
+    // garbage for the opponent, after cancelling own incoming garbage
+    Attack { rows: i32, hole: i32 },
+    // sent after every piece, for the opponent's view
+    State(PlayerState),
+    GameOver,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Welcome { player: usize },
+    // indexed by player; `None` for a free place
+    Lobby { players: Vec<Option<LobbyEntry>> },
+    // both players play the pieces of `Game::with_seed(seed)`
+    Start { seed: u64 },
+    Attack { rows: i32, hole: i32 },
+    OpponentState(PlayerState),
+    RoundOver { winner: usize },
+    OpponentLeft,
+    Error { reason: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LobbyEntry {
+    pub name: String,
+    pub ready: bool,
+    pub wins: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerState {
+    // one string per row, top row first, see `encode_board`
+    pub board: Vec<String>,
+    pub incoming: i32,
+    pub lines: i32,
+}
+
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, message: &T) -> io::Result<()> {
+    let payload = serde_json::to_vec(message).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(&payload)?;
+    return writer.flush();
+}
+
+// `Ok(None)` when the other side closed the connection between frames.
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> io::Result<Option<T>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => (),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload)?;
+    let message = serde_json::from_slice(&payload).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    return Ok(Some(message));
+}
+
+// '.' for free cells, 'G' for garbage and '1' to '8' for the colors.
+pub fn encode_board(board: &Board) -> Vec<String> {
+    return (0..board.height())
+        .map(|y| (0..board.width()).map(|x| encode_cell(board.at(x, y))).collect())
+        .collect();
+}
+
+pub fn decode_board(rows: &[String]) -> Board {
+    let mut board = Board::new();
+    for (y, row) in rows.iter().enumerate().take(board.height() as usize) {
+        for (x, cell) in row.chars().enumerate().take(board.width() as usize) {
+            board.set(x as i32, y as i32, decode_cell(cell));
+        }
+    }
+    return board;
+}
+
+pub fn encode_cell(val: CellVal) -> char {
+    return match val {
+        CellVal::Free => '.',
+        CellVal::Color1 => '1',
+        CellVal::Color2 => '2',
+        CellVal::Color3 => '3',
+        CellVal::Color4 => '4',
+        CellVal::Color5 => '5',
+        CellVal::Color6 => '6',
+        CellVal::Color7 => '7',
+        CellVal::Color8 => '8',
+        CellVal::Garbage => 'G',
+    };
+}
+
+// Anything else, like the '#' of older versions, is taken for the first color.
+pub fn decode_cell(cell: char) -> CellVal {
+    return match cell {
+        '.' => CellVal::Free,
+        '2' => CellVal::Color2,
+        '3' => CellVal::Color3,
+        '4' => CellVal::Color4,
+        '5' => CellVal::Color5,
+        '6' => CellVal::Color6,
+        '7' => CellVal::Color7,
+        '8' => CellVal::Color8,
+        'G' => CellVal::Garbage,
+        _ => CellVal::Color1,
+    };
+}
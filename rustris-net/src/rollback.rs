@@ -0,0 +1,514 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustris_core::model::UserInput;
+use rustris_core::versus::Match;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+
+// Moves packets between the two peers of a rollback session. Packets may be
+// lost, duplicated or reordered; the session copes with all of it.
+pub trait Transport {
+    fn send(&mut self, packet: Vec<u8>);
+    fn receive(&mut self) -> Option<Vec<u8>>;
+}
+
+// What a peer sends every frame. All inputs the other side hasn't
+// acknowledged yet are repeated, so a lost packet costs nothing but time.
+#[derive(Serialize, Deserialize)]
+struct Packet {
+    first_frame: u32,
+    inputs: Vec<u8>,
+    // remote inputs received so far, everything before is acknowledged
+    ack: u32,
+    // the latest confirmed frame and the state hash after it
+    hash: Option<(u32, u64)>,
+}
+
+pub fn input_code(input: UserInput) -> u8 {
+    return match input {
+        UserInput::NoInput => 0,
+        UserInput::MoveLeft => 1,
+        UserInput::MoveRight => 2,
+        UserInput::MoveDown => 3,
+        UserInput::DropDown => 4,
+        UserInput::RotateLeft => 5,
+        UserInput::RotateRight => 6,
+        UserInput::Reset => 7,
+        UserInput::Hold => 8,
+    };
+}
+
+pub fn input_from_code(code: u8) -> UserInput {
+    return match code {
+        1 => UserInput::MoveLeft,
+        2 => UserInput::MoveRight,
+        3 => UserInput::MoveDown,
+        4 => UserInput::DropDown,
+        5 => UserInput::RotateLeft,
+        6 => UserInput::RotateRight,
+        7 => UserInput::Reset,
+        8 => UserInput::Hold,
+        _ => UserInput::NoInput,
+    };
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    // frames between reading a local input and playing it
+    pub input_delay: u32,
+    // how far the local game may run ahead of the remote inputs
+    pub max_rollback: u32,
+    // frames between two state hashes
+    pub hash_interval: u32,
+}
+
+impl Default for SessionConfig {
+    fn default() -> SessionConfig {
+        return SessionConfig::new();
+    }
+}
+
+impl SessionConfig {
+    pub fn new() -> SessionConfig {
+        return SessionConfig {
+            input_delay: 2,
+            max_rollback: 8,
+            hash_interval: 10,
+        };
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Advance {
+    Ran,
+    // too far ahead of the remote inputs, the frame has to wait
+    Stalled,
+}
+
+// One peer of a two player `Match` with lockstep inputs and rollback. Both
+// peers start from the same seed and play the same inputs, so they play the
+// same match. Missing remote inputs are predicted as `NoInput`; when the real
+// ones arrive and differ, the match is rolled back to a snapshot and played
+// again.
+pub struct RollbackSession<T: Transport> {
+    transport: T,
+    config: SessionConfig,
+    local: usize,
+    state: Match,
+    // the next frame to simulate
+    frame: u32,
+    // local inputs from frame `local_base` on
+    local_inputs: VecDeque<u8>,
+    local_base: u32,
+    // confirmed remote inputs from frame `remote_base` on
+    remote_inputs: VecDeque<u8>,
+    remote_base: u32,
+    // local inputs the remote has
+    acked: u32,
+    // the state before each frame from the oldest unconfirmed one on
+    snapshots: VecDeque<(u32, Match)>,
+    // the next frame to hash once it is confirmed
+    next_hash: u32,
+    local_hashes: VecDeque<(u32, u64)>,
+    remote_hashes: VecDeque<(u32, u64)>,
+    desync: Option<u32>,
+    rollbacks: u32,
+    resimulated: u32,
+    stalls: u32,
+}
+
+impl<T: Transport> RollbackSession<T> {
+    pub fn new(transport: T, local: usize, seed: u64, config: SessionConfig) -> RollbackSession<T> {
+        let delay = config.input_delay as usize;
+        return RollbackSession {
+            transport,
+            next_hash: config.hash_interval.max(1),
+            config,
+            local,
+            state: Match::with_seed(seed),
+            frame: 0,
+            local_inputs: std::iter::repeat_n(input_code(UserInput::NoInput), delay).collect(),
+            local_base: 0,
+            remote_inputs: VecDeque::new(),
+            remote_base: 0,
+            acked: 0,
+            snapshots: VecDeque::new(),
+            local_hashes: VecDeque::new(),
+            remote_hashes: VecDeque::new(),
+            desync: None,
+            rollbacks: 0,
+            resimulated: 0,
+            stalls: 0,
+        };
+    }
+
+    // Plays one frame with the given local input, unless the remote inputs
+    // fall too far behind. A stalled input is dropped, the caller may try
+    // again next frame.
+    pub fn advance(&mut self, input: UserInput) -> Advance {
+        self.receive();
+        if self.frame >= self.remote_confirmed() + self.config.max_rollback {
+            self.stalls += 1;
+            self.send();
+            return Advance::Stalled;
+        }
+
+        self.local_inputs.push_back(input_code(input));
+        self.snapshots.push_back((self.frame, self.state.clone()));
+        self.simulate(self.frame);
+        self.frame += 1;
+        self.confirm();
+        self.send();
+        return Advance::Ran;
+    }
+
+    // Exchanges packets without playing a frame.
+    pub fn poll(&mut self) {
+        self.receive();
+        self.send();
+    }
+
+    pub fn state(&self) -> &Match {
+        return &self.state;
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        return &mut self.transport;
+    }
+
+    pub fn local_player(&self) -> usize {
+        return self.local;
+    }
+
+    pub fn frame(&self) -> u32 {
+        return self.frame;
+    }
+
+    // Frames played with the real inputs of both players.
+    pub fn confirmed_frame(&self) -> u32 {
+        return self.frame.min(self.remote_confirmed());
+    }
+
+    // The first frame after which the peers' states differed.
+    pub fn desync(&self) -> Option<u32> {
+        return self.desync;
+    }
+
+    pub fn rollbacks(&self) -> u32 {
+        return self.rollbacks;
+    }
+
+    pub fn resimulated_frames(&self) -> u32 {
+        return self.resimulated;
+    }
+
+    pub fn stalls(&self) -> u32 {
+        return self.stalls;
+    }
+
+    fn remote_confirmed(&self) -> u32 {
+        return self.remote_base + self.remote_inputs.len() as u32;
+    }
+
+    fn simulate(&mut self, frame: u32) {
+        let local = input_from_code(self.local_inputs[(frame - self.local_base) as usize]);
+        let remote = match self.remote_inputs.get((frame - self.remote_base) as usize) {
+            Some(code) => input_from_code(*code),
+            None => UserInput::NoInput,
+        };
+        let mut inputs = [UserInput::NoInput; 2];
+        inputs[self.local] = local;
+        inputs[1 - self.local] = remote;
+        // the same order on both peers, player 0 first
+        for (player, input) in inputs.iter().enumerate() {
+            if *input != UserInput::NoInput {
+                self.state.handle_input(player, input);
+            }
+        }
+        self.state.run_step();
+    }
+
+    fn receive(&mut self) {
+        let mut rollback_to: Option<u32> = None;
+        while let Some(bytes) = self.transport.receive() {
+            let packet: Packet = match serde_json::from_slice(&bytes) {
+                Ok(packet) => packet,
+                Err(_) => continue,
+            };
+            self.acked = self.acked.max(packet.ack);
+            if let Some(hash) = packet.hash {
+                self.remote_hashes.push_back(hash);
+            }
+
+            let confirmed = self.remote_confirmed();
+            let last = packet.first_frame + packet.inputs.len() as u32;
+            if packet.first_frame > confirmed || last <= confirmed {
+                continue;
+            }
+            for frame in confirmed..last {
+                let code = packet.inputs[(frame - packet.first_frame) as usize];
+                self.remote_inputs.push_back(code);
+                // everything from the first wrong prediction on is played again
+                if frame < self.frame && code != input_code(UserInput::NoInput) && rollback_to.is_none() {
+                    rollback_to = Some(frame);
+                }
+            }
+        }
+
+        if let Some(frame) = rollback_to {
+            self.rollback(frame);
+        }
+        self.confirm();
+    }
+
+    fn rollback(&mut self, to: u32) {
+        let index = match self.snapshots.iter().position(|(frame, _)| *frame == to) {
+            Some(index) => index,
+            None => return,
+        };
+        self.snapshots.truncate(index + 1);
+        self.state = self.snapshots[index].1.clone();
+        self.snapshots.pop_back();
+        for frame in to..self.frame {
+            self.snapshots.push_back((frame, self.state.clone()));
+            self.simulate(frame);
+        }
+        self.rollbacks += 1;
+        self.resimulated += self.frame - to;
+    }
+
+    // Hashes the newly confirmed frames and drops what no rollback can need.
+    fn confirm(&mut self) {
+        let confirmed = self.confirmed_frame();
+        while self.next_hash <= confirmed {
+            // the state after frame `next_hash - 1`
+            let hash = match self.snapshots.iter().find(|(frame, _)| *frame == self.next_hash) {
+                Some((_, state)) => state.state_hash(),
+                None => self.state.state_hash(),
+            };
+            self.local_hashes.push_back((self.next_hash, hash));
+            self.next_hash += self.config.hash_interval.max(1);
+        }
+
+        while self.snapshots.front().is_some_and(|(frame, _)| *frame < confirmed) {
+            self.snapshots.pop_front();
+        }
+        while self.remote_base < confirmed && !self.remote_inputs.is_empty() {
+            self.remote_inputs.pop_front();
+            self.remote_base += 1;
+        }
+        let keep = self.acked.min(confirmed);
+        while self.local_base < keep {
+            self.local_inputs.pop_front();
+            self.local_base += 1;
+        }
+        self.compare_hashes();
+    }
+
+    fn compare_hashes(&mut self) {
+        let locals = &self.local_hashes;
+        for (frame, hash) in self.remote_hashes.iter() {
+            let mismatch = locals.iter().any(|(f, h)| f == frame && h != hash);
+            if mismatch && self.desync.is_none_or(|desync| *frame < desync) {
+                self.desync = Some(*frame);
+            }
+        }
+        // hashes the other side will never match anymore
+        let newest = locals.back().map_or(0, |(frame, _)| *frame);
+        self.remote_hashes.retain(|(frame, _)| *frame > newest);
+        while self.local_hashes.len() > 64 {
+            self.local_hashes.pop_front();
+        }
+    }
+
+    fn send(&mut self) {
+        let first_frame = self.acked.max(self.local_base);
+        let inputs: Vec<u8> = self
+            .local_inputs
+            .iter()
+            .skip((first_frame - self.local_base) as usize)
+            .copied()
+            .collect();
+        let packet = Packet {
+            first_frame,
+            inputs,
+            ack: self.remote_confirmed(),
+            hash: self.local_hashes.back().copied(),
+        };
+        if let Ok(bytes) = serde_json::to_vec(&packet) {
+            self.transport.send(bytes);
+        }
+    }
+}
+
+// How the simulated link between two loopback transports behaves.
+#[derive(Clone, Debug)]
+pub struct LinkConfig {
+    // frames until a packet arrives
+    pub latency: u32,
+    // up to this many frames more, so packets may overtake each other
+    pub jitter: u32,
+    // the chance of a packet getting lost
+    pub loss: f64,
+    pub seed: u64,
+}
+
+struct Channel {
+    packets: Vec<(u64, Vec<u8>)>,
+    rng: StdRng,
+}
+
+// One end of an in-process link with latency, jitter and loss. Time is
+// counted in frames; call `tick` once per frame.
+pub struct LoopbackTransport {
+    config: LinkConfig,
+    outgoing: Arc<Mutex<Channel>>,
+    incoming: Arc<Mutex<Channel>>,
+    now: u64,
+}
+
+pub fn loopback_pair(config: LinkConfig) -> (LoopbackTransport, LoopbackTransport) {
+    let channel = |seed: u64| {
+        return Arc::new(Mutex::new(Channel {
+            packets: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+        }));
+    };
+    let a_to_b = channel(config.seed);
+    let b_to_a = channel(!config.seed);
+    let a = LoopbackTransport {
+        config: config.clone(),
+        outgoing: a_to_b.clone(),
+        incoming: b_to_a.clone(),
+        now: 0,
+    };
+    let b = LoopbackTransport {
+        config,
+        outgoing: b_to_a,
+        incoming: a_to_b,
+        now: 0,
+    };
+    return (a, b);
+}
+
+impl LoopbackTransport {
+    pub fn tick(&mut self) {
+        self.now += 1;
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, packet: Vec<u8>) {
+        let mut channel = self.outgoing.lock().unwrap();
+        if channel.rng.gen_bool(self.config.loss.clamp(0.0, 1.0)) {
+            return;
+        }
+        let jitter = channel.rng.gen_range(0, self.config.jitter + 1);
+        let arrival = self.now + (self.config.latency + jitter) as u64;
+        channel.packets.push((arrival, packet));
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let mut channel = self.incoming.lock().unwrap();
+        let index = channel.packets.iter().position(|(arrival, _)| *arrival <= self.now)?;
+        return Some(channel.packets.remove(index).1);
+    }
+}
+
+// Packets over UDP to a fixed peer.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+}
+
+impl UdpTransport {
+    pub fn connect(local: SocketAddr, peer: SocketAddr) -> io::Result<UdpTransport> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        return Ok(UdpTransport {
+            socket,
+            buffer: vec![0; 65536],
+        });
+    }
+}
+
+impl Transport for UdpTransport {
+    // Lost like any other datagram if it fails.
+    fn send(&mut self, packet: Vec<u8>) {
+        let _ = self.socket.send(&packet);
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let size = self.socket.recv(&mut self.buffer).ok()?;
+        return Some(self.buffer[..size].to_vec());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays `frames` frames of random inputs on two peers over a lossy link.
+    fn play(frames: u32, seeds: [u64; 2]) -> [RollbackSession<LoopbackTransport>; 2] {
+        let link = LinkConfig {
+            latency: 4,
+            jitter: 3,
+            loss: 0.1,
+            seed: 7,
+        };
+        let (a, b) = loopback_pair(link);
+        let mut peers = [
+            RollbackSession::new(a, 0, seeds[0], SessionConfig::new()),
+            RollbackSession::new(b, 1, seeds[1], SessionConfig::new()),
+        ];
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..frames * 20 {
+            if peers.iter().all(|peer| peer.confirmed_frame() >= frames) {
+                break;
+            }
+            for peer in peers.iter_mut() {
+                peer.transport_mut().tick();
+                if peer.frame() < frames {
+                    let input = match rng.gen_bool(0.25) {
+                        true => input_from_code(rng.gen_range(1, 9)),
+                        false => UserInput::NoInput,
+                    };
+                    peer.advance(input);
+                } else {
+                    peer.poll();
+                }
+            }
+        }
+        return peers;
+    }
+
+    #[test]
+    fn input_codes_round_trip() {
+        for code in 0..9 {
+            assert_eq!(input_code(input_from_code(code)), code);
+        }
+    }
+
+    #[test]
+    fn peers_stay_in_sync_over_a_lossy_link() {
+        let peers = play(600, [3, 3]);
+        for peer in peers.iter() {
+            assert!(peer.confirmed_frame() >= 600);
+            assert_eq!(peer.desync(), None);
+        }
+        assert!(peers[0].rollbacks() + peers[1].rollbacks() > 0);
+        assert_eq!(peers[0].state().state_hash(), peers[1].state().state_hash());
+    }
+
+    #[test]
+    fn peers_with_different_seeds_notice_the_desync() {
+        let peers = play(300, [3, 4]);
+        for peer in peers.iter() {
+            assert!(peer.desync().is_some());
+        }
+    }
+}
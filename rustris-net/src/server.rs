@@ -0,0 +1,328 @@
+use crate::protocol::{read_frame, write_frame, ClientMessage, LobbyEntry, ServerMessage, PROTOCOL_VERSION};
+use rustris_core::versus::PLAYERS;
+use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, sync_channel, Sender, SyncSender};
+use std::time::Duration;
+
+enum Event {
+    Connected(TcpStream),
+    Message(u64, ClientMessage),
+    Disconnected(u64),
+    HelloTimeout(u64),
+}
+
+// Messages a player hasn't received yet; a player who falls this far behind
+// is dropped rather than holding up the table.
+const OUTBOX_SIZE: usize = 256;
+
+// A connection that hasn't said hello by then gives its place up again.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Seat {
+    id: u64,
+    stream: TcpStream,
+    // written by a thread of its own, so a slow player never blocks the table;
+    // `None` once the seat is closing, see `close`
+    outbox: Option<SyncSender<ServerMessage>>,
+    name: Option<String>,
+    ready: bool,
+    wins: i32,
+}
+
+// Runs the lobby and the rounds of one versus table for two players, for
+// as long as the process lives.
+pub fn serve(listener: TcpListener) -> io::Result<()> {
+    let (events, event_rx) = channel();
+    let acceptor = events.clone();
+    let accept_listener = listener.try_clone()?;
+    std::thread::spawn(move || {
+        for stream in accept_listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            if acceptor.send(Event::Connected(stream)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut table = Table {
+        seats: [None, None],
+        playing: false,
+        next_id: 0,
+        events,
+    };
+    while let Ok(event) = event_rx.recv() {
+        match event {
+            Event::Connected(stream) => table.join(stream),
+            Event::Message(id, message) => table.handle(id, message),
+            Event::Disconnected(id) => table.leave(id),
+            Event::HelloTimeout(id) => table.hello_timeout(id),
+        }
+    }
+    return Ok(());
+}
+
+struct Table {
+    seats: [Option<Seat>; PLAYERS],
+    playing: bool,
+    next_id: u64,
+    events: Sender<Event>,
+}
+
+impl Table {
+    fn join(&mut self, mut stream: TcpStream) {
+        let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+        let place = match self.seats.iter().position(|seat| seat.is_none()) {
+            Some(place) => place,
+            None => {
+                println!("{} refused, table full", peer);
+                let error = ServerMessage::Error {
+                    reason: String::from("the table is full"),
+                };
+                let _ = write_frame(&mut stream, &error);
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+        };
+        let (reader, mut writer) = match (stream.try_clone(), stream.try_clone()) {
+            (Ok(reader), Ok(writer)) => (reader, writer),
+            _ => return,
+        };
+        let _ = stream.set_nodelay(true);
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let events = self.events.clone();
+        std::thread::spawn(move || {
+            let mut reader = reader;
+            while let Ok(Some(message)) = read_frame::<_, ClientMessage>(&mut reader) {
+                if events.send(Event::Message(id, message)).is_err() {
+                    return;
+                }
+            }
+            let _ = events.send(Event::Disconnected(id));
+        });
+        let (outbox, outbox_rx) = sync_channel::<ServerMessage>(OUTBOX_SIZE);
+        std::thread::spawn(move || {
+            while let Ok(message) = outbox_rx.recv() {
+                if write_frame(&mut writer, &message).is_err() {
+                    break;
+                }
+            }
+            // after the last frame of a closing seat; the reader thread then
+            // reports the player as gone
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+        let events = self.events.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(HELLO_TIMEOUT);
+            let _ = events.send(Event::HelloTimeout(id));
+        });
+
+        println!("{} joined as player {}", peer, place + 1);
+        self.seats[place] = Some(Seat {
+            id,
+            stream,
+            outbox: Some(outbox),
+            name: None,
+            ready: false,
+            wins: 0,
+        });
+        self.send(place, &ServerMessage::Welcome { player: place });
+    }
+
+    fn leave(&mut self, id: u64) {
+        let place = match self.place_of(id) {
+            Some(place) => place,
+            None => return,
+        };
+        println!("player {} left", place + 1);
+        self.seats[place] = None;
+        if self.playing {
+            self.playing = false;
+            self.send(1 - place, &ServerMessage::OpponentLeft);
+        }
+        self.broadcast_lobby();
+    }
+
+    fn hello_timeout(&mut self, id: u64) {
+        let place = match self.place_of(id) {
+            Some(place) => place,
+            None => return,
+        };
+        if self.seat(place).name.is_none() {
+            let reason = String::from("no hello in time");
+            self.send(place, &ServerMessage::Error { reason });
+            self.close(place);
+        }
+    }
+
+    fn handle(&mut self, id: u64, message: ClientMessage) {
+        let place = match self.place_of(id) {
+            Some(place) => place,
+            None => return,
+        };
+        if self.seat(place).outbox.is_none() {
+            return;
+        }
+        let opponent = 1 - place;
+
+        match message {
+            ClientMessage::Hello { name, version } => {
+                if version != PROTOCOL_VERSION {
+                    let reason = format!("protocol version {} needed, got {}", PROTOCOL_VERSION, version);
+                    self.send(place, &ServerMessage::Error { reason });
+                    self.close(place);
+                    return;
+                }
+                self.seat(place).name = Some(name);
+                self.broadcast_lobby();
+            }
+            ClientMessage::Ready => {
+                if self.playing || self.seat(place).name.is_none() {
+                    return;
+                }
+                self.seat(place).ready = true;
+                self.broadcast_lobby();
+                let all_ready = self.seats.iter().all(|seat| seat.as_ref().is_some_and(|s| s.ready));
+                if all_ready {
+                    self.start_round();
+                }
+            }
+            ClientMessage::Attack { rows, hole } if self.playing => {
+                self.send(opponent, &ServerMessage::Attack { rows, hole });
+            }
+            ClientMessage::State(state) if self.playing => {
+                self.send(opponent, &ServerMessage::OpponentState(state));
+            }
+            ClientMessage::GameOver if self.playing => {
+                self.playing = false;
+                self.seat(opponent).wins += 1;
+                println!("round over, player {} wins", opponent + 1);
+                for player in 0..PLAYERS {
+                    self.send(player, &ServerMessage::RoundOver { winner: opponent });
+                }
+                self.broadcast_lobby();
+            }
+            // leftovers of a round that ended already
+            _ => (),
+        }
+    }
+
+    fn start_round(&mut self) {
+        let seed: u64 = rand::random();
+        self.playing = true;
+        println!("round started");
+        for player in 0..PLAYERS {
+            self.seat(player).ready = false;
+            self.send(player, &ServerMessage::Start { seed });
+        }
+        self.broadcast_lobby();
+    }
+
+    fn broadcast_lobby(&mut self) {
+        let players: Vec<Option<LobbyEntry>> = self
+            .seats
+            .iter()
+            .map(|seat| {
+                let seat = seat.as_ref()?;
+                Some(LobbyEntry {
+                    name: seat.name.clone()?,
+                    ready: seat.ready,
+                    wins: seat.wins,
+                })
+            })
+            .collect();
+        for player in 0..PLAYERS {
+            self.send(
+                player,
+                &ServerMessage::Lobby {
+                    players: players.clone(),
+                },
+            );
+        }
+    }
+
+    fn place_of(&self, id: u64) -> Option<usize> {
+        return self
+            .seats
+            .iter()
+            .position(|seat| seat.as_ref().is_some_and(|s| s.id == id));
+    }
+
+    // Only called for taken places.
+    fn seat(&mut self, place: usize) -> &mut Seat {
+        return self.seats[place].as_mut().unwrap();
+    }
+
+    fn send(&mut self, place: usize, message: &ServerMessage) {
+        let failed = match self.seats[place].as_ref().and_then(|seat| seat.outbox.as_ref()) {
+            Some(outbox) => outbox.try_send(message.clone()).is_err(),
+            None => false,
+        };
+        if failed {
+            self.disconnect(place);
+        }
+    }
+
+    // Closes the connection once the messages sent so far are written.
+    fn close(&mut self, place: usize) {
+        if let Some(seat) = &mut self.seats[place] {
+            seat.outbox = None;
+        }
+    }
+
+    // Right away, for a player too far behind to wait for. The reader thread
+    // notices and reports the player as gone.
+    fn disconnect(&mut self, place: usize) {
+        if let Some(seat) = &self.seats[place] {
+            let _ = seat.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(addr: &str, name: &str, version: u32) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let hello = ClientMessage::Hello {
+            name: String::from(name),
+            version,
+        };
+        write_frame(&mut stream, &hello).unwrap();
+        return stream;
+    }
+
+    fn next(stream: &mut TcpStream) -> ServerMessage {
+        return read_frame(stream).unwrap().expect("a message");
+    }
+
+    #[test]
+    fn greets_players_and_turns_away_other_versions() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || serve(listener));
+
+        let mut first = connect(&addr, "first", PROTOCOL_VERSION);
+        assert!(matches!(next(&mut first), ServerMessage::Welcome { player: 0 }));
+        match next(&mut first) {
+            ServerMessage::Lobby { players } => assert_eq!(players[0].as_ref().unwrap().name, "first"),
+            message => panic!("{:?}", message),
+        }
+
+        let mut second = connect(&addr, "second", PROTOCOL_VERSION + 1);
+        assert!(matches!(next(&mut second), ServerMessage::Welcome { player: 1 }));
+        match next(&mut second) {
+            ServerMessage::Error { reason } => assert!(reason.contains("protocol version")),
+            message => panic!("{:?}", message),
+        }
+        // the connection closes after the error
+        assert!(!matches!(read_frame::<_, ServerMessage>(&mut second), Ok(Some(_))));
+    }
+}
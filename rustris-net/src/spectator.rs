@@ -0,0 +1,335 @@
+// A read-only feed of a running game. The playing side publishes a snapshot
+// whenever something visible changed; watchers connect over TCP and get the
+// snapshots as frames like the ones of `protocol`, starting with the current
+// one.
+
+use crate::protocol::{decode_board, decode_cell, encode_board, encode_cell, read_frame, write_frame};
+use rustris_core::game::Game;
+use rustris_core::model::{Board, Stats, Tetromino, BOARD_DIM_Y};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GameSnapshot {
+    // see `encode_board`
+    pub board: Vec<String>,
+    // the box of the falling piece, encoded like the board
+    pub piece: Vec<String>,
+    pub piece_offset: (i32, i32),
+    pub next: Vec<String>,
+    // the pieces after `next`, and the one in the hold slot, encoded like `next`
+    #[serde(default)]
+    pub preview: Vec<Vec<String>>,
+    #[serde(default)]
+    pub hold: Option<Vec<String>>,
+    pub cleared: i32,
+    #[serde(default)]
+    pub clr_cmb_5: i32,
+    pub clr_cmb_4: i32,
+    pub clr_cmb_3: i32,
+    pub clr_cmb_2: i32,
+    pub clr_cmb_1: i32,
+    #[serde(default)]
+    pub chains: i32,
+    pub score: i32,
+    pub level: i32,
+    pub game_over: bool,
+    // the status lines of the game mode, e.g. the lines left to a goal
+    #[serde(default)]
+    pub mode: Vec<String>,
+}
+
+impl GameSnapshot {
+    pub fn of(game: &Game, mode: &[String]) -> GameSnapshot {
+        let stats = game.stats();
+        let scaled = |piece: &Tetromino| encode_piece(piece, piece.scale);
+        return GameSnapshot {
+            board: encode_board(&visible_cells(game.current_board())),
+            piece: encode_piece(game.active_piece(), 1),
+            piece_offset: game.active_piece().offset,
+            next: scaled(game.next_piece()),
+            preview: game.preview_pieces().skip(1).map(scaled).collect(),
+            hold: game.held_tetromino().map(scaled),
+            cleared: stats.cleared,
+            clr_cmb_5: stats.clr_cmb_5,
+            clr_cmb_4: stats.clr_cmb_4,
+            clr_cmb_3: stats.clr_cmb_3,
+            clr_cmb_2: stats.clr_cmb_2,
+            clr_cmb_1: stats.clr_cmb_1,
+            chains: stats.chains,
+            score: stats.score,
+            level: stats.level,
+            game_over: game.is_game_over(),
+            mode: mode.to_vec(),
+        };
+    }
+
+    pub fn piece(&self) -> Tetromino {
+        let mut piece = decode_piece(&self.piece);
+        piece.offset = self.piece_offset;
+        return piece;
+    }
+
+    pub fn next_piece(&self) -> Tetromino {
+        return decode_piece(&self.next);
+    }
+
+    pub fn preview_pieces(&self) -> Vec<Tetromino> {
+        return self.preview.iter().map(|rows| decode_piece(rows)).collect();
+    }
+
+    pub fn held_piece(&self) -> Option<Tetromino> {
+        return self.hold.as_ref().map(|rows| decode_piece(rows));
+    }
+
+    pub fn board(&self) -> Board {
+        return decode_board(&self.board);
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::new();
+        stats.cleared = self.cleared;
+        stats.clr_cmb_5 = self.clr_cmb_5;
+        stats.clr_cmb_4 = self.clr_cmb_4;
+        stats.clr_cmb_3 = self.clr_cmb_3;
+        stats.clr_cmb_2 = self.clr_cmb_2;
+        stats.clr_cmb_1 = self.clr_cmb_1;
+        stats.chains = self.chains;
+        stats.score = self.score;
+        stats.level = self.level;
+        return stats;
+    }
+}
+
+// Watchers see the board as the player does, without the hidden cells.
+fn visible_cells(board: &Board) -> Board {
+    let mut shown = Board::new();
+    for y in 0..board.height() {
+        for x in 0..board.width() {
+            if board.is_visible(x, y) {
+                shown.set(x, y, board.at(x, y));
+            }
+        }
+    }
+    return shown;
+}
+
+// Every `step`th cell, so big pieces can be shown at their normal size.
+fn encode_piece(piece: &Tetromino, step: i32) -> Vec<String> {
+    return (0..piece.height() / step)
+        .map(|y| {
+            (0..piece.width() / step)
+                .map(|x| encode_cell(piece.at(x * step, y * step)))
+                .collect()
+        })
+        .collect();
+}
+
+fn decode_piece(rows: &[String]) -> Tetromino {
+    let mut piece = Tetromino::new();
+    // no box in play is bigger than the board
+    piece.resize(rows.len().clamp(1, BOARD_DIM_Y) as i32);
+    for (y, row) in rows.iter().enumerate().take(piece.height() as usize) {
+        for (x, cell) in row.chars().enumerate().take(piece.width() as usize) {
+            piece.set(x as i32, y as i32, decode_cell(cell));
+        }
+    }
+    return piece;
+}
+
+// The publishing side. Every watcher is written to by a thread of its own,
+// so a slow watcher never holds up the game.
+pub struct SpectatorFeed {
+    listener: TcpListener,
+    joined: Arc<Mutex<Vec<Sender<Arc<GameSnapshot>>>>>,
+    watchers: Vec<Sender<Arc<GameSnapshot>>>,
+    last: Option<Arc<GameSnapshot>>,
+}
+
+impl SpectatorFeed {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<SpectatorFeed> {
+        let listener = TcpListener::bind(addr)?;
+        let accept_listener = listener.try_clone()?;
+        let joined = Arc::new(Mutex::new(Vec::new()));
+        let accept_joined = joined.clone();
+        std::thread::spawn(move || {
+            for stream in accept_listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let (sender, snapshots) = channel();
+                std::thread::spawn(move || watch(stream, snapshots));
+                accept_joined.lock().unwrap().push(sender);
+            }
+        });
+        return Ok(SpectatorFeed {
+            listener,
+            joined,
+            watchers: Vec::new(),
+            last: None,
+        });
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    pub fn watchers(&self) -> usize {
+        return self.watchers.len();
+    }
+
+    // Cheap to call every frame, only changes are sent.
+    pub fn publish(&mut self, game: &Game, mode: &[String]) {
+        let joined: Vec<_> = self.joined.lock().unwrap().drain(..).collect();
+        if joined.is_empty() && self.watchers.is_empty() {
+            self.last = None;
+            return;
+        }
+
+        let snapshot = GameSnapshot::of(game, mode);
+        let changed = self.last.as_ref().is_none_or(|last| **last != snapshot);
+        if changed {
+            self.last = Some(Arc::new(snapshot));
+        }
+        let last = self.last.as_ref().unwrap();
+        if changed {
+            // gone watchers have dropped their receiver
+            self.watchers.retain(|watcher| watcher.send(last.clone()).is_ok());
+        }
+        for watcher in joined {
+            if watcher.send(last.clone()).is_ok() {
+                self.watchers.push(watcher);
+            }
+        }
+    }
+}
+
+fn watch(mut stream: TcpStream, snapshots: Receiver<Arc<GameSnapshot>>) {
+    let _ = stream.set_nodelay(true);
+    while let Ok(mut snapshot) = snapshots.recv() {
+        // only the newest one matters to a watcher that fell behind
+        while let Ok(newer) = snapshots.try_recv() {
+            snapshot = newer;
+        }
+        if write_frame(&mut stream, &*snapshot).is_err() {
+            break;
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+// The watching side, read on a thread of its own like `NetClient`.
+pub struct SpectatorClient {
+    stream: TcpStream,
+    snapshots: Receiver<GameSnapshot>,
+    connected: bool,
+}
+
+impl SpectatorClient {
+    pub fn connect(addr: &str) -> io::Result<SpectatorClient> {
+        let stream = TcpStream::connect(addr)?;
+        let mut reader = stream.try_clone()?;
+        let (sender, snapshots) = channel();
+        std::thread::spawn(move || {
+            while let Ok(Some(snapshot)) = read_frame::<_, GameSnapshot>(&mut reader) {
+                if sender.send(snapshot).is_err() {
+                    break;
+                }
+            }
+        });
+        return Ok(SpectatorClient {
+            stream,
+            snapshots,
+            connected: true,
+        });
+    }
+
+    // The newest snapshot that arrived since the last poll.
+    pub fn poll(&mut self) -> Option<GameSnapshot> {
+        let mut newest = None;
+        loop {
+            match self.snapshots.try_recv() {
+                Ok(snapshot) => newest = Some(snapshot),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break;
+                }
+            }
+        }
+        return newest;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        return self.connected;
+    }
+}
+
+impl Drop for SpectatorClient {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustris_core::model::{CellVal, UserInput};
+    use rustris_core::ruleset::Ruleset;
+
+    fn cells(piece: &Tetromino) -> Vec<(i32, i32, CellVal)> {
+        let mut cells = Vec::new();
+        for y in 0..piece.height() {
+            for x in (0..piece.width()).filter(|x| piece.at(*x, y) != CellVal::Free) {
+                cells.push((x, y, piece.at(x, y)));
+            }
+        }
+        return cells;
+    }
+
+    #[test]
+    fn snapshots_survive_the_wire() {
+        let mut game = Game::with_seed(3);
+        game.set_ruleset(Ruleset::guideline());
+        game.handle_input(&UserInput::Hold);
+        for _ in 0..6 {
+            game.handle_input(&UserInput::MoveLeft);
+            game.handle_input(&UserInput::DropDown);
+        }
+        let mode = vec![String::from(" Lines left:  34")];
+        let snapshot = GameSnapshot::of(&game, &mode);
+
+        let mut frame = Vec::new();
+        write_frame(&mut frame, &snapshot).unwrap();
+        let received: GameSnapshot = read_frame(&mut frame.as_slice()).unwrap().unwrap();
+        assert_eq!(received, snapshot);
+
+        let (board, shown) = (received.board(), visible_cells(game.current_board()));
+        assert_eq!(board.cells, shown.cells);
+        // more than one color made it
+        let colors: Vec<CellVal> = board
+            .cells
+            .iter()
+            .flatten()
+            .filter(|c| **c != CellVal::Free)
+            .cloned()
+            .collect();
+        assert!(colors.iter().any(|color| *color != colors[0]));
+        assert_eq!(cells(&received.piece()), cells(game.active_piece()));
+        assert_eq!(received.piece().offset, game.active_piece().offset);
+        assert_eq!(cells(&received.next_piece()), cells(game.next_piece()));
+        let preview: Vec<_> = received.preview_pieces().iter().map(cells).collect();
+        let expected: Vec<_> = game.preview_pieces().skip(1).map(cells).collect();
+        assert_eq!(preview, expected);
+        assert_eq!(
+            cells(&received.held_piece().unwrap()),
+            cells(game.held_tetromino().unwrap())
+        );
+        assert_eq!(received.mode, mode);
+        assert_eq!(received.stats().score, game.stats().score);
+    }
+}
@@ -1,8 +1,15 @@
 extern crate console_error_panic_hook;
 extern crate wasm_bindgen;
 
-use rustris_core::game::Game;
-use rustris_core::model::{CellVal, UserInput};
+use rustris_core::game::{Game, FRAME_TIME};
+use rustris_core::invisible::Invisible;
+use rustris_core::mode::{Endless, Outcome};
+use rustris_core::model::{CellVal, ClearEvent, Tetromino, UserInput};
+use rustris_core::records::Records;
+use rustris_core::ruleset::Ruleset;
+use rustris_core::sprint::Sprint;
+use rustris_core::view::View;
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -18,6 +25,14 @@ extern "C" {
 #[wasm_bindgen]
 pub struct WasmAPI {
     game: Game,
+    // of the coming frame, see `advance_timer`
+    frame_time: Duration,
+    records: Records,
+    // if the finished sprint was submitted
+    recorded: bool,
+    new_best: bool,
+    // the board and pieces are seen through it, and the inputs are turned
+    view: View,
 }
 
 fn to_js_color(color: &CellVal) -> i32 {
@@ -31,14 +46,137 @@ fn to_js_color(color: &CellVal) -> i32 {
         CellVal::Color6 => 6,
         CellVal::Color7 => 7,
         CellVal::Color8 => 8,
+        CellVal::Garbage => 9,
+    }
+}
+
+impl Default for WasmAPI {
+    fn default() -> WasmAPI {
+        return WasmAPI::new("rustris");
     }
 }
 
 #[wasm_bindgen]
 impl WasmAPI {
-    pub fn new() -> WasmAPI {
+    // `rules` is one of `Ruleset::NAMES`; others play by the default rules.
+    pub fn new(rules: &str) -> WasmAPI {
         console_error_panic_hook::set_once();
-        return WasmAPI { game: Game::new() };
+        let ruleset = Ruleset::by_name(rules).unwrap_or_else(|| {
+            log_many("unknown rules, playing by the default ones:", rules);
+            Ruleset::default()
+        });
+        let mut game = Game::new();
+        game.set_ruleset(ruleset);
+        return WasmAPI {
+            game,
+            frame_time: FRAME_TIME,
+            records: Records::new(),
+            recorded: false,
+            new_best: false,
+            view: View::default(),
+        };
+    }
+
+    // Starts over with a sprint of `lines` lines.
+    pub fn start_sprint(&mut self, lines: i32) {
+        self.game.set_mode(Box::new(Sprint::new(lines)));
+        self.reset();
+    }
+
+    // Starts over without a goal.
+    pub fn start_endless(&mut self) {
+        self.game.set_mode(Box::new(Endless));
+        self.reset();
+    }
+
+    // Starts over with locked blocks disappearing after `fade_ms`, right
+    // away with 0.
+    pub fn start_invisible(&mut self, fade_ms: u32) {
+        self.game
+            .set_mode(Box::new(Invisible::new(Duration::from_millis(fade_ms as u64))));
+        self.reset();
+    }
+
+    // Groups of `size` or more connected blocks of one color clear too, see
+    // `Ruleset::color_match`; 0 turns it off. Starts over, and returns false
+    // without a change if the pieces themselves are that big.
+    pub fn set_color_match(&mut self, size: u32) -> bool {
+        let mut ruleset = self.game.ruleset().clone();
+        if size > 0 && size as usize <= ruleset.rotation.largest_piece() {
+            return false;
+        }
+        ruleset.color_match = match size {
+            0 => None,
+            size => Some(size as usize),
+        };
+        self.game.set_ruleset(ruleset);
+        self.reset();
+        return true;
+    }
+
+    // Shows the board mirrored or upside down, with the keys to match. The
+    // game plays on as it was.
+    pub fn set_view(&mut self, mirrored: bool, upside_down: bool) {
+        self.view = View::new(mirrored, upside_down);
+    }
+
+    pub fn is_mirrored(&self) -> bool {
+        return self.view.mirrored;
+    }
+
+    pub fn is_upside_down(&self) -> bool {
+        return self.view.upside_down;
+    }
+
+    // The time the last frame took, from `requestAnimationFrame` timestamps.
+    // It is counted by the next `run_step`.
+    pub fn advance_timer(&mut self, elapsed_ms: f64) {
+        self.frame_time = Duration::from_secs_f64(elapsed_ms.max(0.0) / 1000.0);
+    }
+
+    pub fn is_sprint(&self) -> bool {
+        return self.sprint().is_some();
+    }
+
+    pub fn is_sprint_finished(&self) -> bool {
+        return self.is_sprint() && self.game.outcome() == Some(Outcome::Won);
+    }
+
+    pub fn sprint_time_ms(&self) -> f64 {
+        return self
+            .sprint()
+            .map_or(0.0, |sprint| sprint.elapsed().as_secs_f64() * 1000.0);
+    }
+
+    pub fn sprint_lines_left(&self) -> i32 {
+        return self.sprint().map_or(0, |sprint| sprint.lines_left(&self.game));
+    }
+
+    // -1 without a record for the current line target and rules.
+    pub fn sprint_best_ms(&self) -> f64 {
+        let rules = self.game.ruleset().record_key();
+        let best = self
+            .sprint()
+            .and_then(|sprint| self.records.sprint_best(&rules, sprint.target()));
+        return best.map_or(-1.0, |best| best.as_secs_f64() * 1000.0);
+    }
+
+    // If the finished sprint beat the personal best.
+    pub fn is_new_best(&self) -> bool {
+        return self.new_best;
+    }
+
+    pub fn pieces(&self) -> i32 {
+        return self.game.stats().pieces;
+    }
+
+    // Personal bests as text, for the page to keep in `localStorage`.
+    pub fn records(&self) -> String {
+        return self.records.to_string();
+    }
+
+    pub fn load_records(&mut self, text: &str) {
+        self.records = Records::parse(text);
     }
 
     pub fn print_info(&mut self) {
@@ -51,7 +189,12 @@ impl WasmAPI {
         cc1 += self.game.active_piece().change_count;
         cc1 += self.game.next_piece().change_count;
 
-        self.game.run_step();
+        self.game.run_step_timed(self.frame_time);
+        self.record();
+        // without a goal, a lost game starts over right away
+        if self.game.is_game_over() && self.game.mode_as::<Endless>().is_some() {
+            self.reset();
+        }
         let mut cc2 = 0;
         cc2 += self.game.current_board().change_count;
         cc2 += self.game.active_piece().change_count;
@@ -60,47 +203,130 @@ impl WasmAPI {
         return cc1 != cc2;
     }
 
+    // -1 for free cells and ones hidden by the invisible mode. Like the
+    // pieces, in the coordinates of the view.
     pub fn board_color_at(&self, x: i32, y: i32) -> i32 {
-        return to_js_color(&self.game.current_board().at(x, y));
+        let board = self.game.current_board();
+        let (x, y) = self.view.cell(x, y, board);
+        return match board.is_visible(x, y) {
+            true => to_js_color(&board.at(x, y)),
+            false => -1,
+        };
+    }
+
+    // The cells of the color groups the last lock cleared, as x and y pairs in
+    // the coordinates of the view. Empty once taken.
+    pub fn take_cleared_groups(&mut self) -> Vec<i32> {
+        let events = self.game.take_clear_events();
+        let board = self.game.current_board();
+        let mut cells = Vec::new();
+        for event in events {
+            if let ClearEvent::Group { cells: group, .. } = event {
+                for (x, y) in group {
+                    let (x, y) = self.view.cell(x, y, board);
+                    cells.push(x);
+                    cells.push(y);
+                }
+            }
+        }
+        return cells;
     }
 
     pub fn active_piece_at(&self, x: i32, y: i32) -> i32 {
-        return to_js_color(&self.game.active_piece().at(x, y));
+        return to_js_color(&self.active_piece().at(x, y));
     }
 
     pub fn active_piece_x(&self) -> i32 {
-        return self.game.active_piece().offset.0;
+        return self.active_piece().offset.0;
     }
 
     pub fn active_piece_y(&self) -> i32 {
-        return self.game.active_piece().offset.1;
+        return self.active_piece().offset.1;
     }
 
     pub fn next_piece_at(&self, x: i32, y: i32) -> i32 {
-        return to_js_color(&self.game.next_piece().at(x, y));
+        let next = self.view.piece(self.game.next_piece(), self.game.current_board());
+        return to_js_color(&next.at(x, y));
+    }
+
+    pub fn has_hold(&self) -> bool {
+        return self.game.ruleset().hold;
+    }
+
+    // "I", "T" and so on, or "" with an empty hold slot.
+    pub fn held_piece(&self) -> String {
+        let rotation = &self.game.ruleset().rotation;
+        return self
+            .game
+            .held_piece()
+            .map_or(String::new(), |t_type| String::from(rotation.piece_name(t_type)));
+    }
+
+    pub fn level(&self) -> i32 {
+        return self.game.stats().level;
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        return self.game.is_game_over();
+    }
+
+    pub fn reset(&mut self) {
+        self.recorded = false;
+        self.new_best = false;
+        self.game.handle_input(&UserInput::Reset);
     }
 
     pub fn move_left(&mut self) {
-        self.game.handle_input(&UserInput::MoveLeft);
+        self.play(UserInput::MoveLeft);
     }
 
     pub fn move_right(&mut self) {
-        self.game.handle_input(&UserInput::MoveRight);
+        self.play(UserInput::MoveRight);
     }
 
     pub fn drop_down(&mut self) {
-        self.game.handle_input(&UserInput::DropDown);
+        self.play(UserInput::DropDown);
     }
 
     pub fn move_down(&mut self) {
-        self.game.handle_input(&UserInput::MoveDown);
+        self.play(UserInput::MoveDown);
     }
 
     pub fn rotate_left(&mut self) {
-        self.game.handle_input(&UserInput::RotateLeft);
+        self.play(UserInput::RotateLeft);
     }
 
     pub fn rotate_right(&mut self) {
-        self.game.handle_input(&UserInput::RotateRight);
+        self.play(UserInput::RotateRight);
+    }
+
+    pub fn hold(&mut self) {
+        self.play(UserInput::Hold);
+    }
+}
+
+impl WasmAPI {
+    fn play(&mut self, input: UserInput) {
+        self.game.handle_input(&self.view.input(input));
+        self.record();
+    }
+
+    fn active_piece(&self) -> Tetromino {
+        return self.view.piece(self.game.active_piece(), self.game.current_board());
+    }
+
+    fn sprint(&self) -> Option<&Sprint> {
+        return self.game.mode_as::<Sprint>();
+    }
+
+    // Submits a sprint once it is finished.
+    fn record(&mut self) {
+        if self.recorded || !self.is_sprint_finished() {
+            return;
+        }
+        let rules = self.game.ruleset().record_key();
+        let sprint = self.sprint().unwrap();
+        self.new_best = self.records.submit_sprint(&rules, sprint.target(), sprint.elapsed());
+        self.recorded = true;
     }
 }
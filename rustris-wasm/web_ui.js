@@ -3,8 +3,16 @@ var api = null;
 import init, { WasmAPI } from "./pkg/rustris_wasm.js";
 init()
     .then(() => {
-        api = WasmAPI.new();
+        // rustris.html?rules=guideline, see `Ruleset::NAMES`, and &colors=8
+        // to clear groups of 8 blocks of one color
+        let params = new URLSearchParams(window.location.search);
+        api = WasmAPI.new(params.get("rules") || "rustris");
+        let colors = parseInt(params.get("colors") || "0");
+        if (colors > 0 && !api.set_color_match(colors)) {
+            console.log("color groups must be bigger than the pieces:", colors);
+        }
         api.print_info();
+        api.load_records(localStorage.getItem("rustris-records") || "");
     });
 
 const renderer = new THREE.WebGLRenderer();
@@ -27,8 +35,12 @@ var material = {
     5: new THREE.MeshBasicMaterial({ color: '#888888' }),
     6: new THREE.MeshBasicMaterial({ color: '#777777' }),
     7: new THREE.MeshBasicMaterial({ color: '#666666' }),
-    8: new THREE.MeshBasicMaterial({ color: '#555555' })
+    8: new THREE.MeshBasicMaterial({ color: '#555555' }),
+    9: new THREE.MeshBasicMaterial({ color: '#333333' }),
+    // cleared color groups, for `FLASH_FRAMES`
+    10: new THREE.MeshBasicMaterial({ color: '#FFFFFF' })
 };
+const FLASHED = 10;
 
 function onDocumentKeyDown(event) {
     switch (event.code) {
@@ -37,6 +49,19 @@ function onDocumentKeyDown(event) {
         case "KeyW": api.drop_down(); break;
         case "KeyQ": api.rotate_left(); break;
         case "KeyE": api.rotate_right(); break;
+        case "KeyC": api.hold(); break;
+        case "KeyN": api.reset(); break;
+        case "Digit1": api.start_endless(); break;
+        case "Digit2": api.start_sprint(40); break;
+        case "Digit3": api.start_invisible(3000); break;
+        case "KeyM":
+            api.set_view(!api.is_mirrored(), api.is_upside_down());
+            viewChanged = true;
+            break;
+        case "KeyU":
+            api.set_view(api.is_mirrored(), !api.is_upside_down());
+            viewChanged = true;
+            break;
         case "ControlLeft":
         case "Space":
             api.move_down();
@@ -110,18 +135,77 @@ for (let y = 0; y < fgBlocks.length; y++) {
     }
 }
 
-const gameLoop = function () {
+const info = document.getElementById("info");
+var lastTimestamp = null;
+var savedRecords = false;
+// the blocks are placed anew, even if nothing changed in the game
+var viewChanged = false;
+// how long cleared color groups flash
+const FLASH_FRAMES = 20;
+// "x,y" of the cells flashing, and the frames until they show as they are
+// again; -1 once they do
+var flashed = new Set();
+var flashFrames = -1;
+
+function formatTime(ms) {
+    let seconds = Math.floor(ms / 1000);
+    let millis = String(Math.floor(ms) % 1000).padStart(3, "0");
+    return Math.floor(seconds / 60) + ":" + String(seconds % 60).padStart(2, "0") + "." + millis;
+}
+
+function updateInfo() {
+    let hold = api.has_hold() ? ", hold " + (api.held_piece() || "-") : "";
+    if (!api.is_sprint()) {
+        info.textContent = "Level " + api.level() + hold + " - 1 endless, 2 sprint 40 lines, 3 invisible, m mirror, u upside down";
+        return;
+    }
+    let best = api.sprint_best_ms();
+    let text = "Sprint: " + api.sprint_lines_left() + " lines left, " + formatTime(api.sprint_time_ms())
+        + ", best " + (best < 0 ? "-:--.---" : formatTime(best)) + hold;
+    if (api.is_sprint_finished()) {
+        text = "SPRINT COMPLETE " + formatTime(api.sprint_time_ms()) + ", " + api.pieces() + " pieces"
+            + (api.is_new_best() ? ", NEW PERSONAL BEST" : "") + " - n to play again";
+        if (!savedRecords) {
+            localStorage.setItem("rustris-records", api.records());
+            savedRecords = true;
+        }
+    } else {
+        savedRecords = false;
+    }
+    info.textContent = text;
+}
+
+const gameLoop = function (timestamp) {
     requestAnimationFrame(gameLoop);
     if (!api) {
         return; // wait for wasm to be initialized
     }
 
-    let needUpdate = api.run_step();
+    if (lastTimestamp !== null) {
+        api.advance_timer(timestamp - lastTimestamp);
+    }
+    lastTimestamp = timestamp;
+    let needUpdate = api.run_step() || viewChanged;
+    viewChanged = false;
+    updateInfo();
+
+    let cleared = api.take_cleared_groups();
+    if (cleared.length > 0) {
+        flashed = new Set();
+        for (let i = 0; i < cleared.length; i += 2) {
+            flashed.add(cleared[i] + "," + cleared[i + 1]);
+        }
+        flashFrames = FLASH_FRAMES;
+    }
+    if (flashFrames >= 0) {
+        needUpdate = true;
+    }
 
     for (let y = 0; y < bgBlocks.length; y++) {
         for (let x = 0; x < bgBlocks[y].length; x++) {
             if (needUpdate) {
-                bgBlocks[y][x].setColor(api.board_color_at(x, y));
+                let flash = flashFrames > 0 && flashed.has(x + "," + y);
+                bgBlocks[y][x].setColor(flash ? FLASHED : api.board_color_at(x, y));
             }
 
             bgBlocks[y][x].animate();
@@ -141,6 +225,10 @@ const gameLoop = function () {
         }
     }
 
+    if (flashFrames >= 0) {
+        flashFrames--;
+    }
+
     renderer.render(scene, camera);
 };
 